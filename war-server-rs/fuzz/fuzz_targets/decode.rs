@@ -0,0 +1,20 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+use war_protocol::*;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok((message, len)) = Message::decode(data) {
+        let (buf, encoded_len) = message.encode();
+        assert_eq!(&buf[..encoded_len], &data[..len]);
+    }
+    if let Ok(message) = Message::try_from(data) {
+        let (buf, len) = message.encode();
+        assert_eq!(&buf[..len], data);
+    }
+    for message in Message::decode_stream(data).flatten() {
+        assert!(message.wire_len() <= MAX_MESSAGE_SIZE);
+    }
+    let _ = Message::from_json(data);
+});
@@ -0,0 +1,28 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+use war_protocol::*;
+
+// Starts from valid messages instead of bytes, so the encoder gets exercised
+// too.
+fuzz_target!(|message: Message| {
+    let (buf, len) = message.encode();
+    let decoded = Message::try_from(&buf[..len]).expect("Encoded messages always decode.");
+    let (again, again_len) = decoded.encode();
+    assert_eq!(&again[..again_len], &buf[..len]);
+
+    for wire in [WireVersion::V1, WireVersion::V2] {
+        let (frame, frame_len) = wire.encode(&message);
+        assert!(frame_len <= MAX_V2_FRAME_LEN);
+        assert!(frame[..frame_len].ends_with(&buf[..len]));
+    }
+    let (frame, frame_len) = WireVersion::V2Checked.encode(&message);
+    assert!(frame_len <= MAX_FRAME_LEN);
+    let (checked, checksum) = frame[..frame_len].split_at(frame_len - CHECKSUM_LEN);
+    assert!(checked.ends_with(&buf[..len]));
+    assert_eq!(checksum, [crc8(checked)]);
+    let json = message.to_json();
+    let from_json = Message::from_json(json.as_bytes()).expect("JSON always parses back.");
+    assert_eq!(from_json.encode(), (buf, len));
+});
@@ -0,0 +1,518 @@
+//! Listening for connections, accepting them, and riding out the ways that
+//! can fail without taking every game in progress down with the server.
+
+use std::{io, net::SocketAddr, num::NonZeroUsize, str::FromStr, time::Duration};
+#[cfg(unix)]
+use std::{os::unix::fs::FileTypeExt, path::Path};
+
+use socket2::{SockRef, TcpKeepalive};
+#[cfg(unix)]
+use tokio::net::{UnixListener, UnixStream};
+use tokio::{
+    net::{TcpListener, TcpSocket, TcpStream},
+    time::Instant,
+};
+use tokio_stream::{Stream, StreamExt};
+use tracing::warn;
+
+/// How long to wait before trying to accept again, after the first failure in
+/// a row, and at most, however many there are.
+const MIN_BACKOFF: Duration = Duration::from_millis(10);
+const MAX_BACKOFF: Duration = Duration::from_secs(1);
+
+/// How often to say that accepting is still failing, at most.
+const WARN_EVERY: Duration = Duration::from_secs(5);
+
+/// How many connections can be waiting to be accepted, which is the same as
+/// what `TcpListener::bind` asks for.
+const BACKLOG: u32 = 1024;
+
+#[derive(thiserror::Error, Debug)]
+pub enum BindError {
+    #[error("Couldn't look up {host:?}: {error}")]
+    Lookup { host: String, error: io::Error },
+    #[error("{host:?} doesn't have any addresses")]
+    NoAddresses { host: String },
+    #[error("Couldn't listen on any of {host:?}'s addresses: {}", failures(.tried))]
+    Unavailable {
+        host: String,
+        tried: Vec<(SocketAddr, io::Error)>,
+    },
+}
+
+/// Every address that was tried, and why it didn't work, like
+/// `127.0.0.1:4444 (Address in use), ...`.
+fn failures(tried: &[(SocketAddr, io::Error)]) -> String {
+    let failures: Vec<_> = tried
+        .iter()
+        .map(|(addr, error)| format!("{addr} ({error})"))
+        .collect();
+    failures.join(", ")
+}
+
+/// Somewhere to listen, written like `localhost:4444`, `10.0.0.5:4444`, or
+/// `[::1]:4444`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ListenAddr {
+    /// Anything [`bind`] takes.
+    pub host: String,
+    pub port: u16,
+}
+
+impl FromStr for ListenAddr {
+    type Err = String;
+
+    fn from_str(addr: &str) -> Result<ListenAddr, String> {
+        let Some((host, port)) = addr.rsplit_once(':') else {
+            return Err("expected a host and a port, like localhost:4444".to_string());
+        };
+        if host.is_empty() {
+            return Err("missing the host before the port".to_string());
+        }
+        if host.contains(':') && !(host.starts_with('[') && host.ends_with(']')) {
+            return Err("IPv6 addresses need brackets around them, like [::1]:4444".to_string());
+        }
+        let port = port
+            .parse()
+            .map_err(|_| format!("{port:?} isn't a port number"))?;
+        Ok(ListenAddr {
+            host: host.to_string(),
+            port,
+        })
+    }
+}
+
+/// Whether more than one socket can listen on the same address, with the
+/// kernel spreading connections out between them (with `SO_REUSEPORT`).
+pub const CAN_SHARE_ADDRS: bool = cfg!(target_os = "linux");
+
+/// How to listen, wherever it is.
+#[derive(Debug, Clone, Copy)]
+pub struct ListenOptions {
+    /// Whether an IPv6 address also takes IPv4 connections, wherever the OS
+    /// would otherwise only take IPv6 ones by default. That way, `::` takes
+    /// every connection, whichever family it's from.
+    pub dual_stack: bool,
+    /// How many sockets listen on each address, each to be accepted from on
+    /// its own. Anything but one needs [`CAN_SHARE_ADDRS`].
+    pub acceptors: NonZeroUsize,
+}
+
+impl Default for ListenOptions {
+    fn default() -> ListenOptions {
+        ListenOptions {
+            dual_stack: false,
+            acceptors: NonZeroUsize::MIN,
+        }
+    }
+}
+
+/// Listens on `port` at the first of `host`'s addresses that'll have it, in
+/// the order they're looked up in, with as many sockets as there are
+/// `options.acceptors`. `host` can be a name to look up, or an IP address,
+/// with or without brackets around it if it's IPv6.
+pub async fn bind(
+    host: &str,
+    port: u16,
+    options: ListenOptions,
+) -> Result<Vec<TcpListener>, BindError> {
+    let unbracketed = host
+        .strip_prefix('[')
+        .and_then(|host| host.strip_suffix(']'))
+        .unwrap_or(host);
+    let addrs = tokio::net::lookup_host((unbracketed, port))
+        .await
+        .map_err(|error| BindError::Lookup {
+            host: host.to_string(),
+            error,
+        })?;
+    let mut tried = Vec::new();
+    for addr in addrs {
+        match bind_addr(addr, options) {
+            Ok(listeners) => return Ok(listeners),
+            Err(error) => tried.push((addr, error)),
+        }
+    }
+    let host = host.to_string();
+    if tried.is_empty() {
+        return Err(BindError::NoAddresses { host });
+    }
+    Err(BindError::Unavailable { host, tried })
+}
+
+/// Listens on `addr` with every socket, all bound to exactly where the first
+/// one was, even if the OS picked its port.
+fn bind_addr(addr: SocketAddr, options: ListenOptions) -> io::Result<Vec<TcpListener>> {
+    let first = bind_socket(addr, options)?;
+    let addr = first.local_addr()?;
+    let mut listeners = vec![first];
+    for _ in 1..options.acceptors.get() {
+        listeners.push(bind_socket(addr, options)?);
+    }
+    Ok(listeners)
+}
+
+fn bind_socket(addr: SocketAddr, options: ListenOptions) -> io::Result<TcpListener> {
+    let socket = if addr.is_ipv4() {
+        TcpSocket::new_v4()?
+    } else {
+        TcpSocket::new_v6()?
+    };
+    // Like TcpListener::bind, so restarting the server doesn't have to wait
+    // out the last one's connections.
+    #[cfg(not(windows))]
+    socket.set_reuseaddr(true)?;
+    if addr.is_ipv6() && options.dual_stack {
+        SockRef::from(&socket).set_only_v6(false)?;
+    }
+    #[cfg(target_os = "linux")]
+    if options.acceptors.get() > 1 {
+        socket.set_reuseport(true)?;
+    }
+    socket.bind(addr)?;
+    socket.listen(BACKLOG)
+}
+
+/// Listens on a Unix socket at `path`, first removing any socket left there
+/// by a server that never got to clean up after itself. A socket that's still
+/// being listened on is left alone.
+#[cfg(unix)]
+pub async fn bind_unix(path: &Path) -> io::Result<UnixListener> {
+    let is_socket = std::fs::symlink_metadata(path).is_ok_and(|meta| meta.file_type().is_socket());
+    if is_socket {
+        match UnixStream::connect(path).await {
+            Ok(_) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::AddrInUse,
+                    "another server is listening there",
+                ));
+            }
+            Err(error) if error.kind() == io::ErrorKind::ConnectionRefused => {
+                std::fs::remove_file(path)?;
+            }
+            // Binding will say what's wrong.
+            Err(_) => {}
+        }
+    }
+    UnixListener::bind(path)
+}
+
+/// What to set on every connection as soon as it's accepted.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SocketOptions {
+    /// How long a connection can sit idle before TCP starts checking the
+    /// other end is still there, if it should at all. Without this, a player
+    /// whose connection is dropped by a NAT box while they wait for an
+    /// opponent isn't noticed until something's sent to them.
+    pub keepalive: Option<Duration>,
+    /// Whether to send each message as soon as it's written, rather than
+    /// letting Nagle's algorithm hold small ones back to go out together.
+    pub nodelay: bool,
+}
+
+impl SocketOptions {
+    pub fn apply(&self, stream: &TcpStream) -> io::Result<()> {
+        if self.nodelay {
+            stream.set_nodelay(true)?;
+        }
+        if let Some(idle) = self.keepalive {
+            SockRef::from(stream).set_tcp_keepalive(&TcpKeepalive::new().with_time(idle))?;
+        }
+        Ok(())
+    }
+}
+
+/// Hands each connection that comes in on `incoming` to `welcome`, which
+/// should get it going on a task of its own, until there are no more, or
+/// accepting them is broken for good.
+///
+/// Usually, `incoming` is a `TcpListenerStream`. A connection that's gone
+/// before it's accepted is skipped. Anything else, most likely running out of
+/// file descriptors, is waited out, backing off a little more each time, since
+/// games ending frees them up again. Only a listener that's broken for good
+/// (`EINVAL`) gives up.
+pub async fn accept_all<C>(
+    mut incoming: impl Stream<Item = io::Result<C>> + Unpin,
+    mut welcome: impl FnMut(C),
+) -> io::Result<()> {
+    let mut backoff = MIN_BACKOFF;
+    let mut last_warned: Option<Instant> = None;
+    while let Some(accepted) = incoming.next().await {
+        let error = match accepted {
+            Ok(connection) => {
+                backoff = MIN_BACKOFF;
+                welcome(connection);
+                continue;
+            }
+            Err(error) => error,
+        };
+        match error.kind() {
+            io::ErrorKind::ConnectionAborted
+            | io::ErrorKind::ConnectionReset
+            | io::ErrorKind::ConnectionRefused
+            | io::ErrorKind::Interrupted => continue,
+            io::ErrorKind::InvalidInput => return Err(error),
+            _ => {}
+        }
+        if last_warned.is_none_or(|warned| warned.elapsed() >= WARN_EVERY) {
+            warn!(%error, ?backoff, "Couldn't accept a connection, trying again");
+            last_warned = Some(Instant::now());
+        }
+        tokio::time::sleep(backoff).await;
+        backoff = (backoff * 2).min(MAX_BACKOFF);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn out_of_files() -> io::Result<u32> {
+        Err(io::Error::other("Too many open files"))
+    }
+
+    /// Accepts everything in `incoming`, returning what was welcomed, how
+    /// long it took, and how it ended.
+    async fn accept(incoming: Vec<io::Result<u32>>) -> (Vec<u32>, Duration, io::Result<()>) {
+        let mut welcomed = Vec::new();
+        let started = Instant::now();
+        let ended = accept_all(tokio_stream::iter(incoming), |connection| {
+            welcomed.push(connection)
+        })
+        .await;
+        (welcomed, started.elapsed(), ended)
+    }
+
+    #[tokio::test]
+    async fn every_connection_is_welcomed_in_order() {
+        let (welcomed, _, ended) = accept((1..=5).map(Ok).collect()).await;
+        assert_eq!(welcomed, [1, 2, 3, 4, 5]);
+        assert!(ended.is_ok());
+    }
+
+    #[tokio::test]
+    async fn running_out_of_files_is_waited_out() {
+        let (welcomed, took, ended) = accept(vec![
+            out_of_files(),
+            out_of_files(),
+            out_of_files(),
+            Ok(1),
+            out_of_files(),
+            Ok(2),
+        ])
+        .await;
+        assert_eq!(welcomed, [1, 2]);
+        assert!(ended.is_ok());
+        // Backing off twice as long each time, until one gets through.
+        let waited = MIN_BACKOFF + MIN_BACKOFF * 2 + MIN_BACKOFF * 4 + MIN_BACKOFF;
+        assert!(took >= waited, "{took:?}");
+    }
+
+    #[tokio::test]
+    async fn connections_gone_before_theyre_accepted_are_skipped() {
+        let (welcomed, took, _) = accept(vec![
+            Err(io::ErrorKind::ConnectionAborted.into()),
+            Ok(1),
+            Err(io::ErrorKind::ConnectionReset.into()),
+            Ok(2),
+        ])
+        .await;
+        assert_eq!(welcomed, [1, 2]);
+        assert!(took < MIN_BACKOFF);
+    }
+
+    /// Both ends of a fresh loopback connection, the server's first.
+    async fn connected() -> (TcpStream, TcpStream) {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let client = TcpStream::connect(listener.local_addr().unwrap())
+            .await
+            .unwrap();
+        let (server, _) = listener.accept().await.unwrap();
+        (server, client)
+    }
+
+    #[tokio::test]
+    async fn keepalive_is_only_set_when_asked_for() {
+        let (server, _client) = connected().await;
+        SocketOptions::default().apply(&server).unwrap();
+        assert!(!SockRef::from(&server).keepalive().unwrap());
+
+        let idle = Duration::from_secs(42);
+        let options = SocketOptions {
+            keepalive: Some(idle),
+            ..SocketOptions::default()
+        };
+        options.apply(&server).unwrap();
+        let server = SockRef::from(&server);
+        assert!(server.keepalive().unwrap());
+        #[cfg(target_os = "linux")]
+        assert_eq!(server.tcp_keepalive_time().unwrap(), idle);
+    }
+
+    #[tokio::test]
+    async fn nodelay_is_only_set_when_asked_for() {
+        let (server, _client) = connected().await;
+        SocketOptions::default().apply(&server).unwrap();
+        assert!(!server.nodelay().unwrap());
+
+        let options = SocketOptions {
+            nodelay: true,
+            ..SocketOptions::default()
+        };
+        options.apply(&server).unwrap();
+        assert!(server.nodelay().unwrap());
+    }
+
+    #[tokio::test]
+    async fn names_and_addresses_can_both_be_listened_on() {
+        for (host, ip) in [
+            ("localhost", "127.0.0.1"),
+            ("127.0.0.1", "127.0.0.1"),
+            ("::1", "::1"),
+            ("[::1]", "::1"),
+        ] {
+            let listeners = bind(host, 0, ListenOptions::default()).await.unwrap();
+            assert_eq!(listeners.len(), 1);
+            assert_eq!(listeners[0].local_addr().unwrap().ip().to_string(), ip);
+        }
+    }
+
+    #[tokio::test]
+    async fn dual_stack_listeners_take_ipv4_connections_too() {
+        let dual_stack = ListenOptions {
+            dual_stack: true,
+            ..ListenOptions::default()
+        };
+        let [listener] = &bind("::", 0, dual_stack).await.unwrap()[..] else {
+            panic!("Expected one listener.");
+        };
+        assert!(!SockRef::from(&listener).only_v6().unwrap());
+        let port = listener.local_addr().unwrap().port();
+        for client in ["127.0.0.1", "::1"] {
+            TcpStream::connect((client, port)).await.unwrap();
+            let (_, addr) = listener.accept().await.unwrap();
+            assert_eq!(addr.ip().to_canonical().to_string(), client);
+        }
+        // An IPv4 address is listened on just the same.
+        let listeners = bind("127.0.0.1", 0, dual_stack).await.unwrap();
+        assert!(listeners[0].local_addr().unwrap().is_ipv4());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn acceptors_share_an_address() {
+        let options = ListenOptions {
+            acceptors: NonZeroUsize::new(3).unwrap(),
+            ..ListenOptions::default()
+        };
+        let listeners = bind("127.0.0.1", 0, options).await.unwrap();
+        assert_eq!(listeners.len(), 3);
+        let addr = listeners[0].local_addr().unwrap();
+        assert!(
+            listeners
+                .iter()
+                .all(|listener| listener.local_addr().unwrap() == addr)
+        );
+
+        let connections = 30;
+        let mut clients = Vec::new();
+        for _ in 0..connections {
+            clients.push(TcpStream::connect(addr).await.unwrap());
+        }
+        // Every connection is waiting on one of them, to be accepted.
+        let mut accepted = [0; 3];
+        for _ in 0..connections {
+            let (acceptor, _) = tokio::select! {
+                Ok(connection) = listeners[0].accept() => (0, connection),
+                Ok(connection) = listeners[1].accept() => (1, connection),
+                Ok(connection) = listeners[2].accept() => (2, connection),
+            };
+            accepted[acceptor] += 1;
+        }
+        // The kernel picks by hashing each connection's addresses, so any one
+        // acceptor getting all 30 is a one in 3^29 chance.
+        assert!(
+            accepted.iter().filter(|&&count| count > 0).count() > 1,
+            "{accepted:?}"
+        );
+    }
+
+    #[test]
+    fn listen_addrs_need_a_host_and_a_port() {
+        let parsed = |addr: &str| addr.parse::<ListenAddr>();
+        let addr = |host: &str, port| ListenAddr {
+            host: host.to_string(),
+            port,
+        };
+        assert_eq!(parsed("localhost:4444"), Ok(addr("localhost", 4444)));
+        assert_eq!(parsed("10.0.0.5:0"), Ok(addr("10.0.0.5", 0)));
+        assert_eq!(parsed("[::1]:4444"), Ok(addr("[::1]", 4444)));
+        for bad in [
+            "localhost",
+            ":4444",
+            "::1:4444",
+            "localhost:http",
+            "localhost:65536",
+        ] {
+            assert!(parsed(bad).is_err(), "{bad}");
+        }
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn stale_unix_sockets_are_replaced_but_live_ones_arent() {
+        let path = std::env::temp_dir().join(format!("war-accept-{}.sock", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        // Dropping a listener leaves its socket behind.
+        drop(UnixListener::bind(&path).unwrap());
+        let listener = bind_unix(&path).await.unwrap();
+        let error = bind_unix(&path).await.unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::AddrInUse);
+        // The first one's still listening.
+        UnixStream::connect(&path).await.unwrap();
+        listener.accept().await.unwrap();
+
+        drop(listener);
+        std::fs::remove_file(&path).unwrap();
+        std::fs::write(&path, "not a socket").unwrap();
+        assert!(bind_unix(&path).await.is_err());
+        assert_eq!(std::fs::read(&path).unwrap(), b"not a socket");
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn names_that_dont_resolve_arent_listened_on() {
+        let error = bind("nowhere.invalid", 0, ListenOptions::default())
+            .await
+            .unwrap_err();
+        assert!(matches!(error, BindError::Lookup { .. }), "{error}");
+        assert!(error.to_string().contains("\"nowhere.invalid\""), "{error}");
+    }
+
+    #[tokio::test]
+    async fn every_address_tried_is_in_the_error() {
+        let taken = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = taken.local_addr().unwrap().port();
+        let error = bind("127.0.0.1", port, ListenOptions::default())
+            .await
+            .unwrap_err();
+        let BindError::Unavailable { tried, .. } = &error else {
+            panic!("{error}");
+        };
+        assert_eq!(tried.len(), 1);
+        assert_eq!(tried[0].1.kind(), io::ErrorKind::AddrInUse);
+        assert!(
+            error.to_string().contains(&format!("127.0.0.1:{port} (")),
+            "{error}"
+        );
+    }
+
+    #[tokio::test]
+    async fn a_broken_listener_gives_up() {
+        let (welcomed, _, ended) =
+            accept(vec![Ok(1), Err(io::ErrorKind::InvalidInput.into()), Ok(2)]).await;
+        assert_eq!(welcomed, [1]);
+        assert_eq!(ended.unwrap_err().kind(), io::ErrorKind::InvalidInput);
+    }
+}
@@ -0,0 +1,171 @@
+//! Every game being played, each in a task of its own, and how each one went
+//! once it's over.
+
+use std::{
+    collections::HashMap,
+    num::NonZeroUsize,
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+    },
+};
+
+use tokio::{
+    sync::{Semaphore, mpsc, watch},
+    task::{self, JoinError, JoinSet},
+};
+use tracing::{Span, error, info, warn};
+
+use crate::server::*;
+
+/// Plays the games the matchmaker pairs, and logs how each one went.
+///
+/// If there's a limit on how many games can be played at once, pairs that
+/// come along once it's reached wait for a game to end before theirs starts,
+/// in the order they were paired. They aren't told they're waiting, just like
+/// they aren't told while they wait for an opponent, and they're hung up on
+/// like everyone else if the server shuts down first.
+pub struct Games {
+    playing: JoinSet<Result<GameOutcome, GameError>>,
+    /// The span of each game being played, to log how it went in.
+    spans: HashMap<task::Id, Span>,
+    paired: mpsc::UnboundedReceiver<Game>,
+    /// How many games have been paired and aren't over yet, which counts the
+    /// ones that haven't been started yet too.
+    live: Arc<watch::Sender<usize>>,
+    tally: Arc<Tally>,
+    aborting: watch::Receiver<bool>,
+    /// A permit for each game that can be played at once, if there's a limit.
+    tables: Option<Arc<Semaphore>>,
+}
+
+/// How the games that are over went, since the server started.
+#[derive(Debug, Default)]
+pub struct Tally {
+    /// How many games are over, however they ended, short of being aborted.
+    pub served: AtomicU64,
+    /// How many of those ended early, because of a [`GameError`].
+    pub failed: AtomicU64,
+    /// How many of the ones that failed did so by panicking.
+    pub panicked: AtomicU64,
+}
+
+impl Games {
+    pub fn new(
+        paired: mpsc::UnboundedReceiver<Game>,
+        live: Arc<watch::Sender<usize>>,
+        tally: Arc<Tally>,
+        aborting: watch::Receiver<bool>,
+    ) -> Games {
+        Games {
+            playing: JoinSet::new(),
+            spans: HashMap::new(),
+            paired,
+            live,
+            tally,
+            aborting,
+            tables: None,
+        }
+    }
+
+    /// Plays at most `max_games` games at once.
+    pub fn limit_to(&mut self, max_games: NonZeroUsize) {
+        self.tables = Some(Arc::new(Semaphore::new(max_games.get())));
+    }
+
+    /// Starts each game as it's paired, and logs how each one went as it
+    /// ends, until no more can be paired and every game is over.
+    pub async fn run(mut self) {
+        loop {
+            tokio::select! {
+                Some(game) = self.paired.recv() => self.start(game),
+                Some(done) = self.playing.join_next_with_id() => self.finish(done),
+                else => return,
+            }
+        }
+    }
+
+    fn start(&mut self, game: Game) {
+        let span = game.span();
+        let mut aborting = self.aborting.clone();
+        let abort = async move {
+            if aborting.wait_for(|&aborting| aborting).await.is_err() {
+                // No one's left who could abort the game.
+                std::future::pending().await
+            }
+        };
+        let tables = self.tables.clone();
+        let waiting = span.clone();
+        let task = self.playing.spawn(async move {
+            let mut abort = std::pin::pin!(abort);
+            // Held until the game's over, however it ends.
+            let _table = match tables {
+                None => None,
+                Some(tables) => match tables.clone().try_acquire_owned() {
+                    Ok(table) => Some(table),
+                    Err(_) => {
+                        waiting.in_scope(|| info!("Waiting for another game to end first"));
+                        tokio::select! {
+                            table = tables.acquire_owned() => {
+                                Some(table.expect("The tables are never closed."))
+                            }
+                            () = abort.as_mut() => {
+                                let Game { mut player_one, mut player_two, .. } = game;
+                                tokio::join!(
+                                    player_one.abort(SHUTTING_DOWN),
+                                    player_two.abort(SHUTTING_DOWN)
+                                );
+                                return Err(GameError::Aborted);
+                            }
+                        }
+                    }
+                },
+            };
+            serve_game(game, abort).await
+        });
+        self.spans.insert(task.id(), span);
+    }
+
+    fn finish(&mut self, done: Result<(task::Id, Result<GameOutcome, GameError>), JoinError>) {
+        let id = match &done {
+            Ok((id, _)) => *id,
+            Err(error) => error.id(),
+        };
+        let span = self
+            .spans
+            .remove(&id)
+            .expect("Every game is given a span when it starts.");
+        let _entered = span.enter();
+        if !matches!(done, Ok((_, Err(GameError::Aborted)))) {
+            self.tally.served.fetch_add(1, Ordering::Relaxed);
+            if !matches!(done, Ok((_, Ok(_)))) {
+                self.tally.failed.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        match done {
+            Ok((_, Ok(outcome))) => info!(
+                reason = ?outcome.reason,
+                rounds_played = outcome.rounds_played,
+                player_one_score = outcome.player_one_score,
+                player_two_score = outcome.player_two_score,
+                winner = ?outcome.winner,
+                duration = ?outcome.duration,
+                "Game over"
+            ),
+            Ok((_, Err(GameError::Panicked { message }))) => self.panicked(&message),
+            Ok((_, Err(error))) => warn!(%error, "Game ended early"),
+            // Games catch their own panics, so only one that panicked while
+            // it was hanging up on its players ends up here.
+            Err(error) => match error.try_into_panic() {
+                Ok(panic) => self.panicked(&panic_message(&*panic)),
+                Err(error) => error!(%error, "Game was cancelled"),
+            },
+        }
+        self.live.send_modify(|live| *live -= 1);
+    }
+
+    fn panicked(&mut self, message: &str) {
+        let games_panicked = self.tally.panicked.fetch_add(1, Ordering::Relaxed) + 1;
+        error!(panic = message, games_panicked, "Game panicked");
+    }
+}
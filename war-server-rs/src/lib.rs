@@ -0,0 +1,23 @@
+//! A War server: everything but its command line, so it can be run from
+//! inside another program, or tested from outside without a binary in the
+//! way.
+//!
+//! [`run_server`] and [`serve`] run a whole server. Below those, a [`Game`]
+//! between two greeted [`Player`]s can be played with [`serve_game`] over any
+//! stream, in-memory pipes included. What goes over the wire is defined by
+//! the `war_protocol` crate.
+
+pub mod accept;
+mod games;
+pub mod logging;
+mod matchmaker;
+pub mod run;
+pub mod server;
+pub mod session;
+pub mod stream;
+pub mod tls;
+
+pub use matchmaker::Heartbeat;
+pub use run::{Listener, ServerConfig, ServerError, ServerStats, run_server, serve};
+pub use server::{Game, GameError, GameId, GameOutcome, Player, serve_game};
+pub use stream::{PlayerId, Stream};
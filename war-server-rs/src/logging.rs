@@ -0,0 +1,94 @@
+//! What the logs look like: lines for people to read, or JSON for a log
+//! pipeline to pick apart.
+
+use tracing::Subscriber;
+use tracing_subscriber::{EnvFilter, fmt::MakeWriter};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum LogFormat {
+    /// One line per event, with the game it's about up front.
+    #[default]
+    Text,
+    /// One JSON object per line, with the game it's about under `span`, and
+    /// the message and the rest of the event's fields as keys of their own.
+    Json,
+}
+
+/// Logs what `filter` lets through to `writer`, formatted as `format`, in
+/// color if `ansi`.
+pub fn subscriber<W>(
+    format: LogFormat,
+    filter: EnvFilter,
+    writer: W,
+    ansi: bool,
+) -> Box<dyn Subscriber + Send + Sync>
+where
+    W: for<'writer> MakeWriter<'writer> + Send + Sync + 'static,
+{
+    let builder = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(writer);
+    match format {
+        LogFormat::Text => Box::new(builder.with_ansi(ansi).finish()),
+        LogFormat::Json => Box::new(
+            builder
+                .json()
+                .flatten_event(true)
+                .with_current_span(true)
+                .with_span_list(false)
+                .finish(),
+        ),
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use std::sync::LazyLock;
+
+    use tracing::{Dispatch, subscriber::NoSubscriber};
+
+    use super::*;
+
+    /// Everything logged, at every level, while capturing, as the server would
+    /// write it.
+    #[derive(Clone, Default)]
+    pub struct Logs(std::sync::Arc<std::sync::Mutex<Vec<u8>>>);
+
+    impl Logs {
+        /// Captures what's logged on this thread until the guard is dropped,
+        /// which is everything a current-thread runtime's tasks log too.
+        pub fn capture(&self, format: LogFormat) -> tracing::subscriber::DefaultGuard {
+            // Whether each span and event is wanted is decided once, for every
+            // thread. With only one subscriber around, tracing just asks
+            // whichever's the default on the thread that gets there first,
+            // which for another test is none at all, so this would miss
+            // whatever that test logged first. Keeping another one around for
+            // good makes it ask all of them.
+            static ANOTHER: LazyLock<Dispatch> =
+                LazyLock::new(|| Dispatch::new(NoSubscriber::default()));
+            LazyLock::force(&ANOTHER);
+            let logs = self.clone();
+            tracing::subscriber::set_default(subscriber(
+                format,
+                EnvFilter::new("trace"),
+                move || logs.clone(),
+                false,
+            ))
+        }
+
+        pub fn contents(&self) -> String {
+            String::from_utf8(self.0.lock().unwrap().clone()).unwrap()
+        }
+    }
+
+    impl std::io::Write for Logs {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+}
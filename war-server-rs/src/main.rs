@@ -1,37 +1,534 @@
-mod format;
-mod server;
+use std::{
+    io::{IsTerminal, stderr},
+    num::NonZeroUsize,
+    path::{Path, PathBuf},
+    process::ExitCode,
+    time::Duration,
+};
 
-use std::{net::IpAddr, process::ExitCode};
+use clap::{CommandFactory, Parser, error::ErrorKind};
+use tokio::sync::oneshot;
+use tracing::{error, level_filters::LevelFilter, warn};
+use tracing_subscriber::EnvFilter;
+use war_protocol::{HAND_SIZE, MAX_DECKS, RankOrder, TieBreak, WireFormat};
+use war_server_rs::{
+    Heartbeat, Listener, ServerConfig,
+    accept::{self, CAN_SHARE_ADDRS, ListenAddr, ListenOptions, SocketOptions, bind},
+    logging::{self, LogFormat},
+    serve,
+    session::Rules,
+    tls,
+};
 
-use clap::Parser;
-use server::*;
-use tokio::net::TcpListener;
+/// What the server exits with when it shut down before every game was over,
+/// having run out of time to let them finish.
+const EXIT_FORCED_DRAIN: u8 = 3;
+
+/// Where to listen when nothing says otherwise: the classic assignment's
+/// host and port.
+const DEFAULT_HOST: &str = "127.0.0.1";
+const DEFAULT_PORT: u16 = 4444;
 
 #[derive(clap::Parser)]
 struct Args {
-    host: IpAddr,
-    /// Can be set to 0 to request the OS to pick a port.
-    port: u16,
+    /// The same as --host, for scripts from before there was one.
+    #[arg(value_name = "HOST", conflicts_with = "host")]
+    positional_host: Option<String>,
+    /// The same as --port, for scripts from before there was one.
+    #[arg(value_name = "PORT", conflicts_with = "port")]
+    positional_port: Option<u16>,
+    /// A name to look up, like localhost, or an IP address. If it has more
+    /// than one address, the first one that can be listened on is.
+    /// [default: 127.0.0.1]
+    #[arg(long)]
+    host: Option<String>,
+    /// Can be set to 0 to request the OS to pick a port. [default: 4444]
+    #[arg(long)]
+    port: Option<u16>,
+    /// Somewhere to listen, like 127.0.0.1:4444 or [::1]:4444, instead of
+    /// HOST and PORT. Can be given more than once, to listen in more than one
+    /// place, with players from each of them paired with each other.
+    #[arg(
+        long,
+        value_name = "HOST:PORT",
+        conflicts_with_all = ["positional_host", "positional_port", "host", "port"]
+    )]
+    listen: Vec<ListenAddr>,
+    /// Listen on a Unix domain socket at PATH instead, replacing any socket
+    /// a server that didn't shut down cleanly left there. It's removed again
+    /// when the server's asked to stop. Only on Unix.
+    #[arg(
+        long,
+        value_name = "PATH",
+        conflicts_with_all = ["positional_host", "positional_port", "host", "port", "listen"]
+    )]
+    unix: Option<PathBuf>,
+    /// Serve over TLS, showing clients the certificate chain in the PEM file
+    /// at PATH, leaf first. Every TCP listener is TLS-only then.
+    #[arg(
+        long,
+        value_name = "PATH",
+        requires = "tls_key",
+        conflicts_with = "unix"
+    )]
+    tls_cert: Option<PathBuf>,
+    /// The private key for --tls-cert, in the PEM file at PATH.
+    #[arg(long, value_name = "PATH", requires = "tls_cert")]
+    tls_key: Option<PathBuf>,
+    /// Once listening, write everywhere the server's listening to PATH, one
+    /// address (or Unix socket path) per line, like 127.0.0.1:54321, for
+    /// scripts that need to know which port the OS picked. It's written
+    /// before anything's printed, and all at once, so it's never seen half
+    /// written.
+    #[arg(long, value_name = "PATH")]
+    port_file: Option<PathBuf>,
+    /// Take IPv4 connections on an IPv6 address too, even where the OS
+    /// wouldn't by default, so that listening on :: takes every connection.
+    #[arg(long)]
+    dual_stack: bool,
+    /// How many sockets to listen with at each address, each accepted from
+    /// on its own, with the kernel spreading connections out between them.
+    /// Only on Linux.
+    #[arg(long, default_value = "1")]
+    acceptors: NonZeroUsize,
+    /// Rank aces below twos instead of above kings.
+    #[arg(long)]
+    aces_low: bool,
+    /// What decides a round between two cards of the same rank.
+    #[arg(long, value_enum, default_value_t)]
+    tie_break: TieBreak,
+    /// What every client is expected to speak.
+    #[arg(long, value_enum, default_value_t)]
+    format: WireFormat,
+    /// Check that every deal is a proper split of the deck, even in release
+    /// builds, ending the game if it isn't.
+    #[arg(long)]
+    strict: bool,
+    /// Log every hand that's dealt, at debug level, so with -v too. Anyone
+    /// who can read the logs can see them.
+    #[arg(long)]
+    log_hands: bool,
+    /// How many decks to shuffle together for each game. Clients have to speak
+    /// protocol version 3 or later to play with more than one.
+    #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u8).range(1..=i64::from(MAX_DECKS)))]
+    decks: u8,
+    /// How many cards to deal each player, for games shorter than the whole
+    /// deck. Clients have to speak protocol version 3 or later to be dealt
+    /// anything but 26. [default: all of the cards]
+    #[arg(long, value_parser = clap::value_parser!(u8).range(1..))]
+    hand_size: Option<u8>,
+    /// How many seconds a player has to make each move before they forfeit.
+    #[arg(long, default_value_t = 30, value_parser = clap::value_parser!(u64).range(1..))]
+    move_timeout: u64,
+    /// How many seconds a client has to finish its handshake, opening with a
+    /// WantGame (or JoinLobby), before it's hung up on.
+    #[arg(long, default_value_t = 5, value_parser = clap::value_parser!(u64).range(1..))]
+    handshake_timeout: u64,
+    /// How many seconds a player waits for an opponent before they're hung up
+    /// on. Players in a lobby wait --lobby-timeout instead.
+    #[arg(long, default_value_t = 120, value_parser = clap::value_parser!(u64).range(1..))]
+    pairing_timeout: u64,
+    /// How many seconds a player who joins a lobby waits for someone else to
+    /// join it before they're hung up on.
+    #[arg(long, default_value_t = 120, value_parser = clap::value_parser!(u64).range(1..))]
+    lobby_timeout: u64,
+    /// How many seconds apart to ping players who are waiting for an
+    /// opponent, to check they're still there. Only clients that speak
+    /// protocol version 4 or later are pinged.
+    #[arg(long, default_value_t = 15, value_parser = clap::value_parser!(u64).range(1..))]
+    heartbeat_interval: u64,
+    /// How many seconds a waiting player has to answer a ping before they're
+    /// hung up on.
+    #[arg(long, default_value_t = 10, value_parser = clap::value_parser!(u64).range(1..))]
+    heartbeat_timeout: u64,
+    /// How many games can be played at once. Once that many are, players who
+    /// are paired wait for one of them to end before their game starts.
+    /// [default: no limit]
+    #[arg(long)]
+    max_games: Option<NonZeroUsize>,
+    /// Have TCP check that connections which have been idle for this many
+    /// seconds are still there, so that players whose connections were
+    /// dropped are noticed even while nothing's being sent to them.
+    /// [default: never]
+    #[arg(long, value_parser = clap::value_parser!(u64).range(1..))]
+    tcp_keepalive: Option<u64>,
+    /// Let Nagle's algorithm hold back small messages to send them together,
+    /// rather than sending each one as soon as it's written. Every message is
+    /// small, so this only ever trades latency for fewer packets.
+    #[arg(long)]
+    no_nodelay: bool,
+    /// Play just one game: stop listening as soon as the first two players
+    /// are paired, and exit once their game is over, with a failure if it
+    /// ended early because of an error, like a player hanging up.
+    #[arg(long)]
+    once: bool,
+    /// How many seconds games in progress have to finish once the server's
+    /// asked to stop, before they're aborted, and the server exits with code 3.
+    #[arg(long, default_value_t = 30)]
+    drain_timeout: u64,
+    /// Log more: debug with -v, and everything with -vv. RUST_LOG, if set,
+    /// takes precedence, like RUST_LOG=war_server=debug.
+    #[arg(short, long, action = clap::ArgAction::Count, conflicts_with = "quiet")]
+    verbose: u8,
+    /// Log less: only warnings with -q, and only errors with -qq.
+    #[arg(short, long, action = clap::ArgAction::Count)]
+    quiet: u8,
+    /// How to write the logs.
+    #[arg(long, value_enum, default_value_t)]
+    log_format: LogFormat,
+}
+
+impl Args {
+    /// Everywhere to listen over TCP, which is nowhere if it's over a Unix
+    /// socket instead.
+    fn listen_addrs(&self) -> Vec<ListenAddr> {
+        if self.unix.is_some() || !self.listen.is_empty() {
+            return self.listen.clone();
+        }
+        vec![ListenAddr {
+            host: self
+                .host
+                .clone()
+                .or_else(|| self.positional_host.clone())
+                .unwrap_or_else(|| DEFAULT_HOST.to_string()),
+            port: self.port.or(self.positional_port).unwrap_or(DEFAULT_PORT),
+        }]
+    }
+
+    /// What to log when RUST_LOG doesn't say.
+    fn log_level(&self) -> LevelFilter {
+        match (self.verbose, self.quiet) {
+            (0, 0) => LevelFilter::INFO,
+            (1, _) => LevelFilter::DEBUG,
+            (_, 0) => LevelFilter::TRACE,
+            (_, 1) => LevelFilter::WARN,
+            (_, _) => LevelFilter::ERROR,
+        }
+    }
 }
 
 #[tokio::main]
 async fn main() -> ExitCode {
     let args = Args::parse();
-    // STRETCH: what would it mean to let the user bind to a string (e.g., a DNS
-    // name)? Should I support that?
-    let listener = TcpListener::bind((args.host, args.port)).await.unwrap();
-    println!("Listening on {addr}", addr = listener.local_addr().unwrap());
-    // TODO: When might accept fail? Also, consider `TcpListenerStream`.
-    while let Ok(player_one) = listener.accept().await {
-        println!("Got client {0:?}", player_one.1);
-        let Ok(player_two) = listener.accept().await else {
-            break;
-        };
-        tokio::spawn(serve_game(Game {
-            player_one,
-            player_two,
-        }));
-    }
-    eprintln!("How did I get here? `accept` failed, I think!");
-    return ExitCode::from(1);
+    // Logs go to stderr, so stdout is only ever the address we're listening on.
+    let filter = EnvFilter::builder()
+        .with_default_directive(args.log_level().into())
+        .from_env_lossy();
+    tracing::subscriber::set_global_default(logging::subscriber(
+        args.log_format,
+        filter,
+        stderr,
+        stderr().is_terminal(),
+    ))
+    .expect("Nothing else sets up logging.");
+    if let Some(hand_size) = args.hand_size
+        && usize::from(hand_size) > usize::from(args.decks) * HAND_SIZE
+    {
+        Args::command()
+            .error(
+                ErrorKind::ValueValidation,
+                format!(
+                    "can't deal two hands of {hand_size} from {decks} deck(s)",
+                    decks = args.decks
+                ),
+            )
+            .exit();
+    }
+    if args.acceptors.get() > 1 && !CAN_SHARE_ADDRS {
+        Args::command()
+            .error(
+                ErrorKind::ArgumentConflict,
+                "--acceptors needs SO_REUSEPORT to spread connections out, which only Linux has",
+            )
+            .exit();
+    }
+    if cfg!(not(unix)) && args.unix.is_some() {
+        Args::command()
+            .error(ErrorKind::ArgumentConflict, "--unix only works on Unix")
+            .exit();
+    }
+    // Loaded before listening, so a server that can't do TLS never looks
+    // like it's up.
+    let tls = match (&args.tls_cert, &args.tls_key) {
+        (Some(cert), Some(key)) => match tls::acceptor(cert, key) {
+            Ok(acceptor) => Some(acceptor),
+            Err(error) => {
+                error!(%error, "Couldn't set up TLS");
+                return ExitCode::FAILURE;
+            }
+        },
+        _ => None,
+    };
+    let listen_options = ListenOptions {
+        dual_stack: args.dual_stack,
+        acceptors: args.acceptors,
+    };
+    let mut listeners = Vec::new();
+    for ListenAddr { host, port } in args.listen_addrs() {
+        match bind(&host, port, listen_options).await {
+            Ok(acceptors) => listeners.push(acceptors),
+            Err(error) => {
+                error!(%error, "Couldn't start listening");
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+    #[cfg(unix)]
+    let unix_listener = match &args.unix {
+        Some(path) => match accept::bind_unix(path).await {
+            Ok(listener) => Some((listener, std::sync::Arc::<Path>::from(path.as_path()))),
+            Err(error) => {
+                error!(%error, path = %path.display(), "Couldn't start listening");
+                return ExitCode::FAILURE;
+            }
+        },
+        None => None,
+    };
+    // Listened for before anyone knows where to connect, so asking to stop
+    // right away isn't missed.
+    let mut stop_requests = match StopRequests::listen() {
+        Ok(stop_requests) => stop_requests,
+        Err(error) => {
+            error!(%error, "Couldn't listen for signals");
+            return ExitCode::FAILURE;
+        }
+    };
+    let mut places = Vec::new();
+    for acceptors in &listeners {
+        match acceptors[0].local_addr() {
+            Ok(addr) => places.push(addr.to_string()),
+            Err(error) => {
+                error!(%error, "Couldn't tell where it's listening");
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+    places.extend(args.unix.iter().map(|path| path.display().to_string()));
+    if let Some(port_file) = &args.port_file
+        && let Err(error) = write_port_file(port_file, &places)
+    {
+        error!(%error, path = %port_file.display(), "Couldn't write the port file");
+        return ExitCode::FAILURE;
+    }
+    for place in &places {
+        println!("Listening on {place}");
+    }
+    let config = ServerConfig {
+        rules: Rules {
+            rank_order: if args.aces_low {
+                RankOrder::AcesLow
+            } else {
+                RankOrder::AcesHigh
+            },
+            tie_break: args.tie_break,
+            strict: args.strict,
+            log_hands: args.log_hands,
+            decks: args.decks,
+            hand_size: args.hand_size.map(usize::from),
+            move_timeout: Duration::from_secs(args.move_timeout),
+            ..Rules::default()
+        },
+        format: args.format,
+        handshake_timeout: Duration::from_secs(args.handshake_timeout),
+        pairing_timeout: Duration::from_secs(args.pairing_timeout),
+        lobby_timeout: Duration::from_secs(args.lobby_timeout),
+        heartbeat: Heartbeat {
+            interval: Duration::from_secs(args.heartbeat_interval),
+            timeout: Duration::from_secs(args.heartbeat_timeout),
+        },
+        max_games: args.max_games,
+        socket_options: SocketOptions {
+            keepalive: args.tcp_keepalive.map(Duration::from_secs),
+            nodelay: !args.no_nodelay,
+        },
+        drain_timeout: Duration::from_secs(args.drain_timeout),
+        once: args.once,
+    };
+    let mut listeners: Vec<Listener> = listeners
+        .into_iter()
+        .flatten()
+        .map(|listener| match &tls {
+            Some(acceptor) => Listener::Tls {
+                listener,
+                acceptor: acceptor.clone(),
+            },
+            None => Listener::from(listener),
+        })
+        .collect();
+    #[cfg(unix)]
+    if let Some((listener, path)) = unix_listener {
+        listeners.push(Listener::Unix { listener, path });
+    }
+    let (stop, stopped) = oneshot::channel();
+    let mut serving = std::pin::pin!(serve(config, listeners, async {
+        let _ = stopped.await;
+    }));
+    let served = tokio::select! {
+        served = &mut serving => served,
+        () = stop_requests.next() => {
+            let _ = stop.send(());
+            tokio::select! {
+                served = serving => served,
+                () = stop_requests.next() => {
+                    warn!("Asked to stop again, quitting now");
+                    return ExitCode::FAILURE;
+                }
+            }
+        }
+    };
+    match served {
+        Ok(stats) if stats.games_aborted > 0 => ExitCode::from(EXIT_FORCED_DRAIN),
+        Ok(stats) if args.once && stats.games_failed > 0 => ExitCode::FAILURE,
+        Ok(_) => ExitCode::SUCCESS,
+        Err(error) => {
+            error!(%error, "Couldn't keep serving");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Writes `places` to `path`, one per line, by writing them somewhere else
+/// first, then moving that over `path`.
+fn write_port_file(path: &Path, places: &[String]) -> std::io::Result<()> {
+    let mut partial = path.as_os_str().to_owned();
+    partial.push(".partial");
+    let contents: String = places.iter().map(|place| format!("{place}\n")).collect();
+    std::fs::write(&partial, contents)?;
+    std::fs::rename(&partial, path)
+}
+
+/// Listens for the server to be asked to stop: by Ctrl-C, or on Unix, SIGTERM.
+struct StopRequests {
+    #[cfg(unix)]
+    interrupt: tokio::signal::unix::Signal,
+    #[cfg(unix)]
+    terminate: tokio::signal::unix::Signal,
+}
+
+impl StopRequests {
+    /// Starts listening, so that every request from now on is heard, even if
+    /// nothing's waiting for one yet.
+    fn listen() -> std::io::Result<StopRequests> {
+        #[cfg(unix)]
+        {
+            use tokio::signal::unix::{SignalKind, signal};
+            Ok(StopRequests {
+                interrupt: signal(SignalKind::interrupt())?,
+                terminate: signal(SignalKind::terminate())?,
+            })
+        }
+        #[cfg(not(unix))]
+        Ok(StopRequests {})
+    }
+
+    /// Waits for the next request to stop.
+    async fn next(&mut self) {
+        #[cfg(unix)]
+        tokio::select! {
+            _ = self.interrupt.recv() => {}
+            _ = self.terminate.recv() => {}
+        }
+        #[cfg(not(unix))]
+        if tokio::signal::ctrl_c().await.is_err() {
+            std::future::pending::<()>().await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Args, clap::Error> {
+        Args::try_parse_from(["war-server-rs"].iter().chain(args))
+    }
+
+    fn addr(host: &str, port: u16) -> ListenAddr {
+        ListenAddr {
+            host: host.to_string(),
+            port,
+        }
+    }
+
+    #[test]
+    fn args_are_consistent() {
+        Args::command().debug_assert();
+    }
+
+    #[test]
+    fn host_and_port_default_to_the_classic_ones() {
+        let args = parse(&[]).unwrap();
+        assert_eq!(args.listen_addrs(), [addr("127.0.0.1", 4444)]);
+    }
+
+    #[test]
+    fn host_and_port_can_still_be_positional() {
+        let args = parse(&["0.0.0.0", "5555"]).unwrap();
+        assert_eq!(args.listen_addrs(), [addr("0.0.0.0", 5555)]);
+        let args = parse(&["localhost"]).unwrap();
+        assert_eq!(args.listen_addrs(), [addr("localhost", 4444)]);
+    }
+
+    #[test]
+    fn host_and_port_can_be_flags() {
+        let args = parse(&["--host", "::1", "--port", "0"]).unwrap();
+        assert_eq!(args.listen_addrs(), [addr("::1", 0)]);
+        let args = parse(&["--port", "5555"]).unwrap();
+        assert_eq!(args.listen_addrs(), [addr("127.0.0.1", 5555)]);
+        // Either way of giving one doesn't stop the other from being given
+        // the other way.
+        let args = parse(&["localhost", "--port", "5555"]).unwrap();
+        assert_eq!(args.listen_addrs(), [addr("localhost", 5555)]);
+    }
+
+    #[test]
+    fn the_same_thing_cant_be_given_both_ways() {
+        for args in [
+            &["0.0.0.0", "--host", "::1"][..],
+            &["0.0.0.0", "1", "--port", "2"],
+        ] {
+            let error = parse(args).err().unwrap();
+            assert_eq!(error.kind(), ErrorKind::ArgumentConflict, "{args:?}");
+        }
+    }
+
+    #[test]
+    fn listen_and_unix_replace_host_and_port() {
+        let args = parse(&["--listen", "[::1]:1", "--listen", "127.0.0.1:2"]).unwrap();
+        assert_eq!(
+            args.listen_addrs(),
+            [addr("[::1]", 1), addr("127.0.0.1", 2)]
+        );
+        let args = parse(&["--unix", "/run/war.sock"]).unwrap();
+        assert_eq!(args.listen_addrs(), []);
+        for args in [
+            &["--listen", "[::1]:1", "--port", "2"][..],
+            &["--listen", "[::1]:1", "localhost"],
+            &["--unix", "/run/war.sock", "--host", "localhost"],
+            &["--unix", "/run/war.sock", "localhost", "1"],
+        ] {
+            let error = parse(args).err().unwrap();
+            assert_eq!(error.kind(), ErrorKind::ArgumentConflict, "{args:?}");
+        }
+    }
+
+    #[test]
+    fn tls_takes_a_cert_and_a_key_together() {
+        assert!(parse(&["--tls-cert", "war.crt", "--tls-key", "war.key"]).is_ok());
+        for args in [&["--tls-cert", "war.crt"][..], &["--tls-key", "war.key"]] {
+            let error = parse(args).err().unwrap();
+            assert_eq!(error.kind(), ErrorKind::MissingRequiredArgument, "{args:?}");
+        }
+        let error = parse(&[
+            "--tls-cert",
+            "war.crt",
+            "--tls-key",
+            "war.key",
+            "--unix",
+            "/run/war.sock",
+        ])
+        .err()
+        .unwrap();
+        assert_eq!(error.kind(), ErrorKind::ArgumentConflict);
+    }
 }
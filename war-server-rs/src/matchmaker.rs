@@ -0,0 +1,876 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, atomic::Ordering},
+    time::Duration,
+};
+
+use tokio::{
+    sync::{mpsc, watch},
+    task::JoinSet,
+    time::Instant,
+};
+use tracing::{info, warn};
+
+use war_protocol::*;
+
+use crate::{
+    games::{Games, Tally},
+    server::*,
+    session::Rules,
+    stream::{PlayerId, Stream},
+};
+
+/// Holds greeted players until someone they can play with shows up, then
+/// starts a game between them.
+///
+/// Players who open with WantGame wait in the default pool and are paired in
+/// the order they arrive, like they always have been. Players who open with
+/// JoinLobby are only ever paired with someone who joined the same lobby.
+/// Either way, they're hung up on if no one turns up to play them in time, and
+/// players who speak [`PING_VERSION`] or later are pinged while they wait, and
+/// hung up on if they stop answering.
+#[derive(Clone)]
+pub struct Matchmaker {
+    waiting: Arc<Mutex<Waiting>>,
+    /// Where games go to be played once their players are paired.
+    paired: mpsc::UnboundedSender<Game>,
+    /// How many games have been paired and aren't over yet.
+    games: Arc<watch::Sender<usize>>,
+    /// How the games that are over went.
+    tally: Arc<Tally>,
+    /// Whether to stop pairing anyone once the first game's been paired.
+    once: bool,
+    /// Whether that game has been.
+    paired_once: Arc<watch::Sender<bool>>,
+    /// Whether the games being played should be given up on.
+    aborting: Arc<watch::Sender<bool>>,
+    /// How long a client has to say what it wants, after connecting.
+    handshake_timeout: Duration,
+    lobby_timeout: Duration,
+    /// How long a player in the default pool waits for an opponent.
+    pairing_timeout: Duration,
+    heartbeat: Heartbeat,
+    rules: Rules,
+    format: WireFormat,
+}
+
+/// How often to ping a waiting player, and how long they have to answer.
+#[derive(Debug, Clone, Copy)]
+pub struct Heartbeat {
+    pub interval: Duration,
+    pub timeout: Duration,
+}
+
+/// Everyone waiting for an opponent, keyed by lobby (`None` being the default
+/// pool). There's never more than one player waiting per lobby, since the
+/// second one to arrive is paired with the first.
+#[derive(Default)]
+struct Waiting {
+    players: HashMap<Option<LobbyCode>, (u64, Player)>,
+    /// Tells apart the players who've waited, so an eviction (or a ping) doesn't
+    /// go to someone who arrived after the player it was meant for got paired.
+    next_ticket: u64,
+    /// Whether the server is shutting down, so no one else should wait.
+    closed: bool,
+}
+
+impl Matchmaker {
+    /// A matchmaker, and the games it pairs players for, which are only
+    /// played while they're run.
+    pub fn new(
+        handshake_timeout: Duration,
+        lobby_timeout: Duration,
+        pairing_timeout: Duration,
+        heartbeat: Heartbeat,
+        rules: Rules,
+        format: WireFormat,
+    ) -> (Matchmaker, Games) {
+        let (paired, to_play) = mpsc::unbounded_channel();
+        let games = Arc::new(watch::Sender::new(0));
+        let tally = Arc::new(Tally::default());
+        let aborting = watch::Sender::new(false);
+        let to_play = Games::new(to_play, games.clone(), tally.clone(), aborting.subscribe());
+        let matchmaker = Matchmaker {
+            waiting: Arc::default(),
+            paired,
+            games,
+            tally,
+            once: false,
+            paired_once: Arc::new(watch::Sender::new(false)),
+            aborting: Arc::new(aborting),
+            handshake_timeout,
+            lobby_timeout,
+            pairing_timeout,
+            heartbeat,
+            rules,
+            format,
+        };
+        (matchmaker, to_play)
+    }
+
+    /// Pairs only one game: once it's paired, hangs up on everyone who
+    /// finishes their handshake from then on, like after [`close`]. Everyone
+    /// already waiting goes on waiting until that's called.
+    ///
+    /// [`close`]: Matchmaker::close
+    pub fn pair_once(&mut self) {
+        self.once = true;
+    }
+
+    /// Waits for the one game to be paired, when only one is, or forever
+    /// otherwise.
+    pub async fn paired_once(&self) {
+        let _ = self
+            .paired_once
+            .subscribe()
+            .wait_for(|&paired| paired)
+            .await;
+    }
+
+    /// Greets a freshly accepted client, then finds them an opponent. A client
+    /// that botches its handshake (or takes too long to finish it) is hung up
+    /// on without costing anyone else their game.
+    ///
+    /// Which framing the client speaks is worked out from how it opens, so
+    /// legacy and v2 clients can connect to the same port.
+    pub async fn welcome(self, connection: (Stream, PlayerId)) {
+        let mut player = Player::new(connection, self.format);
+        let greeting = match tokio::time::timeout(self.handshake_timeout, player.greet()).await {
+            Ok(greeting) => greeting,
+            Err(_) => {
+                info!(%player, "Never finished its handshake");
+                Err(ErrorCode::Timeout)
+            }
+        };
+        let greeting = greeting.and_then(|lobby| {
+            if player.can_play(&self.rules) {
+                Ok(lobby)
+            } else {
+                Err(ErrorCode::BadHandshake)
+            }
+        });
+        match greeting {
+            Ok(lobby) => {
+                info!(%player, "Handshake complete");
+                self.join(player, lobby);
+            }
+            Err(code) => {
+                let error = GameError::HandshakeFailed {
+                    player: player.id().clone(),
+                    code,
+                };
+                warn!(%error, "Hanging up");
+                player.abort(code).await;
+            }
+        }
+    }
+
+    fn join(&self, player: Player, lobby: Option<LobbyCode>) {
+        let answers_pings = player.answers_pings();
+        let ticket = {
+            let mut waiting = self.waiting.lock().unwrap();
+            waiting.next_ticket += 1;
+            waiting.next_ticket
+        };
+        if self.wait(player, lobby, ticket) {
+            tokio::spawn(self.clone().watch(lobby, ticket, answers_pings));
+        }
+    }
+
+    /// Starts a game between `player` and whoever's waiting in `lobby`, or if
+    /// no one is (or they've hung up), has them wait there with `ticket`.
+    /// Returns whether they're waiting.
+    fn wait(&self, mut player: Player, lobby: Option<LobbyCode>, ticket: u64) -> bool {
+        let mut waiting = self.waiting.lock().unwrap();
+        if waiting.closed {
+            tokio::spawn(async move { player.abort(SHUTTING_DOWN).await });
+            return false;
+        }
+        if let Some((_, player_one)) = waiting.players.remove(&lobby) {
+            if player_one.has_hung_up() {
+                info!(player = %player_one, "Hung up while waiting");
+                waiting.players.insert(lobby, (ticket, player));
+                return true;
+            }
+            let game = Game {
+                id: GameId::next(),
+                player_one,
+                player_two: player,
+                rules: self.rules,
+            };
+            // Counted now, so a game that's been paired but not started yet
+            // is waited for too.
+            self.games.send_modify(|live| *live += 1);
+            self.paired
+                .send(game)
+                .expect("Games are played as long as players can be paired.");
+            if self.once {
+                waiting.closed = true;
+                self.paired_once.send_replace(true);
+            }
+            return false;
+        }
+        waiting.players.insert(lobby, (ticket, player));
+        true
+    }
+
+    /// How many games have been paired and aren't over yet.
+    pub fn live_games(&self) -> usize {
+        *self.games.borrow()
+    }
+
+    /// How many games are over, however they ended, short of being aborted.
+    pub fn games_served(&self) -> u64 {
+        self.tally.served.load(Ordering::Relaxed)
+    }
+
+    /// How many games ended early, because of a [`GameError`].
+    pub fn games_failed(&self) -> u64 {
+        self.tally.failed.load(Ordering::Relaxed)
+    }
+
+    /// How many of the games that failed did so by panicking.
+    pub fn games_panicked(&self) -> u64 {
+        self.tally.panicked.load(Ordering::Relaxed)
+    }
+
+    /// Stops pairing anyone: hangs up on everyone waiting for an opponent,
+    /// and from now on, on everyone who finishes their handshake. Games
+    /// already being played go on.
+    pub async fn close(&self) {
+        let players = {
+            let mut waiting = self.waiting.lock().unwrap();
+            waiting.closed = true;
+            std::mem::take(&mut waiting.players)
+        };
+        let mut hanging_up = JoinSet::new();
+        for (_, (_, mut player)) in players {
+            info!(%player, "Hanging up on a waiting player to shut down");
+            hanging_up.spawn(async move { player.abort(SHUTTING_DOWN).await });
+        }
+        hanging_up.join_all().await;
+    }
+
+    /// Gives up on every game being played, and any started from now on,
+    /// telling the players the server's shutting down.
+    pub fn abort_games(&self) {
+        self.aborting.send_replace(true);
+    }
+
+    /// Waits for every game that's been paired to be over.
+    pub async fn drained(&self) {
+        let _ = self.games.subscribe().wait_for(|&live| live == 0).await;
+    }
+
+    /// Takes whoever's waiting in `lobby` out of line, as long as it's still
+    /// the player who was given `ticket`.
+    fn take(&self, lobby: Option<LobbyCode>, ticket: u64) -> Option<Player> {
+        let mut waiting = self.waiting.lock().unwrap();
+        match waiting.players.get(&lobby) {
+            Some(&(waiting_ticket, _)) if waiting_ticket == ticket => {
+                waiting.players.remove(&lobby).map(|(_, player)| player)
+            }
+            _ => None,
+        }
+    }
+
+    /// Looks after the player waiting in `lobby` with `ticket` until they're
+    /// paired: pinging them every so often if they answer Pings, and hanging
+    /// up on them if they stop, or if no one turns up to play them in time.
+    async fn watch(self, lobby: Option<LobbyCode>, ticket: u64, answers_pings: bool) {
+        let deadline = Instant::now()
+            + match lobby {
+                Some(_) => self.lobby_timeout,
+                None => self.pairing_timeout,
+            };
+        let mut nonce = 0_u8;
+        loop {
+            let ping = answers_pings.then(|| Instant::now() + self.heartbeat.interval);
+            let wake = ping.map_or(deadline, |ping| ping.min(deadline));
+            tokio::time::sleep_until(wake).await;
+            let Some(mut player) = self.take(lobby, ticket) else {
+                return;
+            };
+            if wake == deadline {
+                match lobby {
+                    Some(_) => info!(%player, "No one joined their lobby in time"),
+                    None => info!(%player, "No one showed up to play them in time"),
+                }
+                player.abort(ErrorCode::Timeout).await;
+                return;
+            }
+            nonce = nonce.wrapping_add(1);
+            if let Err(code) = player.check_in(nonce, self.heartbeat.timeout).await {
+                info!(%player, ?code, "Stopped answering while waiting");
+                player.abort(code).await;
+                return;
+            }
+            if !self.wait(player, lobby, ticket) {
+                return;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::{TcpListener, TcpStream},
+    };
+
+    use std::{net::SocketAddr, num::NonZeroUsize};
+
+    use super::*;
+    use crate::logging::{LogFormat, test::Logs};
+
+    const TEST_HANDSHAKE_TIMEOUT: Duration = Duration::from_millis(200);
+    const TEST_LOBBY_TIMEOUT: Duration = Duration::from_millis(300);
+    /// Longer than any test waits for an opponent, unless it's looking for
+    /// this.
+    const TEST_PAIRING_TIMEOUT: Duration = Duration::from_secs(5);
+
+    /// Slow enough that no test runs long enough to see a Ping unless it's
+    /// looking for one.
+    const NO_HEARTBEAT: Heartbeat = Heartbeat {
+        interval: Duration::from_secs(3600),
+        timeout: Duration::from_secs(3600),
+    };
+
+    const TEST_HEARTBEAT: Heartbeat = Heartbeat {
+        interval: Duration::from_millis(50),
+        timeout: Duration::from_millis(200),
+    };
+
+    /// Starts accepting connections on an ephemeral port, handing each one to
+    /// a matchmaker.
+    async fn start_matchmaker() -> SocketAddr {
+        start_matchmaker_with(NO_HEARTBEAT, Rules::default()).await
+    }
+
+    async fn start_matchmaker_with(heartbeat: Heartbeat, rules: Rules) -> SocketAddr {
+        serve(test_matchmaker(heartbeat, rules)).await
+    }
+
+    /// A matchmaker whose games are played for as long as the test runs.
+    fn test_matchmaker(heartbeat: Heartbeat, rules: Rules) -> Matchmaker {
+        let (matchmaker, games) = Matchmaker::new(
+            TEST_HANDSHAKE_TIMEOUT,
+            TEST_LOBBY_TIMEOUT,
+            TEST_PAIRING_TIMEOUT,
+            heartbeat,
+            rules,
+            WireFormat::Binary,
+        );
+        tokio::spawn(games.run());
+        matchmaker
+    }
+
+    /// Starts accepting connections on an ephemeral port, handing each one to
+    /// `matchmaker`.
+    async fn serve(matchmaker: Matchmaker) -> SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            loop {
+                let (stream, addr) = listener.accept().await.unwrap();
+                tokio::spawn(matchmaker.clone().welcome((stream.into(), addr.into())));
+            }
+        });
+        addr
+    }
+
+    async fn connect(addr: SocketAddr, opening: &[u8]) -> TcpStream {
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        stream.write_all(opening).await.unwrap();
+        stream
+    }
+
+    /// The bytes of each of `messages`, back to back.
+    fn encoded(messages: &[Message]) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        for message in messages {
+            let (buf, len) = message.encode();
+            bytes.extend_from_slice(&buf[..len]);
+        }
+        bytes
+    }
+
+    fn hello(name: &str) -> Message {
+        Message::Hello(PlayerName::try_from(name).unwrap())
+    }
+
+    /// Finishes a handshake with `opening`, and reads the ServerInfo that a
+    /// client speaking the latest version is sent for it.
+    async fn greeted(addr: SocketAddr, opening: &[Message]) -> TcpStream {
+        let mut stream = connect(addr, &encoded(opening)).await;
+        let Message::ServerInfo { proto_version, .. } =
+            Message::read_from(&mut stream).await.unwrap()
+        else {
+            panic!("Expected ServerInfo.");
+        };
+        assert_eq!(proto_version, PROTOCOL_VERSION);
+        stream
+    }
+
+    async fn join(addr: SocketAddr, code: &LobbyCode) -> TcpStream {
+        let join = Message::JoinLobby {
+            version: PROTOCOL_VERSION,
+            code: *code,
+        };
+        greeted(addr, &[join, hello("friend")]).await
+    }
+
+    /// Asks for a game with anyone, speaking the latest version.
+    async fn enter(addr: SocketAddr, name: &str) -> TcpStream {
+        greeted(addr, &[want_game(), hello(name)]).await
+    }
+
+    fn want_game() -> Message {
+        Message::WantGame {
+            version: PROTOCOL_VERSION,
+        }
+    }
+
+    async fn assert_paired(stream: &mut TcpStream) {
+        assert!(matches!(
+            Message::read_from(stream).await.unwrap(),
+            Message::GameStart(_)
+        ));
+    }
+
+    async fn read_until_closed(mut stream: TcpStream) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        stream.read_to_end(&mut bytes).await.unwrap();
+        bytes
+    }
+
+    #[tokio::test]
+    async fn players_are_paired_by_lobby() {
+        let addr = start_matchmaker().await;
+        let mut first_a = join(addr, b"AAAA").await;
+        let b = join(addr, b"BBBB").await;
+        let mut second_a = join(addr, b"AAAA").await;
+        tokio::join!(assert_paired(&mut first_a), assert_paired(&mut second_a));
+
+        // No one else joined B's lobby, so they're sent away.
+        let started = tokio::time::Instant::now();
+        assert_eq!(read_until_closed(b).await, [5, ErrorCode::Timeout as u8]);
+        assert!(started.elapsed() < TEST_LOBBY_TIMEOUT * 2);
+    }
+
+    #[tokio::test]
+    async fn lobbies_are_separate_from_the_default_pool() {
+        let addr = start_matchmaker().await;
+        let mut first = enter(addr, "stranger").await;
+        let lobby = join(addr, b"AAAA").await;
+        let mut second = connect(addr, &[0, 0]).await;
+        tokio::join!(assert_paired(&mut first), assert_paired(&mut second));
+        assert_eq!(
+            read_until_closed(lobby).await,
+            [5, ErrorCode::Timeout as u8]
+        );
+    }
+
+    #[tokio::test]
+    async fn bad_handshake_only_costs_the_culprit() {
+        let addr = start_matchmaker().await;
+        let mut first = enter(addr, "stranger").await;
+        let bad = connect(addr, &[2, 3]).await;
+        // Whoever sent that never asked for a protocol version that has
+        // errors, so they don't get one.
+        assert_eq!(read_until_closed(bad).await, b"");
+        let mut second = enter(addr, "stranger").await;
+        tokio::join!(assert_paired(&mut first), assert_paired(&mut second));
+    }
+
+    #[tokio::test]
+    async fn garbled_openings_only_cost_the_culprit() {
+        let addr = start_matchmaker().await;
+        let mut first = enter(addr, "stranger").await;
+        // A tag that isn't one, a real tag with a card that isn't one, and
+        // nothing at all.
+        for opening in [&[0xfe, 0][..], &[2, 52], &[]] {
+            let mut bad = connect(addr, opening).await;
+            bad.shutdown().await.unwrap();
+            assert_eq!(read_until_closed(bad).await, b"", "{opening:?}");
+        }
+        let mut second = enter(addr, "stranger").await;
+        tokio::join!(assert_paired(&mut first), assert_paired(&mut second));
+    }
+
+    #[tokio::test]
+    async fn names_are_checked() {
+        let addr = start_matchmaker().await;
+        let mut hello = encoded(&[want_game(), hello("mallory")]);
+        hello[4] = 0x80;
+        let bad = connect(addr, &hello).await;
+        assert_eq!(
+            read_until_closed(bad).await,
+            [5, ErrorCode::BadHandshake as u8]
+        );
+
+        // Sending something else in its place is no better.
+        let forgot = connect(addr, &encoded(&[want_game(), Message::Rematch])).await;
+        assert_eq!(
+            read_until_closed(forgot).await,
+            [5, ErrorCode::BadHandshake as u8]
+        );
+    }
+
+    #[tokio::test]
+    async fn short_games_turn_away_clients_that_cant_be_dealt_them() {
+        let rules = Rules {
+            hand_size: Some(5),
+            ..Rules::default()
+        };
+        let addr = start_matchmaker_with(NO_HEARTBEAT, rules).await;
+        let v2 = Message::WantGame {
+            version: LONG_GAME_START_VERSION - 1,
+        };
+        let v2 = connect(addr, &encoded(&[v2, hello("v2")])).await;
+        assert_eq!(
+            read_until_closed(v2).await,
+            [5, ErrorCode::BadHandshake as u8]
+        );
+        // The original protocol has no errors, so they're just hung up on.
+        let legacy = connect(addr, &[0, 0]).await;
+        assert_eq!(read_until_closed(legacy).await, b"");
+
+        let mut first = enter(addr, "first").await;
+        let mut second = enter(addr, "second").await;
+        for stream in [&mut first, &mut second] {
+            let Message::GameStart(hand) = Message::read_from(stream).await.unwrap() else {
+                panic!("Expected GameStart.");
+            };
+            assert_eq!(hand.len(), 5);
+        }
+    }
+
+    #[tokio::test]
+    async fn only_new_clients_are_sent_server_info() {
+        let addr = start_matchmaker().await;
+        let older = Message::WantGame {
+            version: SERVER_INFO_VERSION - 1,
+        };
+        let mut older = connect(addr, &encoded(&[older, hello("older")])).await;
+        let mut newer = connect(addr, &encoded(&[want_game(), hello("newer")])).await;
+        let Message::ServerInfo {
+            proto_version,
+            flags,
+            build,
+        } = Message::read_from(&mut newer).await.unwrap()
+        else {
+            panic!("Expected ServerInfo.");
+        };
+        assert_eq!(proto_version, PROTOCOL_VERSION);
+        assert_eq!(flags, CAPABILITIES);
+        assert!(build.starts_with(env!("CARGO_PKG_VERSION").as_bytes()));
+        // The older client's first message is its hand.
+        tokio::join!(assert_paired(&mut older), assert_paired(&mut newer));
+    }
+
+    #[tokio::test]
+    async fn waiting_players_who_answer_pings_stay_in_line() {
+        let addr = start_matchmaker_with(TEST_HEARTBEAT, Rules::default()).await;
+        let mut first = enter(addr, "patient").await;
+        for nonce in 1..=3 {
+            assert_eq!(
+                Message::read_from(&mut first).await.unwrap(),
+                Message::Ping(nonce)
+            );
+            let (buf, len) = Message::Pong(nonce).encode();
+            first.write_all(&buf[..len]).await.unwrap();
+        }
+        let mut second = enter(addr, "late").await;
+        tokio::join!(assert_paired(&mut first), assert_paired(&mut second));
+    }
+
+    #[tokio::test]
+    async fn waiting_players_who_go_quiet_are_dropped() {
+        let addr = start_matchmaker_with(TEST_HEARTBEAT, Rules::default()).await;
+        let quiet = enter(addr, "quiet").await;
+        assert_eq!(
+            read_until_closed(quiet).await,
+            [12, 1, 5, ErrorCode::Timeout as u8]
+        );
+        // Their place in line went with them.
+        let mut first = enter(addr, "first").await;
+        let mut second = enter(addr, "second").await;
+        tokio::join!(assert_paired(&mut first), assert_paired(&mut second));
+    }
+
+    #[tokio::test]
+    async fn legacy_players_are_never_pinged() {
+        let addr = start_matchmaker_with(TEST_HEARTBEAT, Rules::default()).await;
+        let mut legacy = connect(addr, &[0, 0]).await;
+        tokio::time::sleep(TEST_HEARTBEAT.interval * 4).await;
+        let mut second = enter(addr, "stranger").await;
+        tokio::join!(assert_paired(&mut legacy), assert_paired(&mut second));
+    }
+
+    #[tokio::test]
+    async fn v2_magic_can_arrive_in_pieces() {
+        let addr = start_matchmaker().await;
+        let mut v2 = connect(addr, &V2_MAGIC[..2]).await;
+        v2.set_nodelay(true).unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        v2.write_all(&V2_MAGIC[2..]).await.unwrap();
+        for message in [want_game(), hello("split")] {
+            WireVersion::V2.write(&message, &mut v2).await.unwrap();
+        }
+        let mut legacy = connect(addr, &[0, 0]).await;
+
+        let mut magic = [0; V2_MAGIC.len()];
+        v2.read_exact(&mut magic).await.unwrap();
+        assert_eq!(magic, V2_MAGIC);
+        assert!(matches!(
+            WireVersion::V2.read(&mut v2).await.unwrap(),
+            Message::ServerInfo { .. }
+        ));
+        assert!(matches!(
+            WireVersion::V2.read(&mut v2).await.unwrap(),
+            Message::GameStart(_)
+        ));
+        assert_paired(&mut legacy).await;
+    }
+
+    #[tokio::test]
+    async fn a_slow_handshake_doesnt_hold_up_anyone_elses() {
+        let addr = start_matchmaker().await;
+        let delay = TEST_HANDSHAKE_TIMEOUT / 2;
+        let mut slow = TcpStream::connect(addr).await.unwrap();
+        let started = Instant::now();
+        let mut quick = enter(addr, "quick").await;
+        assert!(started.elapsed() < delay, "{:?}", started.elapsed());
+
+        tokio::time::sleep_until(started + delay).await;
+        slow.write_all(&[0, 0]).await.unwrap();
+        tokio::join!(assert_paired(&mut slow), assert_paired(&mut quick));
+    }
+
+    #[tokio::test]
+    async fn silent_connections_are_never_paired() {
+        let addr = start_matchmaker().await;
+        let connected = Instant::now();
+        let silent = TcpStream::connect(addr).await.unwrap();
+        let mut first = enter(addr, "first").await;
+        let mut second = enter(addr, "second").await;
+        tokio::join!(assert_paired(&mut first), assert_paired(&mut second));
+        assert_eq!(read_until_closed(silent).await, b"");
+        let hung_up = connected.elapsed();
+        assert!(hung_up >= TEST_HANDSHAKE_TIMEOUT, "{hung_up:?}");
+        assert!(hung_up < TEST_HANDSHAKE_TIMEOUT * 3 / 2, "{hung_up:?}");
+    }
+
+    #[tokio::test]
+    async fn players_who_hang_up_while_waiting_lose_their_place() {
+        let addr = start_matchmaker().await;
+        // Legacy players are never pinged, so nothing else notices them go.
+        let quitter = connect(addr, &[0, 0]).await;
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        drop(quitter);
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        let mut first = enter(addr, "first").await;
+        let mut second = enter(addr, "second").await;
+        tokio::time::timeout(Duration::from_secs(1), async {
+            tokio::join!(assert_paired(&mut first), assert_paired(&mut second))
+        })
+        .await
+        .unwrap();
+    }
+
+    /// Plays a whole game like the original client, in the order the cards
+    /// were dealt, until the server hangs up.
+    async fn play_legacy(addr: SocketAddr) {
+        let mut stream = connect(addr, &encoded(&[Message::WantGame { version: 0 }])).await;
+        let Message::GameStart(hand) = Message::read_from(&mut stream).await.unwrap() else {
+            panic!("Expected GameStart.");
+        };
+        for card in hand.iter() {
+            Message::PlayCard(card).write_to(&mut stream).await.unwrap();
+            Message::read_from(&mut stream).await.unwrap();
+        }
+        read_until_closed(stream).await;
+    }
+
+    #[tokio::test]
+    async fn every_game_is_logged_once_its_over() {
+        let logs = Logs::default();
+        let _capturing = logs.capture(LogFormat::Text);
+        let matchmaker = test_matchmaker(NO_HEARTBEAT, Rules::default());
+        let addr = serve(matchmaker.clone()).await;
+        tokio::join!(
+            play_legacy(addr),
+            play_legacy(addr),
+            play_legacy(addr),
+            play_legacy(addr)
+        );
+        tokio::time::timeout(Duration::from_secs(1), matchmaker.drained())
+            .await
+            .unwrap();
+
+        let logs = logs.contents();
+        let over: Vec<&str> = logs
+            .lines()
+            .filter(|line| line.contains("Game over"))
+            .collect();
+        assert_eq!(over.len(), 2, "{logs}");
+        for line in &over {
+            assert!(line.contains("rounds_played=26"), "{line}");
+        }
+        let game_id = |line: &str| {
+            line.split_whitespace()
+                .find(|word| word.contains("game_id="))
+                .map(str::to_owned)
+        };
+        assert_ne!(game_id(over[0]), game_id(over[1]));
+    }
+
+    #[tokio::test]
+    async fn a_game_panicking_doesnt_stop_anyone_else_playing() {
+        let rules = Rules {
+            panic_after_round: Some(1),
+            ..Rules::default()
+        };
+        let matchmaker = test_matchmaker(NO_HEARTBEAT, rules);
+        let addr = serve(matchmaker.clone()).await;
+        let (mut one, mut two) = tokio::join!(enter(addr, "one"), enter(addr, "two"));
+        let play_first_card = async |stream: &mut TcpStream| {
+            let Message::GameStart(hand) = Message::read_from(&mut *stream).await.unwrap() else {
+                panic!("Expected GameStart.");
+            };
+            Message::PlayCard(hand.cards()[0])
+                .write_to(stream)
+                .await
+                .unwrap();
+        };
+        tokio::join!(play_first_card(&mut one), play_first_card(&mut two));
+        tokio::time::timeout(Duration::from_secs(1), async {
+            tokio::join!(read_until_closed(one), read_until_closed(two))
+        })
+        .await
+        .unwrap();
+        tokio::time::timeout(Duration::from_secs(1), matchmaker.drained())
+            .await
+            .unwrap();
+        assert_eq!(matchmaker.games_panicked(), 1);
+        assert_eq!(matchmaker.games_failed(), 1);
+
+        let (mut three, mut four) = tokio::join!(enter(addr, "three"), enter(addr, "four"));
+        tokio::join!(assert_paired(&mut three), assert_paired(&mut four));
+    }
+
+    #[tokio::test]
+    async fn past_the_game_limit_pairs_wait_their_turn() {
+        let (matchmaker, mut games) = Matchmaker::new(
+            TEST_HANDSHAKE_TIMEOUT,
+            TEST_LOBBY_TIMEOUT,
+            TEST_PAIRING_TIMEOUT,
+            NO_HEARTBEAT,
+            Rules::default(),
+            WireFormat::Binary,
+        );
+        games.limit_to(NonZeroUsize::MIN);
+        tokio::spawn(games.run());
+        let addr = serve(matchmaker.clone()).await;
+        let (mut one, mut two) = tokio::join!(enter(addr, "one"), enter(addr, "two"));
+        tokio::join!(assert_paired(&mut one), assert_paired(&mut two));
+
+        let (mut three, mut four) = tokio::join!(enter(addr, "three"), enter(addr, "four"));
+        let waited = tokio::time::timeout(Duration::from_millis(200), async {
+            tokio::join!(assert_paired(&mut three), assert_paired(&mut four))
+        })
+        .await;
+        assert!(
+            waited.is_err(),
+            "The second game started alongside the first."
+        );
+        assert_eq!(matchmaker.live_games(), 2);
+
+        drop((one, two));
+        tokio::time::timeout(Duration::from_secs(1), async {
+            tokio::join!(assert_paired(&mut three), assert_paired(&mut four))
+        })
+        .await
+        .expect("The second game should've started once the first ended.");
+    }
+
+    #[tokio::test]
+    async fn closing_hangs_up_on_everyone_who_isnt_playing() {
+        let matchmaker = test_matchmaker(NO_HEARTBEAT, Rules::default());
+        let addr = serve(matchmaker.clone()).await;
+        let (mut one, mut two) = tokio::join!(enter(addr, "one"), enter(addr, "two"));
+        tokio::join!(assert_paired(&mut one), assert_paired(&mut two));
+        let early = enter(addr, "early").await;
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        matchmaker.close().await;
+        let gone = [5, SHUTTING_DOWN as u8];
+        assert_eq!(read_until_closed(early).await, gone);
+        let late = enter(addr, "late").await;
+        assert_eq!(read_until_closed(late).await, gone);
+        // The game goes on, until the players leave it.
+        assert_eq!(matchmaker.live_games(), 1);
+        drop((one, two));
+        tokio::time::timeout(Duration::from_secs(1), matchmaker.drained())
+            .await
+            .unwrap();
+        assert_eq!(matchmaker.live_games(), 0);
+    }
+
+    #[tokio::test]
+    async fn pairing_once_turns_away_everyone_after_the_first_game() {
+        let mut matchmaker = test_matchmaker(NO_HEARTBEAT, Rules::default());
+        matchmaker.pair_once();
+        let addr = serve(matchmaker.clone()).await;
+        let (mut one, mut two) = tokio::join!(enter(addr, "one"), enter(addr, "two"));
+        tokio::join!(assert_paired(&mut one), assert_paired(&mut two));
+        tokio::time::timeout(Duration::from_secs(1), matchmaker.paired_once())
+            .await
+            .expect("The game should've counted as the one game.");
+
+        let late = enter(addr, "late").await;
+        assert_eq!(read_until_closed(late).await, [5, SHUTTING_DOWN as u8]);
+        assert_eq!(matchmaker.live_games(), 1);
+    }
+
+    #[tokio::test]
+    async fn waiting_too_long_for_an_opponent_times_out() {
+        let pairing_timeout = Duration::from_millis(100);
+        let addr = serve(Matchmaker {
+            pairing_timeout,
+            ..test_matchmaker(NO_HEARTBEAT, Rules::default())
+        })
+        .await;
+
+        let lone = enter(addr, "lonely").await;
+        let started = Instant::now();
+        assert_eq!(read_until_closed(lone).await, [5, ErrorCode::Timeout as u8]);
+        assert!(started.elapsed() < pairing_timeout * 3 / 2);
+
+        // Someone who turns up in time still gets a game.
+        let mut first = enter(addr, "first").await;
+        tokio::time::sleep(pairing_timeout / 2).await;
+        let mut second = enter(addr, "second").await;
+        tokio::join!(assert_paired(&mut first), assert_paired(&mut second));
+    }
+
+    #[tokio::test]
+    async fn half_a_handshake_times_out() {
+        let addr = start_matchmaker().await;
+        // A legacy WantGame's tag, or the start of the v2 magic, and then
+        // nothing.
+        for opening in [&[0][..], &V2_MAGIC[..1]] {
+            let stalled = connect(addr, opening).await;
+            let started = tokio::time::Instant::now();
+            assert_eq!(read_until_closed(stalled).await, b"");
+            assert!(started.elapsed() < TEST_HANDSHAKE_TIMEOUT * 2);
+        }
+        // They never got in line, so they can't have taken anyone's place.
+        let mut first = connect(addr, &[0, 0]).await;
+        let mut second = connect(addr, &[0, 0]).await;
+        tokio::join!(assert_paired(&mut first), assert_paired(&mut second));
+    }
+}
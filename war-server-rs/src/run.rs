@@ -0,0 +1,364 @@
+//! A whole server: accepting connections until it's asked to stop, pairing
+//! the players who connect, playing their games, then waiting for those games
+//! to be over.
+
+#[cfg(unix)]
+use std::path::Path;
+use std::{
+    io,
+    net::SocketAddr,
+    num::NonZeroUsize,
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::Duration,
+};
+
+#[cfg(unix)]
+use tokio::net::UnixListener;
+use tokio::{
+    net::{TcpListener, TcpStream},
+    task::JoinSet,
+    time::Instant,
+};
+use tokio_rustls::TlsAcceptor;
+use tokio_stream::wrappers::TcpListenerStream;
+use tracing::{error, info, warn};
+use war_protocol::WireFormat;
+
+use crate::{
+    accept::{SocketOptions, accept_all},
+    matchmaker::{Heartbeat, Matchmaker},
+    session::Rules,
+    stream::{PlayerId, Stream},
+    tls,
+};
+
+/// How often to log how many games are left, while waiting for them to finish
+/// to shut down.
+const DRAIN_PROGRESS_EVERY: Duration = Duration::from_secs(1);
+
+/// How long aborted games have to hang up on their players.
+const ABORT_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Everything about how a server plays that isn't where it listens.
+#[derive(Debug, Clone, Copy)]
+pub struct ServerConfig {
+    pub rules: Rules,
+    /// What every client is expected to speak.
+    pub format: WireFormat,
+    /// How long a client has to finish its handshake before it's hung up on.
+    pub handshake_timeout: Duration,
+    /// How long a player in the default pool waits for an opponent.
+    pub pairing_timeout: Duration,
+    /// How long a player who joins a lobby waits for someone else to join it.
+    pub lobby_timeout: Duration,
+    /// How often players waiting for an opponent are pinged, and how long
+    /// they have to answer.
+    pub heartbeat: Heartbeat,
+    /// How many games can be played at once, if there's a limit.
+    pub max_games: Option<NonZeroUsize>,
+    /// What to set on every TCP connection as soon as it's accepted.
+    pub socket_options: SocketOptions,
+    /// How long games in progress have to finish once the server's asked to
+    /// stop, before they're aborted.
+    pub drain_timeout: Duration,
+    /// Whether to play just one game: to stop taking connections as soon as
+    /// the first two players are paired, and stop once their game is over,
+    /// without being asked to.
+    pub once: bool,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        ServerConfig {
+            rules: Rules::default(),
+            format: WireFormat::default(),
+            handshake_timeout: Duration::from_secs(5),
+            pairing_timeout: Duration::from_secs(120),
+            lobby_timeout: Duration::from_secs(120),
+            heartbeat: Heartbeat {
+                interval: Duration::from_secs(15),
+                timeout: Duration::from_secs(10),
+            },
+            max_games: None,
+            socket_options: SocketOptions {
+                keepalive: None,
+                nodelay: true,
+            },
+            drain_timeout: Duration::from_secs(30),
+            once: false,
+        }
+    }
+}
+
+/// Somewhere the server takes connections from.
+pub enum Listener {
+    Tcp(TcpListener),
+    /// TCP, but each connection goes through a TLS handshake with `acceptor`
+    /// before anything's read from it.
+    Tls {
+        listener: TcpListener,
+        acceptor: TlsAcceptor,
+    },
+    /// A Unix domain socket, which is removed from `path` once the server
+    /// stops accepting connections on it.
+    #[cfg(unix)]
+    Unix {
+        listener: UnixListener,
+        path: Arc<Path>,
+    },
+}
+
+impl Listener {
+    /// Where it's listening: an address, or a Unix socket's path.
+    fn place(&self) -> io::Result<String> {
+        match self {
+            Listener::Tcp(listener) | Listener::Tls { listener, .. } => {
+                Ok(listener.local_addr()?.to_string())
+            }
+            #[cfg(unix)]
+            Listener::Unix { path, .. } => Ok(path.display().to_string()),
+        }
+    }
+}
+
+impl From<TcpListener> for Listener {
+    fn from(listener: TcpListener) -> Listener {
+        Listener::Tcp(listener)
+    }
+}
+
+/// What a server did before it stopped.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ServerStats {
+    /// How many connections were accepted, from every listener.
+    pub connections: u64,
+    /// How many games were over by the time the server stopped, however
+    /// they ended, short of being aborted.
+    pub games_served: u64,
+    /// How many of those ended early, with a [`GameError`], rather than
+    /// being played out or forfeited.
+    ///
+    /// [`GameError`]: crate::GameError
+    pub games_failed: u64,
+    /// How many of those failed by panicking, which is always a bug in the
+    /// server.
+    pub games_panicked: u64,
+    /// How many games were aborted, because they were still being played
+    /// when the drain timeout ran out.
+    pub games_aborted: u64,
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum ServerError {
+    #[error("Can't tell where a listener is listening: {0}")]
+    Listen(io::Error),
+    #[error("Can't accept any more connections: {0}")]
+    Accept(io::Error),
+}
+
+/// Serves games to the players who connect to `listener`, with the default
+/// configuration, until `shutdown` finishes. Then, stops accepting
+/// connections, and returns once every game in progress is over (or has been
+/// aborted for taking too long).
+pub async fn run_server(
+    listener: TcpListener,
+    shutdown: impl Future<Output = ()>,
+) -> Result<ServerStats, ServerError> {
+    serve(ServerConfig::default(), vec![listener.into()], shutdown).await
+}
+
+/// Like [`run_server`], but configured by `config`, and taking connections
+/// from every one of `listeners`, with players from each of them paired with
+/// each other. If `config` says to play just one game, stops taking
+/// connections once it's paired, and returns once it's over, even if
+/// `shutdown` never finishes; if it does first, that game is only given the
+/// drain timeout to finish, like any other.
+pub async fn serve(
+    config: ServerConfig,
+    listeners: Vec<Listener>,
+    shutdown: impl Future<Output = ()>,
+) -> Result<ServerStats, ServerError> {
+    // Looked up before anything's started, so there's nothing to stop if one
+    // of them can't be.
+    let places = listeners
+        .iter()
+        .map(Listener::place)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(ServerError::Listen)?;
+    let (mut matchmaker, mut games) = Matchmaker::new(
+        config.handshake_timeout,
+        config.lobby_timeout,
+        config.pairing_timeout,
+        config.heartbeat,
+        config.rules,
+        config.format,
+    );
+    if let Some(max_games) = config.max_games {
+        games.limit_to(max_games);
+    }
+    if config.once {
+        matchmaker.pair_once();
+    }
+    tokio::spawn(games.run());
+    // Each acceptor accepts on a task of its own, counting what it accepts.
+    let mut accepting = JoinSet::new();
+    let mut acceptors = Vec::new();
+    #[cfg(unix)]
+    let mut sockets = Vec::new();
+    for (listener, place) in listeners.into_iter().zip(places) {
+        let accepted = Arc::new(AtomicU64::new(0));
+        acceptors.push((place, accepted.clone()));
+        let matchmaker = matchmaker.clone();
+        let (listener, tls) = match listener {
+            Listener::Tcp(listener) => (listener, None),
+            Listener::Tls { listener, acceptor } => (listener, Some(acceptor)),
+            #[cfg(unix)]
+            Listener::Unix { listener, path } => {
+                sockets.push(path.clone());
+                let incoming = tokio_stream::wrappers::UnixListenerStream::new(listener);
+                accepting.spawn(accept_all(incoming, move |stream| {
+                    let connection = accepted.fetch_add(1, Ordering::Relaxed) + 1;
+                    let id = PlayerId::Unix {
+                        path: path.clone(),
+                        connection,
+                    };
+                    tokio::spawn(matchmaker.clone().welcome((stream.into(), id)));
+                }));
+                continue;
+            }
+        };
+        let options = config.socket_options;
+        let handshake_timeout = config.handshake_timeout;
+        accepting.spawn(accept_all(
+            TcpListenerStream::new(listener),
+            move |stream| {
+                accepted.fetch_add(1, Ordering::Relaxed);
+                welcome(
+                    stream,
+                    options,
+                    tls.as_ref(),
+                    handshake_timeout,
+                    &matchmaker,
+                );
+            },
+        ));
+    }
+    let mut shutdown = std::pin::pin!(shutdown);
+    let mut asked_to_stop = false;
+    tokio::select! {
+        Some(accepted) = accepting.join_next() => {
+            return Err(match accepted {
+                Ok(Err(error)) => ServerError::Accept(error),
+                Ok(Ok(())) => unreachable!("Listeners never run out of connections."),
+                Err(error) => std::panic::resume_unwind(error.into_panic()),
+            });
+        }
+        () = &mut shutdown => asked_to_stop = true,
+        () = matchmaker.paired_once() => {}
+    }
+    accepting.shutdown().await;
+    #[cfg(unix)]
+    for path in sockets {
+        if let Err(error) = std::fs::remove_file(&path) {
+            warn!(%error, path = %path.display(), "Couldn't remove the socket");
+        }
+    }
+    let mut stats = ServerStats::default();
+    for (acceptor, (addr, accepted)) in acceptors.iter().enumerate() {
+        let accepted = accepted.load(Ordering::Relaxed);
+        stats.connections += accepted;
+        info!(acceptor, %addr, accepted, "Accepted connections");
+    }
+
+    // The listeners went with the acceptors, so no one new can connect.
+    if asked_to_stop {
+        info!(
+            games = matchmaker.live_games(),
+            "Shutting down once the games being played are over"
+        );
+    } else {
+        info!("Shutting down once the one game being played is over");
+    }
+    matchmaker.close().await;
+    // Only a server that's been asked to stop is in any hurry to.
+    let mut deadline = asked_to_stop.then(|| Instant::now() + config.drain_timeout);
+    let mut drained = std::pin::pin!(matchmaker.drained());
+    let mut progress =
+        tokio::time::interval_at(Instant::now() + DRAIN_PROGRESS_EVERY, DRAIN_PROGRESS_EVERY);
+    loop {
+        tokio::select! {
+            () = &mut drained => break,
+            _ = progress.tick(), if deadline.is_some() => {
+                info!(games = matchmaker.live_games(), "Waiting for games to finish");
+            }
+            () = &mut shutdown, if deadline.is_none() => {
+                deadline = Some(Instant::now() + config.drain_timeout);
+            }
+            () = sleep_until_some(deadline) => {
+                stats.games_aborted = matchmaker.live_games() as u64;
+                warn!(games = stats.games_aborted, "Games took too long to finish, aborting them");
+                matchmaker.abort_games();
+                let _ = tokio::time::timeout(ABORT_TIMEOUT, drained).await;
+                stats.games_served = matchmaker.games_served();
+                stats.games_failed = matchmaker.games_failed();
+                stats.games_panicked = matchmaker.games_panicked();
+                return Ok(stats);
+            }
+        }
+    }
+    info!("Every game is over");
+    stats.games_served = matchmaker.games_served();
+    stats.games_failed = matchmaker.games_failed();
+    stats.games_panicked = matchmaker.games_panicked();
+    Ok(stats)
+}
+
+/// Sleeps until `deadline`, if there is one, or forever otherwise.
+async fn sleep_until_some(deadline: Option<Instant>) {
+    match deadline {
+        Some(deadline) => tokio::time::sleep_until(deadline).await,
+        None => std::future::pending().await,
+    }
+}
+
+/// Gets a connection that was just accepted going on a task of its own,
+/// where it goes through a TLS handshake first, if there's a `tls` acceptor,
+/// which it has `handshake_timeout` to finish.
+fn welcome(
+    stream: TcpStream,
+    options: SocketOptions,
+    tls: Option<&TlsAcceptor>,
+    handshake_timeout: Duration,
+    matchmaker: &Matchmaker,
+) {
+    if let Err(error) = options.apply(&stream) {
+        warn!(%error, "Couldn't set socket options");
+    }
+    // IPv4 clients of a dual-stack listener show up as IPv6 addresses
+    // otherwise, like ::ffff:127.0.0.1.
+    let addr = match stream.peer_addr() {
+        Ok(addr) => SocketAddr::new(addr.ip().to_canonical(), addr.port()),
+        Err(error) => {
+            error!(%error, "Couldn't tell who connected");
+            return;
+        }
+    };
+    let matchmaker = matchmaker.clone();
+    let tls = tls.cloned();
+    tokio::spawn(async move {
+        let stream = match tls {
+            None => Stream::from(stream),
+            Some(tls) => match tls::accept(&tls, stream, handshake_timeout).await {
+                Ok(stream) => Stream::from(stream),
+                Err(error) => {
+                    info!(player = %addr, %error, "Botched its TLS handshake");
+                    return;
+                }
+            },
+        };
+        matchmaker.welcome((stream, addr.into())).await;
+    });
+}
@@ -1,100 +1,1784 @@
 use std::{
-    io::{Cursor, Write},
-    net::SocketAddr,
+    any::Any,
+    io::IoSlice,
+    panic::AssertUnwindSafe,
+    pin::Pin,
+    sync::atomic::{AtomicU64, Ordering},
+    task::{Context, Poll},
+    time::{Duration, Instant},
 };
 
-use rand::seq::SliceRandom;
-use tokio::{
-    io::{AsyncReadExt, AsyncWriteExt},
-    net::TcpStream,
+use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt, sink};
+use tracing::{Instrument, Span, debug, info, info_span};
+
+use war_protocol::*;
+
+use crate::{
+    session::*,
+    stream::{PlayerId, Stream},
 };
 
-use crate::format::*;
+/// Two players seated at the same table, connected over streams of type `S`:
+/// any [`Stream`] the server accepted, unless it's a test.
+pub struct Game<S = Stream> {
+    pub id: GameId,
+    pub player_one: Player<S>,
+    pub player_two: Player<S>,
+    pub rules: Rules,
+}
 
-pub struct Game {
-    pub player_one: (TcpStream, SocketAddr),
-    pub player_two: (TcpStream, SocketAddr),
+impl<S> Game<S> {
+    /// Where everything logged about the game goes.
+    pub fn span(&self) -> Span {
+        info_span!(
+            "game",
+            game_id = self.id.0,
+            p1 = %self.player_one.id,
+            p2 = %self.player_two.id
+        )
+    }
 }
 
-pub async fn serve_game(mut game: Game) {
-    // TODO: Make this concurrent. If one client hangs sends a malformed
-    // message, we should terminate the game. As it stands, we could hang on the
-    // first client indefinitely.
-    let mut scratch = [0; 27];
-    game.player_one
-        .0
-        .read_exact(&mut scratch[..2])
-        .await
-        .unwrap();
-    assert_eq!(&scratch[..2], Message::WantGame.as_ref());
+/// One seat at a game, along with how that player's client frames its messages.
+pub struct Player<S = Stream> {
+    stream: S,
+    id: PlayerId,
+    format: WireFormat,
+    wire: WireVersion,
+    /// The protocol version agreed on in the handshake.
+    version: u8,
+    /// What the client said to call them, if anything.
+    name: Option<PlayerName>,
+    /// Where each binary frame is encoded on its way out, reused for the
+    /// whole connection.
+    out: [u8; MAX_FRAME_LEN],
+}
 
-    game.player_two
-        .0
-        .read_exact(&mut scratch[..2])
-        .await
-        .unwrap();
+/// Tells games apart in the logs: everything logged about a game is in its
+/// `game` span, which carries its ID, like `game_id=42`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GameId(u64);
+
+impl GameId {
+    /// An ID no other game has had since the server started.
+    pub fn next() -> GameId {
+        static NEXT: AtomicU64 = AtomicU64::new(1);
+        GameId(NEXT.fetch_add(1, Ordering::Relaxed))
+    }
+}
+
+impl std::fmt::Display for GameId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// How the last game between two players went, once they're done playing.
+#[derive(Debug, Clone, Copy)]
+pub struct GameOutcome {
+    /// Which game it was, the same as in its span in the logs.
+    pub game_id: GameId,
+    pub reason: EndReason,
+    pub rounds_played: u8,
+    pub player_one_score: u8,
+    pub player_two_score: u8,
+    /// Who won, or `None` for a draw.
+    pub winner: Option<Seat>,
+    /// How long the players were at the table, rematches and all.
+    pub duration: Duration,
+}
+
+/// Why the last game between two players ended, when it was played out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EndReason {
+    /// Every card was played.
+    Finished,
+    /// The loser gave up.
+    Forfeit,
+    /// The loser took too long to make a move.
+    MoveTimeout,
+}
+
+/// Why a client never got to play, or a game couldn't be played out.
+#[derive(thiserror::Error, Debug)]
+pub enum GameError {
+    #[error("{player} botched its handshake: {code:?}")]
+    HandshakeFailed { player: PlayerId, code: ErrorCode },
+    #[error("{player} hung up partway through the game: {error}")]
+    PlayerDisconnected { player: PlayerId, error: Error },
+    #[error("{player} broke the protocol: {code:?}")]
+    ProtocolViolation { player: PlayerId, code: ErrorCode },
+    #[error("{player} {cheat}")]
+    Cheated { player: PlayerId, cheat: Cheat },
+    #[error("{player} took too long to play")]
+    Timeout { player: PlayerId },
+    #[error("The server shut down before the game was over")]
+    Aborted,
+    #[error("The server crashed partway through the game: {message}")]
+    Panicked { message: String },
+    #[error("The deck was dealt wrong: {0}")]
+    Misdealt(DealError),
+}
+
+/// What players are told when the server shuts down without them having
+/// played a whole game. There's no code for that, but this one at least says
+/// they did nothing wrong.
+pub const SHUTTING_DOWN: ErrorCode = ErrorCode::OpponentGone;
+
+/// What players are told when the server crashes partway through their game,
+/// or deals it wrong. Like with [`SHUTTING_DOWN`], there's no code for that.
+const CRASHED: ErrorCode = ErrorCode::OpponentGone;
+
+/// How long to wait for a client we're hanging up on to hang up too.
+const LINGER: Duration = Duration::from_secs(1);
+
+/// Everything this server does beyond the original protocol, as told to
+/// clients in their ServerInfo.
+pub const CAPABILITIES: Capabilities = Capabilities::GAME_OVER
+    .union(Capabilities::REMATCH)
+    .union(Capabilities::HEARTBEAT)
+    .union(Capabilities::LOBBIES);
+
+/// Which build of the server this is, as told to clients in their ServerInfo:
+/// as much of its version as fits, padded out with NULs.
+const BUILD: [u8; BUILD_LEN] = build(env!("CARGO_PKG_VERSION"));
+
+const fn build(version: &str) -> [u8; BUILD_LEN] {
+    let mut build = [0; BUILD_LEN];
+    let mut i = 0;
+    while i < BUILD_LEN && i < version.len() {
+        build[i] = version.as_bytes()[i];
+        i += 1;
+    }
+    build
+}
+
+impl<S> Player<S> {
+    pub fn new((stream, id): (S, PlayerId), format: WireFormat) -> Player<S> {
+        Player {
+            stream,
+            id,
+            format,
+            wire: WireVersion::default(),
+            version: 0,
+            name: None,
+            out: [0; MAX_FRAME_LEN],
+        }
+    }
+
+    pub fn id(&self) -> &PlayerId {
+        &self.id
+    }
+
+    /// Whether this client only knows the original protocol, and so must
+    /// never be sent anything it wouldn't have gotten from the original
+    /// server.
+    fn is_legacy(&self) -> bool {
+        self.version == 0
+    }
+
+    /// Whether this client can be dealt a game played with `rules`.
+    pub fn can_play(&self, rules: &Rules) -> bool {
+        rules.hand_size() == HAND_SIZE || self.version >= LONG_GAME_START_VERSION
+    }
+
+    /// Whether this client answers Pings while it waits for an opponent.
+    pub fn answers_pings(&self) -> bool {
+        self.version >= PING_VERSION
+    }
+}
+
+impl Player<Stream> {
+    /// Whether the client has hung up, as far as we know right now, without
+    /// waiting to find out or reading anything they've sent.
+    pub fn has_hung_up(&self) -> bool {
+        self.stream.has_hung_up()
+    }
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin> Player<S> {
+    /// Makes sure the client's still there: sends a Ping, and waits up to
+    /// `timeout` for a Pong with the same `nonce`.
+    pub async fn check_in(&mut self, nonce: u8, timeout: Duration) -> Result<(), ErrorCode> {
+        if self.write(&Message::Ping(nonce)).await.is_err() {
+            return Err(ErrorCode::Malformed);
+        }
+        match tokio::time::timeout(timeout, self.read()).await {
+            Ok(Ok(Message::Pong(answer))) if answer == nonce => Ok(()),
+            Ok(_) => Err(ErrorCode::Malformed),
+            Err(_) => Err(ErrorCode::Timeout),
+        }
+    }
+
+    /// Reads the client's WantGame (or JoinLobby), and their Hello if they
+    /// speak a new enough version to send one, agreeing to v2 framing if
+    /// that's what the client opened with, then sends a ServerInfo to clients
+    /// that understand one. Returns the lobby they want to play in, if they
+    /// named one.
+    pub async fn greet(&mut self) -> Result<Option<LobbyCode>, ErrorCode> {
+        let (wire, version, lobby) = match self.format.read_opening(&mut self.stream).await {
+            Ok((wire, Message::WantGame { version })) => (wire, version, None),
+            Ok((wire, Message::JoinLobby { version, code })) => (wire, version, Some(code)),
+            Ok(_) => return Err(ErrorCode::BadHandshake),
+            Err(error) => {
+                debug!(player = %self, %error, "Couldn't read how the client opened");
+                return Err(ErrorCode::BadHandshake);
+            }
+        };
+        self.wire = wire;
+        self.version = version.min(PROTOCOL_VERSION);
+        if let Some(magic) = wire.magic()
+            && send_all(&mut self.stream, &mut [IoSlice::new(&magic)])
+                .await
+                .is_err()
+        {
+            return Err(ErrorCode::BadHandshake);
+        }
+        if self.version >= 2 {
+            let Ok(Message::Hello(name)) = self.read().await else {
+                return Err(ErrorCode::BadHandshake);
+            };
+            self.name = Some(name);
+        }
+        if self.version >= SERVER_INFO_VERSION {
+            let info = Message::ServerInfo {
+                proto_version: self.version,
+                flags: CAPABILITIES,
+                build: BUILD,
+            };
+            if self.write(&info).await.is_err() {
+                return Err(ErrorCode::BadHandshake);
+            }
+        }
+        Ok(lobby)
+    }
+
+    /// Reads the client's move this round, or why it couldn't be read.
+    async fn read_move(&mut self) -> Result<Message, Error> {
+        match self.read().await {
+            Ok(message) => Ok(message),
+            Err(error) => Err(error.into()),
+        }
+    }
+
+    /// Tells the client why we're hanging up on them (if they're still
+    /// listening, and would understand), then does so.
+    pub async fn abort(&mut self, code: ErrorCode) {
+        if !self.is_legacy() {
+            // Best effort: if they're already gone, there's no one to tell.
+            let _ = self.write(&Message::Error(code)).await;
+        }
+        self.hang_up().await;
+    }
 
-    assert_eq!(&scratch[..2], Message::WantGame.as_ref());
+    async fn hang_up(&mut self) {
+        let _ = self.stream.shutdown().await;
+        // Closing a socket with unread bytes in it makes the kernel reset the
+        // connection, which can destroy the error before the client reads it.
+        // So, give them a moment to hang up first, throwing away whatever they
+        // send in the meantime.
+        let _ = tokio::time::timeout(LINGER, tokio::io::copy(&mut self.stream, &mut sink())).await;
+    }
+
+    async fn read(&mut self) -> Result<Message, MessageReadError> {
+        self.format.read(self.wire, &mut self.stream).await
+    }
 
-    // TODO: Consider https://docs.rs/rand/latest/rand/seq/trait.IteratorRandom.html#method.choose_multiple_fill.
-    let mut all_cards_cursor = Cursor::new([0u8; NUM_CARDS_TOTAL as usize]);
-    for c in 0..51 {
-        all_cards_cursor.write_all(&[c]).unwrap();
+    async fn write(&mut self, message: impl Into<MessageRef<'_>>) -> std::io::Result<()> {
+        match self.format {
+            WireFormat::Binary => {
+                let parts = self.wire.encode_split(message, &mut self.out);
+                send_all(&mut self.stream, &mut parts.map(IoSlice::new)).await
+            }
+            WireFormat::Json => {
+                let line = message.into().to_message().to_json();
+                let mut parts = [IoSlice::new(line.as_bytes()), IoSlice::new(b"\n")];
+                send_all(&mut self.stream, &mut parts).await
+            }
+        }
     }
-    // Forreal? There's *gotta* be a safe way to do this.
-    let mut all_cards = unsafe {
-        std::mem::transmute::<_, [Card; NUM_CARDS_TOTAL as usize]>(all_cards_cursor.into_inner())
+
+    /// Sends `messages` one after the other, all in one write.
+    async fn write_batch(&mut self, messages: &[&Message]) -> std::io::Result<()> {
+        send_batch(&mut self.stream, self.format, self.wire, messages).await
+    }
+}
+
+/// The most a batch of frames sent together can take up. Nothing ever sends
+/// more than two messages to a player at once: a PlayResult and the GameOver
+/// after it, at most.
+const BATCH_LEN: usize = 2 * MAX_FRAME_LEN;
+
+/// Sends `messages` to a client speaking `format`, framed for `wire`, in one
+/// write, as long as they all fit in [`BATCH_LEN`] bytes. Otherwise, whatever
+/// doesn't fit goes out in a write of its own.
+async fn send_batch<W: AsyncWrite + Unpin>(
+    writer: &mut W,
+    format: WireFormat,
+    wire: WireVersion,
+    messages: &[&Message],
+) -> std::io::Result<()> {
+    match format {
+        WireFormat::Binary => {
+            let mut batch = MessageBuffer::<BATCH_LEN>::new();
+            for &message in messages {
+                if batch.push_frame(wire, message).is_err() {
+                    send_all(writer, &mut [IoSlice::new(batch.as_bytes())]).await?;
+                    batch.clear();
+                    batch
+                        .push_frame(wire, message)
+                        .expect("Every frame fits in an empty batch.");
+                }
+            }
+            send_all(writer, &mut [IoSlice::new(batch.as_bytes())]).await
+        }
+        WireFormat::Json => {
+            let mut lines = String::new();
+            for message in messages {
+                lines.push_str(&message.to_json());
+                lines.push('\n');
+            }
+            send_all(writer, &mut [IoSlice::new(lines.as_bytes())]).await
+        }
+    }
+}
+
+/// Writes all of `bufs`, one after the other, in a single write unless
+/// `writer` won't take everything at once, then flushes it, for writers like
+/// TLS that can hold on to what's written. Everything sent to a client goes
+/// through here, so each message leaves in one piece, instead of one piece
+/// per part of it.
+pub async fn send_all<W: AsyncWrite + Unpin>(
+    writer: &mut W,
+    mut bufs: &mut [IoSlice<'_>],
+) -> std::io::Result<()> {
+    IoSlice::advance_slices(&mut bufs, 0);
+    while !bufs.is_empty() {
+        let written = writer.write_vectored(bufs).await?;
+        if written == 0 {
+            return Err(std::io::ErrorKind::WriteZero.into());
+        }
+        IoSlice::advance_slices(&mut bufs, written);
+    }
+    writer.flush().await
+}
+
+/// How a player shows up in the logs: who they are (usually their address),
+/// then their name if they gave one.
+impl<S> std::fmt::Display for Player<S> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.id)?;
+        if let Some(name) = &self.name {
+            write!(f, " {name:?}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Plays games between two players who have already been greeted, until one
+/// of them doesn't want a rematch, returning how the last game went. If one of
+/// them hangs up partway through a game, their opponent is told. If `abort`
+/// finishes first, both of them are told the server is shutting down, and
+/// hung up on.
+pub async fn serve_game<S: AsyncRead + AsyncWrite + Unpin>(
+    game: Game<S>,
+    abort: impl Future<Output = ()>,
+) -> Result<GameOutcome, GameError> {
+    let span = game.span();
+    async move {
+        info!("Seated");
+        play_games(game, abort).await
+    }
+    .instrument(span)
+    .await
+}
+
+async fn play_games<S: AsyncRead + AsyncWrite + Unpin>(
+    game: Game<S>,
+    abort: impl Future<Output = ()>,
+) -> Result<GameOutcome, GameError> {
+    let seated = Instant::now();
+    let Game {
+        id,
+        player_one,
+        player_two,
+        rules,
+    } = game;
+    let mut players = [player_one, player_two];
+    // If the game panics, the players are still here to be hung up on.
+    let playing = CatchUnwind(Box::pin(async {
+        loop {
+            match play_one_game(&mut players, rules).await {
+                Ok((Ending::Finished, _)) if rematch(&mut players).await => {}
+                played => break played,
+            }
+        }
+    }));
+    // Whatever the players were in the middle of sending or being sent is
+    // thrown away, but they're about to be hung up on anyway.
+    let played = tokio::select! {
+        played = playing => played.map_err(|panic| GameError::Panicked {
+            message: panic_message(&*panic),
+        }),
+        () = abort => Err(GameError::Aborted),
+    };
+    let [player_one, player_two] = &mut players;
+    let played = match played {
+        Err(error) | Ok(Err(error @ GameError::Misdealt(_))) => {
+            let code = match error {
+                GameError::Panicked { .. } | GameError::Misdealt(_) => CRASHED,
+                _ => SHUTTING_DOWN,
+            };
+            tokio::join!(player_one.abort(code), player_two.abort(code));
+            return Err(error);
+        }
+        Ok(played) => played,
     };
-    // TODO: Does this care at all about PartialEq? Surely not. It better not!
-    all_cards.shuffle(&mut rand::rng());
+    tokio::join!(player_one.hang_up(), player_two.hang_up());
 
-    dbg!(all_cards);
+    let (ending, score) = played?;
+    let player = |seat: Seat| players[seat.index()].id.clone();
+    let (reason, winner) = match ending {
+        Ending::Finished => match score.winner() {
+            Some(RoundResult::Win) => (EndReason::Finished, Some(Seat::One)),
+            Some(RoundResult::Lose) => (EndReason::Finished, Some(Seat::Two)),
+            _ => (EndReason::Finished, None),
+        },
+        Ending::Forfeited(quitter) => (EndReason::Forfeit, Some(quitter.other())),
+        Ending::TimedOut(staller) => (EndReason::MoveTimeout, Some(staller.other())),
+        Ending::Faulted {
+            culprit,
+            code: ErrorCode::Timeout,
+        } => {
+            return Err(GameError::Timeout {
+                player: player(culprit),
+            });
+        }
+        Ending::Faulted { culprit, code } => {
+            return Err(GameError::ProtocolViolation {
+                player: player(culprit),
+                code,
+            });
+        }
+        Ending::Cheated { culprit, cheat } => {
+            return Err(GameError::Cheated {
+                player: player(culprit),
+                cheat,
+            });
+        }
+        Ending::Abandoned(_) => unreachable!("Hanging up is returned as an error."),
+    };
+    Ok(GameOutcome {
+        game_id: id,
+        reason,
+        rounds_played: score.rounds_played(),
+        player_one_score: score.wins(),
+        player_two_score: score.losses(),
+        winner,
+        duration: seated.elapsed(),
+    })
+}
 
-    let mut player_one_hand = [Card::default(); 26];
-    let mut player_two_hand = [Card::default(); 26];
-    player_one_hand.copy_from_slice(&all_cards[..26]);
-    player_two_hand.copy_from_slice(&all_cards[26..]);
+/// Polls a future, but if it panics, finishes with what it panicked with
+/// instead of unwinding any further.
+struct CatchUnwind<F>(Pin<Box<F>>);
 
-    game.player_one
-        .0
-        .write_all(Message::GameStart(player_one_hand).as_ref())
-        .await
-        .unwrap();
-    game.player_two
-        .0
-        .write_all(Message::GameStart(player_two_hand).as_ref())
-        .await
-        .unwrap();
-    for _ in 0..26 {
-        let play_card_message_buffer = &mut scratch[..2];
-        // TODO: Implement game logic:
-        game.player_one
-            .0
-            .read_exact(play_card_message_buffer)
+impl<F: Future> Future for CatchUnwind<F> {
+    type Output = Result<F::Output, Box<dyn Any + Send>>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        // The future's never polled again after it panics, so whatever state
+        // it was left in can't be seen.
+        match std::panic::catch_unwind(AssertUnwindSafe(|| self.0.as_mut().poll(cx))) {
+            Ok(poll) => poll.map(Ok),
+            Err(panic) => Poll::Ready(Err(panic)),
+        }
+    }
+}
+
+/// What a panic was about, going by what it panicked with.
+pub fn panic_message(panic: &(dyn Any + Send)) -> String {
+    if let Some(message) = panic.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = panic.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "(no message)".to_string()
+    }
+}
+
+/// How long both players have after a game to ask for a rematch.
+const REMATCH_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Deals a fresh game on the same connections and plays it to the end, or
+/// until one of the players hangs up, returning how it ended and the score.
+async fn play_one_game<S: AsyncRead + AsyncWrite + Unpin>(
+    players: &mut [Player<S>; 2],
+    rules: Rules,
+) -> Result<(Ending, Score), GameError> {
+    let deck = Deck::shuffled_together(rules.decks, &mut rand::rng());
+    let legacy = players.each_ref().map(|player| player.is_legacy());
+    let hands = deck.deal_hands(rules.hand_size());
+    #[cfg(test)]
+    let hands = if rules.misdeal {
+        (hands.0, hands.0)
+    } else {
+        hands
+    };
+    let mut session = GameSession::new(rules, hands, legacy);
+    let hands = session.start().map_err(GameError::Misdealt)?;
+    let mut sent = Ok(());
+    for (seat, hand) in [Seat::One, Seat::Two].into_iter().zip(hands) {
+        if rules.log_hands {
+            let player = &players[seat.index()];
+            debug!(%player, hand = %format_args!("{:#}", Message::GameStart(*hand)), "Dealt");
+        }
+        if let Err(error) = players[seat.index()]
+            .write(MessageRef::GameStart(hand))
+            .await
+        {
+            sent = sent.and(Err((seat, error.into())));
+        }
+    }
+    // Reads aren't cancel-safe, so instead of waiting on both players at once,
+    // this only ever reads from the one the session is waiting on.
+    while sent.is_ok()
+        && let Some(seat) = session.waiting_on()
+    {
+        let player = &mut players[seat.index()];
+        // Whatever part of a move was read when time runs out is thrown
+        // away, but the game's over by then anyway.
+        let read = tokio::time::timeout(rules.move_timeout, player.read_move()).await;
+        match &read {
+            Ok(Ok(_)) => {}
+            Ok(Err(error)) => info!(%player, %error, "Couldn't read a move"),
+            Err(_) => info!(%player, "Took too long to make a move"),
+        }
+        let before = session.score();
+        let outgoing = match read {
+            Ok(Ok(message)) => session.on_message(seat, message),
+            Ok(Err(error)) if hung_up(&error) => {
+                sent = Err((seat, error));
+                break;
+            }
+            Ok(Err(error)) => session.on_fault(seat, error.code()),
+            Err(_) => session.on_timeout(seat),
+        };
+        let after = session.score();
+        if after.rounds_played() > before.rounds_played() {
+            let result = if after.wins() > before.wins() {
+                RoundResult::Win
+            } else if after.losses() > before.losses() {
+                RoundResult::Lose
+            } else {
+                RoundResult::Draw
+            };
+            debug!(
+                round = after.rounds_played(),
+                player_one = ?result,
+                score = %after,
+                "Round over"
+            );
+            #[cfg(test)]
+            if rules.panic_after_round == Some(after.rounds_played()) {
+                panic!(
+                    "Panicking after round {}, like the test asked.",
+                    after.rounds_played()
+                );
+            }
+        }
+        sent = send(players, &session, outgoing).await;
+    }
+    if let Err((quitter, error)) = sent {
+        if session.ending().is_none() {
+            let outgoing = session.on_hang_up(quitter);
+            // Everything sent to either player now is a courtesy.
+            let _ = send(players, &session, outgoing).await;
+        }
+        return Err(GameError::PlayerDisconnected {
+            player: players[quitter.index()].id.clone(),
+            error,
+        });
+    }
+    let ending = session
+        .ending()
+        .expect("The session stopped waiting before the game was over.");
+    if ending == Ending::Finished {
+        info!(score = %session.score(), "Played to the end");
+    }
+    Ok((ending, session.score()))
+}
+
+/// Whether `error` means the player's connection is gone, rather than that
+/// they sent something wrong.
+fn hung_up(error: &Error) -> bool {
+    use std::io::ErrorKind::*;
+    matches!(
+        error,
+        Error::Io(error)
+            if matches!(error.kind(), UnexpectedEof | ConnectionReset | ConnectionAborted | BrokenPipe)
+    )
+}
+
+/// Sends each player what the session said to, everything for each of them
+/// in one write, returning the first player who couldn't be reached, and why.
+/// Once the game's been forfeited or can't go on, what's left is only a
+/// courtesy, so it's fine if it never arrives.
+async fn send<S: AsyncRead + AsyncWrite + Unpin>(
+    players: &mut [Player<S>; 2],
+    session: &GameSession,
+    outgoing: Outgoing,
+) -> Result<(), (Seat, Error)> {
+    let mut sent = Ok(());
+    for seat in [Seat::One, Seat::Two] {
+        let messages: Vec<_> = outgoing
+            .iter()
+            .filter(|(to, _)| *to == seat)
+            .map(|(_, message)| message)
+            .collect();
+        if messages.is_empty() {
+            continue;
+        }
+        let Err(error) = players[seat.index()].write_batch(&messages).await else {
+            continue;
+        };
+        match session.ending() {
+            Some(Ending::Forfeited(quitter) | Ending::TimedOut(quitter)) if quitter == seat => {}
+            Some(Ending::Faulted { .. } | Ending::Cheated { .. } | Ending::Abandoned(_)) => {}
+            _ => sent = sent.and(Err((seat, error.into()))),
+        }
+    }
+    sent
+}
+
+/// Waits (up to `REMATCH_TIMEOUT`) to see whether both players want to play
+/// again. Legacy clients are never told when a game is over, so if either
+/// player is one, they're hung up on right away, like always.
+async fn rematch<S: AsyncRead + AsyncWrite + Unpin>(
+    [player_one, player_two]: &mut [Player<S>; 2],
+) -> bool {
+    if player_one.is_legacy() || player_two.is_legacy() {
+        return false;
+    }
+    let wants_rematch = async |player: &mut Player<S>| {
+        let answer = tokio::time::timeout(REMATCH_TIMEOUT, player.read()).await;
+        match answer {
+            Ok(Ok(Message::Rematch)) => Ok(()),
+            _ => Err(()),
+        }
+    };
+    // As soon as one of them says no (or hangs up), there's no point waiting
+    // for the other.
+    tokio::try_join!(wants_rematch(player_one), wants_rematch(player_two)).is_ok()
+}
+
+#[cfg(test)]
+mod test {
+    use std::net::SocketAddr;
+
+    use tokio::{
+        io::{AsyncBufReadExt, AsyncReadExt, DuplexStream},
+        net::{TcpListener, TcpStream},
+        task::JoinHandle,
+    };
+
+    use super::*;
+    use crate::logging::{LogFormat, test::Logs};
+
+    /// Accepts two connections on an ephemeral port, greets them, and serves
+    /// them one game.
+    async fn start_game() -> (SocketAddr, JoinHandle<Result<GameOutcome, GameError>>) {
+        start_game_speaking(WireFormat::Binary).await
+    }
+
+    async fn start_game_speaking(
+        format: WireFormat,
+    ) -> (SocketAddr, JoinHandle<Result<GameOutcome, GameError>>) {
+        start_game_with(format, Rules::default()).await
+    }
+
+    async fn start_game_with(
+        format: WireFormat,
+        rules: Rules,
+    ) -> (SocketAddr, JoinHandle<Result<GameOutcome, GameError>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = tokio::spawn(async move {
+            let greet = async || {
+                let (stream, addr) = listener.accept().await.unwrap();
+                let mut player = Player::new((Stream::from(stream), addr.into()), format);
+                player.greet().await.unwrap();
+                player
+            };
+            let player_one = greet().await;
+            let player_two = greet().await;
+            serve_game(
+                Game {
+                    id: GameId::next(),
+                    player_one,
+                    player_two,
+                    rules,
+                },
+                std::future::pending(),
+            )
+            .await
+        });
+        (addr, server)
+    }
+
+    /// Seats `clients` at a game of their own, each speaking binary over one
+    /// end of an in-memory pipe, greets them, and serves them one game. Each
+    /// is known by its label in what the game returns.
+    async fn start_game_over_pipes(
+        clients: [(&'static str, WireVersion, u8); 2],
+        rules: Rules,
+    ) -> (
+        [Client<DuplexStream>; 2],
+        JoinHandle<Result<GameOutcome, GameError>>,
+    ) {
+        let mut players = Vec::new();
+        let mut opened = Vec::new();
+        for (label, wire, version) in clients {
+            // Roomy enough that neither end ever waits on the other for more
+            // than a round.
+            let (ours, theirs) = tokio::io::duplex(4 * BATCH_LEN);
+            players.push(Player::new(
+                (ours, PlayerId::Label(label)),
+                WireFormat::Binary,
+            ));
+            opened.push(Client::open(theirs, wire, version).await);
+        }
+        let server = tokio::spawn(async move {
+            for player in &mut players {
+                player.greet().await.unwrap();
+            }
+            let [player_one, player_two] = players.try_into().ok().unwrap();
+            let id = GameId::next();
+            let served = serve_game(
+                Game {
+                    id,
+                    player_one,
+                    player_two,
+                    rules,
+                },
+                std::future::pending(),
+            )
+            .await;
+            if let Ok(outcome) = &served {
+                assert_eq!(outcome.game_id, id);
+            }
+            served
+        });
+        (opened.try_into().ok().unwrap(), server)
+    }
+
+    /// A scripted client, connected over TCP unless it's said otherwise.
+    struct Client<S = TcpStream> {
+        stream: S,
+        wire: WireVersion,
+        /// Whether the server has yet to answer our v2 magic.
+        awaiting_magic: bool,
+        /// Whether the server has yet to send us its ServerInfo.
+        awaiting_info: bool,
+    }
+
+    impl Client {
+        /// Connects and sends WantGame, without waiting for a reply, since
+        /// there won't be one until the other player connects.
+        async fn connect(addr: SocketAddr, wire: WireVersion, version: u8) -> Client {
+            Client::open(TcpStream::connect(addr).await.unwrap(), wire, version).await
+        }
+    }
+
+    impl<S: AsyncRead + AsyncWrite + Unpin> Client<S> {
+        /// Sends WantGame over `stream`, and Hello if `version` calls for
+        /// one, without waiting for a reply.
+        async fn open(mut stream: S, wire: WireVersion, version: u8) -> Client<S> {
+            if let Some(magic) = wire.magic() {
+                stream.write_all(&magic).await.unwrap();
+            }
+            wire.write(&Message::WantGame { version }, &mut stream)
+                .await
+                .unwrap();
+            if version >= 2 {
+                let name = PlayerName::try_from("test").unwrap();
+                wire.write(&Message::Hello(name), &mut stream)
+                    .await
+                    .unwrap();
+            }
+            Client {
+                stream,
+                wire,
+                awaiting_magic: wire.magic().is_some(),
+                awaiting_info: version >= SERVER_INFO_VERSION,
+            }
+        }
+
+        /// Reads the server's side of the handshake, if it hasn't been read
+        /// already.
+        async fn greeted(&mut self) {
+            if std::mem::take(&mut self.awaiting_magic) {
+                let mut magic = [0; V2_MAGIC.len()];
+                self.stream.read_exact(&mut magic).await.unwrap();
+                assert_eq!(Some(magic), self.wire.magic());
+            }
+            if std::mem::take(&mut self.awaiting_info) {
+                let Message::ServerInfo {
+                    proto_version,
+                    flags,
+                    build,
+                } = self.recv().await
+                else {
+                    panic!("Expected ServerInfo.");
+                };
+                assert_eq!(proto_version, PROTOCOL_VERSION);
+                assert_eq!(flags, CAPABILITIES);
+                assert_eq!(build, BUILD);
+            }
+        }
+
+        /// Waits for the game to start, returning the dealt hand.
+        async fn start(&mut self) -> Hand {
+            self.greeted().await;
+            let Message::GameStart(hand) = self.recv().await else {
+                panic!("Expected GameStart.");
+            };
+            validate_hand(&hand).unwrap();
+            hand
+        }
+
+        async fn send(&mut self, message: Message) {
+            self.wire.write(&message, &mut self.stream).await.unwrap();
+        }
+
+        async fn recv(&mut self) -> Message {
+            self.wire.read(&mut self.stream).await.unwrap()
+        }
+
+        async fn play_round(&mut self, card: Card) -> RoundResult {
+            self.send(Message::PlayCard(card)).await;
+            let Message::PlayResult(result) = self.recv().await else {
+                panic!("Expected PlayResult.");
+            };
+            result
+        }
+
+        /// Plays a whole game in the order the cards were dealt, returning
+        /// the GameOver.
+        async fn play_game(&mut self) -> Message {
+            for card in self.start().await.iter() {
+                self.play_round(card).await;
+            }
+            self.recv().await
+        }
+
+        async fn assert_closed(mut self) {
+            assert_eq!(
+                self.stream.read(&mut [0]).await.unwrap(),
+                0,
+                "The server should've hung up."
+            );
+        }
+    }
+
+    /// Plays a whole game speaking `wire` and protocol `version`, playing
+    /// cards in the order they were dealt. Returns every result the server
+    /// sent, and the GameOver if one was sent.
+    async fn play(
+        addr: SocketAddr,
+        wire: WireVersion,
+        version: u8,
+    ) -> (Vec<RoundResult>, Option<Message>) {
+        let mut client = Client::connect(addr, wire, version).await;
+        let mut results = Vec::new();
+        for card in client.start().await.iter() {
+            results.push(client.play_round(card).await);
+        }
+        let game_over = match version {
+            0 => None,
+            _ => Some(client.recv().await),
+        };
+        client.assert_closed().await;
+        (results, game_over)
+    }
+
+    fn count(results: &[RoundResult], wanted: RoundResult) -> u8 {
+        results.iter().filter(|&&result| result == wanted).count() as u8
+    }
+
+    #[test]
+    fn game_ids_are_never_reused() {
+        let ids: Vec<GameId> = (0..3).map(|_| GameId::next()).collect();
+        assert!(ids.windows(2).all(|pair| pair[0].0 < pair[1].0));
+        assert_eq!(GameId(42).to_string(), "42");
+    }
+
+    #[tokio::test]
+    async fn v1_and_v2_clients_share_a_game() {
+        let (addr, server) = start_game().await;
+        let ((v1, v1_game_over), (v2, v2_game_over)) = tokio::join!(
+            play(addr, WireVersion::V1, 0),
+            play(addr, WireVersion::V2, PROTOCOL_VERSION)
+        );
+        assert_eq!(v1.len(), HAND_SIZE);
+        assert_eq!(v2.len(), HAND_SIZE);
+        assert_eq!(v1_game_over, None);
+        let Some(Message::GameOver {
+            result,
+            my_score,
+            their_score,
+        }) = v2_game_over
+        else {
+            panic!("The versioned client should've been told the game was over.");
+        };
+        assert_eq!(my_score, count(&v2, RoundResult::Win));
+        assert_eq!(their_score, count(&v2, RoundResult::Lose));
+        assert_eq!(their_score, count(&v1, RoundResult::Win));
+        assert_eq!(result, RoundResult::from(my_score.cmp(&their_score)));
+        let outcome = server.await.unwrap().unwrap();
+        assert_eq!(usize::from(outcome.rounds_played), HAND_SIZE);
+        // Either of them could have been seated first.
+        let mut scores = [outcome.player_one_score, outcome.player_two_score];
+        scores.sort();
+        assert_eq!(
+            scores,
+            [my_score.min(their_score), my_score.max(their_score)]
+        );
+    }
+
+    #[tokio::test]
+    async fn hands_are_only_logged_when_asked_for() {
+        for log_hands in [false, true] {
+            let logs = Logs::default();
+            let _capturing = logs.capture(LogFormat::Text);
+            let rules = Rules {
+                log_hands,
+                ..Rules::default()
+            };
+            let (addr, server) = start_game_with(WireFormat::Binary, rules).await;
+            tokio::join!(
+                play(addr, WireVersion::V1, 0),
+                play(addr, WireVersion::V1, 0)
+            );
+            server.await.unwrap().unwrap();
+            let logs = logs.contents();
+            // Every round and the game's end are logged, all in its span.
+            assert_eq!(logs.matches("Round over").count(), HAND_SIZE, "{logs}");
+            assert!(logs.contains("Played to the end"), "{logs}");
+            assert!(
+                logs.lines().all(|line| line.contains("game{game_id=")),
+                "{logs}"
+            );
+            assert_eq!(logs.contains(['♣', '♦', '♥', '♠']), log_hands, "{logs}");
+            // Hands are only ever debugging output, so a filter can keep
+            // them out even when they're asked for.
+            let dealt: Vec<_> = logs.lines().filter(|line| line.contains("Dealt")).collect();
+            assert_eq!(dealt.len(), if log_hands { 2 } else { 0 }, "{logs}");
+            assert!(dealt.iter().all(|line| line.contains("DEBUG")), "{logs}");
+        }
+    }
+
+    #[tokio::test]
+    async fn json_logs_are_one_object_per_line() {
+        let logs = Logs::default();
+        let _capturing = logs.capture(LogFormat::Json);
+        let (addr, server) = start_game().await;
+        tokio::join!(
+            play(addr, WireVersion::V1, 0),
+            play(addr, WireVersion::V2, PROTOCOL_VERSION)
+        );
+        server.await.unwrap().unwrap();
+        let events: Vec<serde_json::Value> = logs
+            .contents()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        // Every event is about the game, and says which one, and who's
+        // playing it, in keys of their own.
+        let game = &events[0]["span"];
+        assert_eq!(game["name"], "game");
+        assert!(game["game_id"].is_u64());
+        for player in ["p1", "p2"] {
+            game[player]
+                .as_str()
+                .unwrap()
+                .parse::<SocketAddr>()
+                .unwrap();
+        }
+        assert!(events.iter().all(|event| event["span"] == *game));
+        let messages = |message| {
+            events
+                .iter()
+                .filter(move |event| event["message"] == message)
+        };
+        assert_eq!(messages("Round over").count(), HAND_SIZE);
+        let round = messages("Round over").next_back().unwrap();
+        assert_eq!(round["round"], HAND_SIZE);
+        assert!(round["score"].is_string());
+    }
+
+    /// Keeps everything written to it, taking at most `most` bytes a write,
+    /// and counts the writes.
+    struct Writes {
+        written: Vec<u8>,
+        writes: usize,
+        most: usize,
+    }
+
+    impl Writes {
+        fn taking(most: usize) -> Writes {
+            Writes {
+                written: Vec::new(),
+                writes: 0,
+                most,
+            }
+        }
+    }
+
+    impl AsyncWrite for Writes {
+        fn poll_write(
+            self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            buf: &[u8],
+        ) -> Poll<std::io::Result<usize>> {
+            self.poll_write_vectored(cx, &[IoSlice::new(buf)])
+        }
+
+        fn poll_write_vectored(
+            mut self: Pin<&mut Self>,
+            _: &mut Context<'_>,
+            bufs: &[IoSlice<'_>],
+        ) -> Poll<std::io::Result<usize>> {
+            self.writes += 1;
+            let before = self.written.len();
+            for buf in bufs {
+                let room = self.most - (self.written.len() - before);
+                self.written.extend(buf.iter().take(room));
+            }
+            Poll::Ready(Ok(self.written.len() - before))
+        }
+
+        fn is_write_vectored(&self) -> bool {
+            true
+        }
+
+        fn poll_flush(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn poll_shutdown(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    #[tokio::test]
+    async fn a_game_start_goes_out_in_one_write() {
+        let hand = Deck::shuffled(&mut rand::rng()).deal().0;
+        for wire in [WireVersion::V1, WireVersion::V2, WireVersion::V2Checked] {
+            let mut out = [0; MAX_FRAME_LEN];
+            let mut parts = wire
+                .encode_split(MessageRef::GameStart(&hand), &mut out)
+                .map(IoSlice::new);
+            let mut writes = Writes::taking(usize::MAX);
+            send_all(&mut writes, &mut parts).await.unwrap();
+            assert_eq!(writes.writes, 1);
+            let (frame, len) = wire.encode(&Message::GameStart(hand));
+            assert_eq!(writes.written, frame[..len]);
+        }
+    }
+
+    #[tokio::test]
+    async fn short_writes_pick_up_where_they_left_off() {
+        let mut parts = [&b"War"[..], b"", b"time", b"!"].map(IoSlice::new);
+        let mut writes = Writes::taking(3);
+        send_all(&mut writes, &mut parts).await.unwrap();
+        assert_eq!(writes.written, b"Wartime!");
+        assert_eq!(writes.writes, 3);
+        // Nothing to write is no write at all.
+        let mut writes = Writes::taking(3);
+        send_all(&mut writes, &mut [IoSlice::new(b"")])
             .await
             .unwrap();
-        let message = Message::try_from(&*play_card_message_buffer)
-            .expect(format!("Bad message received from {}", game.player_one.1).as_str());
-        dbg!(message);
-        game.player_two
-            .0
-            .read_exact(play_card_message_buffer)
+        assert_eq!(writes.writes, 0);
+    }
+
+    #[tokio::test]
+    async fn the_last_round_goes_out_in_one_write() {
+        let round = [
+            Message::PlayResult(RoundResult::Win),
+            Message::GameOver {
+                result: RoundResult::Win,
+                my_score: 14,
+                their_score: 12,
+            },
+        ];
+        let round: Vec<_> = round.iter().collect();
+        for wire in [WireVersion::V1, WireVersion::V2, WireVersion::V2Checked] {
+            let mut writes = Writes::taking(usize::MAX);
+            send_batch(&mut writes, WireFormat::Binary, wire, &round)
+                .await
+                .unwrap();
+            assert_eq!(writes.writes, 1);
+            let mut written = &writes.written[..];
+            for &message in &round {
+                assert_eq!(wire.read(&mut written).await.unwrap(), *message);
+            }
+            assert!(written.is_empty());
+        }
+        let mut writes = Writes::taking(usize::MAX);
+        send_batch(&mut writes, WireFormat::Json, WireVersion::V1, &round)
             .await
             .unwrap();
-        let message = Message::try_from(&*play_card_message_buffer)
-            .expect(format!("Bad message received from {}", game.player_two.1).as_str());
-        dbg!(message);
-
-        // TODO: Everybody wins!
-        game.player_one
-            .0
-            .write_all(Message::PlayResult(RoundResult::Win).as_ref())
+        assert_eq!(writes.writes, 1);
+        assert_eq!(
+            String::from_utf8(writes.written).unwrap(),
+            "{\"type\":\"play_result\",\"result\":0}\n\
+             {\"type\":\"game_over\",\"result\":0,\"my_score\":14,\"their_score\":12}\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn batches_too_big_for_one_write_still_all_go_out() {
+        let biggest = Card::ALL.repeat(MAX_DECKS.into());
+        let game_start = Message::GameStart(Hand::try_from(&biggest[..MAX_HAND_SIZE]).unwrap());
+        let batch = [&game_start; 3];
+        let mut writes = Writes::taking(usize::MAX);
+        send_batch(&mut writes, WireFormat::Binary, WireVersion::V2, &batch)
             .await
-            .expect("Unable to send message");
-        game.player_two
-            .0
-            .write_all(Message::PlayResult(RoundResult::Win).as_ref())
+            .unwrap();
+        assert_eq!(writes.writes, 2);
+        let mut written = &writes.written[..];
+        for _ in batch {
+            assert_eq!(
+                WireVersion::V2.read(&mut written).await.unwrap(),
+                game_start
+            );
+        }
+    }
+
+    async fn read_until_closed(mut stream: TcpStream) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        stream.read_to_end(&mut bytes).await.unwrap();
+        bytes
+    }
+
+    #[tokio::test]
+    async fn versioned_v1_client_gets_game_over() {
+        let (addr, server) = start_game().await;
+        let ((_, legacy), (_, versioned)) = tokio::join!(
+            play(addr, WireVersion::V1, 0),
+            play(addr, WireVersion::V1, 1)
+        );
+        assert_eq!(legacy, None);
+        assert!(matches!(versioned, Some(Message::GameOver { .. })));
+        server.await.unwrap().unwrap();
+    }
+
+    /// A client from before any of the protocol extensions should see exactly
+    /// what it always has: a GameStart, a PlayResult for each card, and then a
+    /// closed connection.
+    #[tokio::test]
+    async fn legacy_game_is_byte_for_byte_unchanged() {
+        async fn legacy_client(addr: SocketAddr) {
+            let mut stream = TcpStream::connect(addr).await.unwrap();
+            stream.write_all(&[0, 0]).await.unwrap();
+            let mut game_start = [0; 27];
+            stream.read_exact(&mut game_start).await.unwrap();
+            assert_eq!(game_start[0], 1);
+            for &card in &game_start[1..] {
+                assert!(card < NUM_CARDS_TOTAL);
+                stream.write_all(&[2, card]).await.unwrap();
+                let mut play_result = [0; 2];
+                stream.read_exact(&mut play_result).await.unwrap();
+                assert_eq!(play_result[0], 3);
+                assert!(play_result[1] <= 2);
+            }
+            assert_eq!(read_until_closed(stream).await, b"");
+        }
+        let (addr, server) = start_game().await;
+        tokio::join!(legacy_client(addr), legacy_client(addr));
+        server.await.unwrap().unwrap();
+    }
+
+    /// Plays a whole game as a JSON client, one line at a time.
+    async fn play_json(addr: SocketAddr) -> (Vec<RoundResult>, Message) {
+        let stream = TcpStream::connect(addr).await.unwrap();
+        let (reader, mut writer) = stream.into_split();
+        let mut lines = tokio::io::BufReader::new(reader).lines();
+        let mut recv = async || {
+            let line = lines.next_line().await.unwrap().unwrap();
+            Message::from_json(line.as_bytes()).unwrap()
+        };
+        writer
+            .write_all(b"{\"type\":\"want_game\",\"version\":2}\n")
+            .await
+            .unwrap();
+        writer
+            .write_all(b"{\"type\":\"hello\",\"name\":\"jq\"}\n")
+            .await
+            .unwrap();
+        let Message::GameStart(hand) = recv().await else {
+            panic!("Expected GameStart.");
+        };
+        let mut results = Vec::new();
+        for card in hand.iter() {
+            let line = format!("{}\n", Message::PlayCard(card).to_json());
+            writer.write_all(line.as_bytes()).await.unwrap();
+            let Message::PlayResult(result) = recv().await else {
+                panic!("Expected PlayResult.");
+            };
+            results.push(result);
+        }
+        let game_over = recv().await;
+        // Hanging up is as good as saying no to a rematch.
+        drop(writer);
+        assert!(lines.next_line().await.unwrap().is_none());
+        (results, game_over)
+    }
+
+    #[tokio::test]
+    async fn json_game() {
+        let (addr, server) = start_game_speaking(WireFormat::Json).await;
+        let ((one, one_game_over), (two, two_game_over)) =
+            tokio::join!(play_json(addr), play_json(addr));
+        assert_eq!(one.len(), HAND_SIZE);
+        assert_eq!(two.len(), HAND_SIZE);
+        let (
+            Message::GameOver {
+                my_score: one_score,
+                their_score: two_score,
+                ..
+            },
+            Message::GameOver {
+                my_score,
+                their_score,
+                ..
+            },
+        ) = (one_game_over, two_game_over)
+        else {
+            panic!("Both clients should've been told the game was over.");
+        };
+        assert_eq!((my_score, their_score), (two_score, one_score));
+        assert_eq!(count(&one, RoundResult::Win), one_score);
+        server.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn misbehaving_player_ends_the_game_for_both() {
+        let (addr, server) = start_game().await;
+        let mut culprit = Client::connect(addr, WireVersion::V1, PROTOCOL_VERSION).await;
+        let mut opponent = Client::connect(addr, WireVersion::V1, PROTOCOL_VERSION).await;
+        let (_, hand) = tokio::join!(culprit.start(), opponent.start());
+        culprit.send(Message::Rematch).await;
+        opponent.send(Message::PlayCard(hand.cards()[0])).await;
+
+        let (culprit, opponent) = tokio::join!(
+            read_until_closed(culprit.stream),
+            read_until_closed(opponent.stream)
+        );
+        assert_eq!(culprit, [5, ErrorCode::OutOfTurn as u8]);
+        assert_eq!(opponent, [5, ErrorCode::OpponentGone as u8]);
+        assert!(matches!(
+            server.await.unwrap(),
+            Err(GameError::ProtocolViolation {
+                code: ErrorCode::OutOfTurn,
+                ..
+            })
+        ));
+    }
+
+    #[tokio::test]
+    async fn playing_the_opponents_card_is_cheating() {
+        let (addr, server) = start_game().await;
+        let mut cheat = Client::connect(addr, WireVersion::V1, PROTOCOL_VERSION).await;
+        let mut opponent = Client::connect(addr, WireVersion::V1, PROTOCOL_VERSION).await;
+        let (_, hand) = tokio::join!(cheat.start(), opponent.start());
+        cheat.send(Message::PlayCard(hand.cards()[0])).await;
+
+        let (cheat, opponent) = tokio::join!(
+            read_until_closed(cheat.stream),
+            read_until_closed(opponent.stream)
+        );
+        assert_eq!(cheat, [5, ErrorCode::InvalidCard as u8]);
+        assert_eq!(opponent, [5, ErrorCode::OpponentGone as u8]);
+        assert!(matches!(
+            server.await.unwrap(),
+            Err(GameError::Cheated {
+                cheat: Cheat::NotDealt(_),
+                ..
+            })
+        ));
+    }
+
+    #[tokio::test]
+    async fn replaying_a_card_is_cheating() {
+        let (addr, server) = start_game().await;
+        let mut cheat = Client::connect(addr, WireVersion::V1, PROTOCOL_VERSION).await;
+        let mut opponent = Client::connect(addr, WireVersion::V1, PROTOCOL_VERSION).await;
+        let (cheat_hand, opponent_hand) = tokio::join!(cheat.start(), opponent.start());
+        for round in 0..3 {
+            tokio::join!(
+                cheat.play_round(cheat_hand.cards()[round]),
+                opponent.play_round(opponent_hand.cards()[round])
+            );
+        }
+        // Honest, right up until they try to play their second card again.
+        cheat.send(Message::PlayCard(cheat_hand.cards()[1])).await;
+
+        let (cheat, opponent) = tokio::join!(
+            read_until_closed(cheat.stream),
+            read_until_closed(opponent.stream)
+        );
+        assert_eq!(cheat, [5, ErrorCode::InvalidCard as u8]);
+        assert_eq!(opponent, [5, ErrorCode::OpponentGone as u8]);
+        assert!(matches!(
+            server.await.unwrap(),
+            Err(GameError::Cheated {
+                cheat: Cheat::Replayed(_),
+                ..
+            })
+        ));
+    }
+
+    #[tokio::test]
+    async fn hanging_up_mid_game_closes_the_opponent_promptly() {
+        let (addr, server) = start_game().await;
+        let mut quitter = Client::connect(addr, WireVersion::V2, PROTOCOL_VERSION).await;
+        let mut opponent = Client::connect(addr, WireVersion::V2, PROTOCOL_VERSION).await;
+        tokio::join!(quitter.start(), opponent.start());
+        drop(quitter);
+
+        let opponent =
+            tokio::time::timeout(Duration::from_secs(1), read_until_closed(opponent.stream))
+                .await
+                .expect("The opponent should've been hung up on.");
+        let (gone, len) = WireVersion::V2.encode(&Message::Error(ErrorCode::OpponentGone));
+        assert_eq!(opponent, gone[..len]);
+        assert!(matches!(
+            server.await.unwrap(),
+            Err(GameError::PlayerDisconnected {
+                error: Error::Io(_),
+                ..
+            })
+        ));
+    }
+
+    #[tokio::test]
+    async fn a_game_that_panics_still_hangs_up_on_both_players() {
+        let rules = Rules {
+            panic_after_round: Some(1),
+            ..Rules::default()
+        };
+        let (addr, server) = start_game_with(WireFormat::Binary, rules).await;
+        let mut one = Client::connect(addr, WireVersion::V2, PROTOCOL_VERSION).await;
+        let mut two = Client::connect(addr, WireVersion::V2, PROTOCOL_VERSION).await;
+        let (one_hand, two_hand) = tokio::join!(one.start(), two.start());
+        one.send(Message::PlayCard(one_hand.cards()[0])).await;
+        two.send(Message::PlayCard(two_hand.cards()[0])).await;
+
+        let (one, two) = tokio::time::timeout(Duration::from_secs(1), async {
+            tokio::join!(read_until_closed(one.stream), read_until_closed(two.stream))
+        })
+        .await
+        .expect("Both players should've been hung up on.");
+        let (crashed, len) = WireVersion::V2.encode(&Message::Error(CRASHED));
+        assert_eq!(one, crashed[..len]);
+        assert_eq!(two, crashed[..len]);
+        assert!(matches!(
+            server.await.unwrap(),
+            Err(GameError::Panicked { message }) if message.contains("after round 1")
+        ));
+    }
+
+    #[tokio::test]
+    async fn a_bad_deal_ends_a_strict_game_before_it_starts() {
+        let rules = Rules {
+            strict: true,
+            misdeal: true,
+            ..Rules::default()
+        };
+        let (addr, server) = start_game_with(WireFormat::Binary, rules).await;
+        let mut one = Client::connect(addr, WireVersion::V2, PROTOCOL_VERSION).await;
+        let mut two = Client::connect(addr, WireVersion::V2, PROTOCOL_VERSION).await;
+        tokio::join!(one.greeted(), two.greeted());
+
+        // Neither of them is sent a GameStart, just told why they're being
+        // hung up on.
+        let (one, two) = tokio::time::timeout(Duration::from_secs(1), async {
+            tokio::join!(read_until_closed(one.stream), read_until_closed(two.stream))
+        })
+        .await
+        .expect("Both players should've been hung up on.");
+        let (crashed, len) = WireVersion::V2.encode(&Message::Error(CRASHED));
+        assert_eq!(one, crashed[..len]);
+        assert_eq!(two, crashed[..len]);
+        assert!(matches!(
+            server.await.unwrap(),
+            Err(GameError::Misdealt(DealError::Duplicate(_)))
+        ));
+    }
+
+    #[tokio::test]
+    async fn corrupted_frame_ends_the_game_cleanly() {
+        let (addr, server) = start_game().await;
+        let mut culprit = Client::connect(addr, WireVersion::V2Checked, PROTOCOL_VERSION).await;
+        let mut opponent = Client::connect(addr, WireVersion::V1, PROTOCOL_VERSION).await;
+        let (hand, _) = tokio::join!(culprit.start(), opponent.start());
+        let (mut frame, len) = WireVersion::V2Checked.encode(&Message::PlayCard(hand.cards()[0]));
+        frame[len - 2] ^= 0x01;
+        culprit.stream.write_all(&frame[..len]).await.unwrap();
+
+        let (culprit, opponent) = tokio::join!(
+            read_until_closed(culprit.stream),
+            read_until_closed(opponent.stream)
+        );
+        let (error, error_len) =
+            WireVersion::V2Checked.encode(&Message::Error(ErrorCode::Malformed));
+        assert_eq!(culprit, error[..error_len]);
+        assert_eq!(opponent, [5, ErrorCode::OpponentGone as u8]);
+        assert!(matches!(
+            server.await.unwrap(),
+            Err(GameError::ProtocolViolation {
+                code: ErrorCode::Malformed,
+                ..
+            })
+        ));
+    }
+
+    #[tokio::test]
+    async fn forfeit_before_the_first_card() {
+        let (addr, server) = start_game().await;
+        let mut quitter = Client::connect(addr, WireVersion::V2, PROTOCOL_VERSION).await;
+        let mut opponent = Client::connect(addr, WireVersion::V1, 0).await;
+        let (_, hand) = tokio::join!(quitter.start(), opponent.start());
+
+        quitter.send(Message::Forfeit).await;
+        assert_eq!(
+            quitter.recv().await,
+            Message::GameOver {
+                result: RoundResult::Lose,
+                my_score: 0,
+                their_score: 0
+            }
+        );
+        // A legacy opponent has no GameOver, so they win the round they're in.
+        assert_eq!(opponent.play_round(hand.cards()[0]).await, RoundResult::Win);
+        tokio::join!(quitter.assert_closed(), opponent.assert_closed());
+        server.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn stalling_forfeits_the_game() {
+        let move_timeout = Duration::from_millis(300);
+        let rules = Rules {
+            move_timeout,
+            ..Rules::default()
+        };
+        let (addr, server) = start_game_with(WireFormat::Binary, rules).await;
+        let mut staller = Client::connect(addr, WireVersion::V2, PROTOCOL_VERSION).await;
+        let mut opponent = Client::connect(addr, WireVersion::V2, PROTOCOL_VERSION).await;
+        let (staller_hand, opponent_hand) = tokio::join!(staller.start(), opponent.start());
+        let mut stalled = Instant::now();
+        for round in 0..3 {
+            // The server starts timing the staller's next move as soon as it
+            // has both cards for this round, before either player hears how
+            // it went.
+            stalled = Instant::now();
+            tokio::join!(
+                staller.play_round(staller_hand.cards()[round]),
+                opponent.play_round(opponent_hand.cards()[round])
+            );
+        }
+        opponent
+            .send(Message::PlayCard(opponent_hand.cards()[3]))
+            .await;
+
+        let game_over = tokio::time::timeout(move_timeout * 2, opponent.recv())
             .await
-            .expect("Unable to send message");
+            .expect("The opponent should've won once the staller ran out of time.");
+        assert!(matches!(
+            game_over,
+            Message::GameOver {
+                result: RoundResult::Win,
+                ..
+            }
+        ));
+        assert!(stalled.elapsed() >= move_timeout);
+        assert!(matches!(
+            staller.recv().await,
+            Message::GameOver {
+                result: RoundResult::Lose,
+                ..
+            }
+        ));
+        tokio::join!(staller.assert_closed(), opponent.assert_closed());
+        let outcome = server.await.unwrap().unwrap();
+        assert_eq!(outcome.reason, EndReason::MoveTimeout);
+        assert_eq!(outcome.winner, Some(Seat::Two));
+        assert_eq!(outcome.rounds_played, 3);
+    }
+
+    #[tokio::test]
+    async fn forfeit_mid_game() {
+        let (addr, server) = start_game().await;
+        let mut opponent = Client::connect(addr, WireVersion::V2, PROTOCOL_VERSION).await;
+        let mut quitter = Client::connect(addr, WireVersion::V2, PROTOCOL_VERSION).await;
+        let (opponent_hand, quitter_hand) = tokio::join!(opponent.start(), quitter.start());
+
+        let mut results = Vec::new();
+        for round in 0..5 {
+            let (result, _) = tokio::join!(
+                opponent.play_round(opponent_hand.cards()[round]),
+                quitter.play_round(quitter_hand.cards()[round])
+            );
+            results.push(result);
+        }
+        opponent
+            .send(Message::PlayCard(opponent_hand.cards()[5]))
+            .await;
+        quitter.send(Message::Forfeit).await;
+
+        let wins = count(&results, RoundResult::Win);
+        let losses = count(&results, RoundResult::Lose);
+        assert_eq!(
+            opponent.recv().await,
+            Message::GameOver {
+                result: RoundResult::Win,
+                my_score: wins,
+                their_score: losses
+            }
+        );
+        assert_eq!(
+            quitter.recv().await,
+            Message::GameOver {
+                result: RoundResult::Lose,
+                my_score: losses,
+                their_score: wins
+            }
+        );
+        tokio::join!(quitter.assert_closed(), opponent.assert_closed());
+        let outcome = server.await.unwrap().unwrap();
+        assert_eq!(outcome.winner, Some(Seat::One));
+        assert_eq!(outcome.rounds_played, 5);
+        assert_eq!(
+            (outcome.player_one_score, outcome.player_two_score),
+            (wins, losses)
+        );
+    }
+
+    #[tokio::test]
+    async fn rematch_on_the_same_connections() {
+        let (addr, server) = start_game().await;
+        let mut player_one = Client::connect(addr, WireVersion::V2, PROTOCOL_VERSION).await;
+        let mut player_two = Client::connect(addr, WireVersion::V2, PROTOCOL_VERSION).await;
+        for _ in 0..2 {
+            let (game_over, _) = tokio::join!(player_one.play_game(), player_two.play_game());
+            assert!(matches!(game_over, Message::GameOver { .. }));
+            player_one.send(Message::Rematch).await;
+            player_two.send(Message::Rematch).await;
+        }
+        tokio::join!(player_one.play_game(), player_two.play_game());
+        drop(player_two);
+        player_one.assert_closed().await;
+        server.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn rematch_needs_both_players() {
+        let (addr, server) = start_game().await;
+        let mut player_one = Client::connect(addr, WireVersion::V2, PROTOCOL_VERSION).await;
+        let mut player_two = Client::connect(addr, WireVersion::V2, PROTOCOL_VERSION).await;
+        tokio::join!(player_one.play_game(), player_two.play_game());
+        player_one.send(Message::Rematch).await;
+        player_two.send(Message::Forfeit).await;
+        tokio::join!(player_one.assert_closed(), player_two.assert_closed());
+        server.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn five_card_game() {
+        let rules = Rules {
+            hand_size: Some(5),
+            ..Rules::default()
+        };
+        let (addr, server) = start_game_with(WireFormat::Binary, rules).await;
+        let mut player_one = Client::connect(addr, WireVersion::V1, LONG_GAME_START_VERSION).await;
+        let mut player_two = Client::connect(addr, WireVersion::V2, PROTOCOL_VERSION).await;
+        let (one_hand, two_hand) = tokio::join!(player_one.start(), player_two.start());
+        assert_eq!((one_hand.len(), two_hand.len()), (5, 5));
+
+        let mut results = Vec::new();
+        for (one_card, two_card) in one_hand.iter().zip(two_hand.iter()) {
+            let (result, _) = tokio::join!(
+                player_one.play_round(one_card),
+                player_two.play_round(two_card)
+            );
+            results.push(result);
+        }
+        let Message::GameOver {
+            my_score,
+            their_score,
+            ..
+        } = player_one.recv().await
+        else {
+            panic!("Expected GameOver.");
+        };
+        assert_eq!(my_score, count(&results, RoundResult::Win));
+        assert_eq!(their_score, count(&results, RoundResult::Lose));
+        assert!(matches!(player_two.recv().await, Message::GameOver { .. }));
+        player_one.send(Message::Forfeit).await;
+        tokio::join!(player_one.assert_closed(), player_two.assert_closed());
+        server.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn two_deck_game() {
+        let rules = Rules {
+            decks: 2,
+            ..Rules::default()
+        };
+        let (addr, server) = start_game_with(WireFormat::Binary, rules).await;
+        let mut player_one = Client::connect(addr, WireVersion::V1, PROTOCOL_VERSION).await;
+        let mut player_two = Client::connect(addr, WireVersion::V2, PROTOCOL_VERSION).await;
+        let (mut one_hand, mut two_hand) = tokio::join!(player_one.start(), player_two.start());
+        assert_eq!(
+            (one_hand.len(), two_hand.len()),
+            (2 * HAND_SIZE, 2 * HAND_SIZE)
+        );
+
+        // Each card is in the shoe twice, so it'd take a miracle for none of
+        // them to have been split between the players. Both of them playing
+        // their copy is perfectly fair.
+        let shared = one_hand
+            .iter()
+            .find(|&card| two_hand.contains(card))
+            .expect("The players should share a card.");
+        let (one_result, two_result) = tokio::join!(
+            player_one.play_round(one_hand.take(shared).unwrap()),
+            player_two.play_round(two_hand.take(shared).unwrap())
+        );
+        assert_eq!(
+            (one_result, two_result),
+            (RoundResult::Draw, RoundResult::Draw)
+        );
+
+        for (one_card, two_card) in one_hand.iter().zip(two_hand.iter()) {
+            tokio::join!(
+                player_one.play_round(one_card),
+                player_two.play_round(two_card)
+            );
+        }
+        let (one_game_over, two_game_over) = tokio::join!(player_one.recv(), player_two.recv());
+        let Message::GameOver {
+            result,
+            my_score,
+            their_score,
+        } = one_game_over
+        else {
+            panic!("Expected GameOver.");
+        };
+        // There was at least one draw, so someone's missing a point.
+        assert!(usize::from(my_score + their_score) < 2 * HAND_SIZE);
+        assert_eq!(
+            two_game_over,
+            Message::GameOver {
+                result: result.invert(),
+                my_score: their_score,
+                their_score: my_score,
+            }
+        );
+        // Anything but a Rematch turns one down.
+        player_one.send(Message::Forfeit).await;
+        tokio::join!(player_one.assert_closed(), player_two.assert_closed());
+        server.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn a_whole_game_over_in_memory_pipes() {
+        let ([mut one, mut two], server) = start_game_over_pipes(
+            [
+                ("one", WireVersion::V1, PROTOCOL_VERSION),
+                ("two", WireVersion::V2Checked, PROTOCOL_VERSION),
+            ],
+            Rules::default(),
+        )
+        .await;
+        let (one_game_over, two_game_over) = tokio::join!(one.play_game(), two.play_game());
+        // Hanging up is saying no to a rematch.
+        drop(two);
+        one.assert_closed().await;
+
+        let outcome = server.await.unwrap().unwrap();
+        assert_eq!(outcome.reason, EndReason::Finished);
+        assert_eq!(outcome.rounds_played as usize, HAND_SIZE);
+        let Message::GameOver {
+            my_score,
+            their_score,
+            ..
+        } = one_game_over
+        else {
+            panic!("Player one should've been told the game was over.");
+        };
+        assert_eq!(
+            (my_score, their_score),
+            (outcome.player_one_score, outcome.player_two_score)
+        );
+        assert!(matches!(
+            two_game_over,
+            Message::GameOver { my_score, .. } if my_score == outcome.player_two_score
+        ));
+    }
+
+    #[tokio::test]
+    async fn hanging_up_over_a_pipe_is_blamed_on_whoever_hung_up() {
+        let ([mut quitter, mut stayer], server) = start_game_over_pipes(
+            [
+                ("quitter", WireVersion::V2, PROTOCOL_VERSION),
+                ("stayer", WireVersion::V2, PROTOCOL_VERSION),
+            ],
+            Rules::default(),
+        )
+        .await;
+        tokio::join!(quitter.start(), stayer.start());
+        drop(quitter);
+
+        assert_eq!(stayer.recv().await, Message::Error(ErrorCode::OpponentGone));
+        stayer.assert_closed().await;
+        let Err(GameError::PlayerDisconnected { player, .. }) = server.await.unwrap() else {
+            panic!("The game should've ended when a player hung up.");
+        };
+        assert_eq!(player, PlayerId::Label("quitter"));
+        assert_eq!(
+            GameError::Timeout { player }.to_string(),
+            "quitter took too long to play"
+        );
+    }
+
+    #[tokio::test]
+    async fn cheating_over_a_pipe_is_blamed_on_the_cheat() {
+        let ([mut cheat, mut opponent], server) = start_game_over_pipes(
+            [
+                ("cheat", WireVersion::V1, PROTOCOL_VERSION),
+                ("opponent", WireVersion::V1, PROTOCOL_VERSION),
+            ],
+            Rules::default(),
+        )
+        .await;
+        let (_, hand) = tokio::join!(cheat.start(), opponent.start());
+        cheat.send(Message::PlayCard(hand.cards()[0])).await;
+
+        assert_eq!(cheat.recv().await, Message::Error(ErrorCode::InvalidCard));
+        assert_eq!(
+            opponent.recv().await,
+            Message::Error(ErrorCode::OpponentGone)
+        );
+        tokio::join!(opponent.assert_closed(), cheat.assert_closed());
+        assert!(matches!(
+            server.await.unwrap(),
+            Err(GameError::Cheated {
+                player: PlayerId::Label("cheat"),
+                cheat: Cheat::NotDealt(_),
+            })
+        ));
     }
 }
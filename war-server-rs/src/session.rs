@@ -0,0 +1,947 @@
+//! The rules of a single game, kept apart from the sockets it's played over so
+//! it can be driven (and tested) one message at a time.
+
+use std::time::Duration;
+
+use war_protocol::*;
+
+/// The choices about how games are played, made once for the whole server.
+#[derive(Debug, Clone, Copy)]
+pub struct Rules {
+    pub rank_order: RankOrder,
+    pub tie_break: TieBreak,
+    /// Check every deal, even in release builds, and end the game if it's
+    /// wrong, instead of only asserting it's right in debug builds.
+    pub strict: bool,
+    /// Log the hands each player is dealt, at debug level. Anyone who can
+    /// read the logs can see them, so this is off unless asked for.
+    pub log_hands: bool,
+    /// How many decks are shuffled together for each game, from 1 to
+    /// [`MAX_DECKS`].
+    pub decks: u8,
+    /// How many cards each player is dealt, and so how many rounds a game
+    /// lasts, or `None` to deal out every card in the decks.
+    pub hand_size: Option<usize>,
+    /// Whether a drawn round starts a war: before playing their next card,
+    /// each player puts [`face_down_for_war`] cards face down, which are gone
+    /// from the game without ever being played.
+    pub face_down: bool,
+    /// How long a player has to send each move before they forfeit.
+    pub move_timeout: Duration,
+    /// Makes the server panic once this many rounds have been played, to test
+    /// that a crashing game doesn't take anything else down with it.
+    #[cfg(test)]
+    pub panic_after_round: Option<u8>,
+    /// Deals both players the same hand, to test that strict rules catch it.
+    #[cfg(test)]
+    pub misdeal: bool,
+}
+
+impl Default for Rules {
+    fn default() -> Self {
+        Rules {
+            rank_order: RankOrder::default(),
+            tie_break: TieBreak::default(),
+            strict: false,
+            log_hands: false,
+            decks: 1,
+            hand_size: None,
+            face_down: false,
+            move_timeout: Duration::from_secs(30),
+            #[cfg(test)]
+            panic_after_round: None,
+            #[cfg(test)]
+            misdeal: false,
+        }
+    }
+}
+
+impl Rules {
+    /// How many cards each player is actually dealt.
+    pub fn hand_size(&self) -> usize {
+        self.hand_size
+            .unwrap_or(usize::from(self.decks) * HAND_SIZE)
+    }
+}
+
+/// Where a player sits at the table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Seat {
+    One,
+    Two,
+}
+
+impl Seat {
+    /// The player across the table.
+    pub fn other(self) -> Seat {
+        match self {
+            Seat::One => Seat::Two,
+            Seat::Two => Seat::One,
+        }
+    }
+
+    /// Where this player goes in anything kept for both players, like
+    /// `[Player; 2]`.
+    pub fn index(self) -> usize {
+        self as usize
+    }
+}
+
+/// How a game came to an end.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Ending {
+    /// Every card was played.
+    Finished,
+    /// The player in this seat gave up.
+    Forfeited(Seat),
+    /// The player in this seat took too long to make a move, and so forfeited.
+    TimedOut(Seat),
+    /// The player in `culprit` did something that means the game can't go on.
+    Faulted { culprit: Seat, code: ErrorCode },
+    /// The player in `culprit` played a card they didn't have.
+    Cheated { culprit: Seat, cheat: Cheat },
+    /// The player in this seat hung up partway through.
+    Abandoned(Seat),
+}
+
+/// A card a player tried to play that wasn't theirs to play. Either way, all
+/// they're told is [`ErrorCode::InvalidCard`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Cheat {
+    /// It was never dealt to them.
+    NotDealt(Card),
+    /// They already played it.
+    Replayed(Card),
+}
+
+impl std::fmt::Display for Cheat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Cheat::NotDealt(card) => write!(f, "played the {card}, which they weren't dealt"),
+            Cheat::Replayed(card) => write!(f, "played the {card} a second time"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    /// The hands haven't been sent out yet.
+    Dealing,
+    /// Partway through a round, with whichever cards have been played in it
+    /// so far, and how many cards each player still has to put face down
+    /// before playing theirs, if the round's a war.
+    AwaitingPlays {
+        played: [Option<Card>; 2],
+        face_down: [u8; 2],
+    },
+    Over(Ending),
+}
+
+/// What a session wants sent, and to whom, in order.
+pub type Outgoing = Vec<(Seat, Message)>;
+
+/// One game between two players. It's told each message a player sends and
+/// says what to send back, but never reads or writes anything itself.
+#[derive(Debug)]
+pub struct GameSession {
+    /// What each player was dealt, with the cards they've played since taken
+    /// out, so they can only ever play each card they were dealt once.
+    hands: [Hand; 2],
+    /// Which players only speak the original protocol, and so must never be
+    /// sent anything it doesn't have.
+    legacy: [bool; 2],
+    tally: Tally,
+    state: State,
+}
+
+impl GameSession {
+    pub fn new(
+        rules: Rules,
+        (player_one_hand, player_two_hand): (Hand, Hand),
+        legacy: [bool; 2],
+    ) -> GameSession {
+        GameSession {
+            hands: [player_one_hand, player_two_hand],
+            legacy,
+            tally: Tally::new(rules, player_one_hand.len()),
+            state: State::Dealing,
+        }
+    }
+
+    /// How the game is going for player one.
+    pub fn score(&self) -> Score {
+        self.tally.score
+    }
+
+    /// How the game ended, or `None` if it's still going.
+    pub fn ending(&self) -> Option<Ending> {
+        match self.state {
+            State::Over(ending) => Some(ending),
+            _ => None,
+        }
+    }
+
+    /// The player whose move the game is waiting on, if any. When it's
+    /// waiting on both, that's player one, so someone reading one player at a
+    /// time always reads them in the same order.
+    pub fn waiting_on(&self) -> Option<Seat> {
+        match self.state {
+            State::AwaitingPlays { played, .. } => [Seat::One, Seat::Two]
+                .into_iter()
+                .find(|seat| played[seat.index()].is_none()),
+            _ => None,
+        }
+    }
+
+    /// Deals the hands, returning the one each player should be sent in their
+    /// GameStart. They're borrowed rather than put in an [`Outgoing`], so they
+    /// can be sent without copying them into a [`Message`] first. Under strict
+    /// rules, a deal that isn't a proper split of the deck is an error, and
+    /// the game never starts.
+    pub fn start(&mut self) -> Result<&[Hand; 2], DealError> {
+        assert_eq!(self.state, State::Dealing, "The game already started.");
+        let validate =
+            || validate_deal_from(self.tally.rules.decks, &self.hands[0], &self.hands[1]);
+        if self.tally.rules.strict {
+            validate()?;
+        } else {
+            debug_assert_eq!(validate(), Ok(()), "The deck was dealt wrong.");
+        }
+        self.state = State::AwaitingPlays {
+            played: [None; 2],
+            face_down: [0; 2],
+        };
+        Ok(&self.hands)
+    }
+
+    /// Handles a message from the player in `seat`. Once the game's over,
+    /// whatever they send (like a Rematch) is up to the caller, so it's
+    /// ignored here.
+    ///
+    /// In a war, a player's PlayFaceDown has to come before their PlayCard,
+    /// with exactly as many cards as they owe; anywhere else, it's out of
+    /// turn. A PlayCard for anything not left in their hand is a [`Cheat`],
+    /// and ends the game.
+    pub fn on_message(&mut self, seat: Seat, message: Message) -> Outgoing {
+        let (mut played, mut face_down) = match self.state {
+            State::AwaitingPlays { played, face_down } => (played, face_down),
+            State::Dealing => return self.on_fault(seat, ErrorCode::OutOfTurn),
+            State::Over(_) => return Vec::new(),
+        };
+        let (card, owed) = (&mut played[seat.index()], &mut face_down[seat.index()]);
+        match message {
+            Message::PlayCard(played_card) if card.is_none() && *owed == 0 => {
+                // Cards compare by rank alone, but this takes out exactly the
+                // one they named, so that's what's checked.
+                let hand = &mut self.hands[seat.index()];
+                let Some(played_card) = hand.take(played_card) else {
+                    let dealt = hand
+                        .cards()
+                        .iter()
+                        .any(|dealt| dealt.value() == played_card.value());
+                    let cheat = if dealt {
+                        Cheat::Replayed(played_card)
+                    } else {
+                        Cheat::NotDealt(played_card)
+                    };
+                    return self.end_early(
+                        seat,
+                        ErrorCode::InvalidCard,
+                        Ending::Cheated {
+                            culprit: seat,
+                            cheat,
+                        },
+                    );
+                };
+                *card = Some(played_card);
+                if let [Some(player_one_card), Some(player_two_card)] = played {
+                    self.finish_round(player_one_card, player_two_card)
+                } else {
+                    self.state = State::AwaitingPlays { played, face_down };
+                    Vec::new()
+                }
+            }
+            Message::PlayFaceDown { count } if count == *owed => {
+                *owed = 0;
+                self.state = State::AwaitingPlays { played, face_down };
+                Vec::new()
+            }
+            Message::Forfeit => self.forfeit(seat, Ending::Forfeited(seat)),
+            _ => self.on_fault(seat, ErrorCode::OutOfTurn),
+        }
+    }
+
+    /// Ends the game because the player in `culprit` did something it can't
+    /// go on after, like sending garbage or hanging up, telling both players
+    /// why.
+    pub fn on_fault(&mut self, culprit: Seat, code: ErrorCode) -> Outgoing {
+        self.end_early(culprit, code, Ending::Faulted { culprit, code })
+    }
+
+    /// Ends the game because the player in `staller` took too long to make
+    /// their move, which counts as forfeiting it.
+    pub fn on_timeout(&mut self, staller: Seat) -> Outgoing {
+        match self.state {
+            State::Over(_) => Vec::new(),
+            _ => self.forfeit(staller, Ending::TimedOut(staller)),
+        }
+    }
+
+    /// Ends the game because the player in `quitter` hung up. There's no one
+    /// left to tell but their opponent.
+    pub fn on_hang_up(&mut self, quitter: Seat) -> Outgoing {
+        self.state = State::Over(Ending::Abandoned(quitter));
+        self.unless_legacy([(quitter.other(), Message::Error(ErrorCode::OpponentGone))])
+    }
+
+    /// Ends the game with `ending`, which is `culprit`'s fault, telling them
+    /// `code` and their opponent that they're gone.
+    fn end_early(&mut self, culprit: Seat, code: ErrorCode, ending: Ending) -> Outgoing {
+        self.state = State::Over(ending);
+        self.unless_legacy([
+            (culprit, Message::Error(code)),
+            (culprit.other(), Message::Error(ErrorCode::OpponentGone)),
+        ])
+    }
+
+    fn finish_round(&mut self, player_one_card: Card, player_two_card: Card) -> Outgoing {
+        let (player_one_result, player_two_result) =
+            self.tally.record(player_one_card, player_two_card);
+        let mut outgoing = vec![
+            (Seat::One, Message::PlayResult(player_one_result)),
+            (Seat::Two, Message::PlayResult(player_two_result)),
+        ];
+        if self.tally.score.winner().is_none() {
+            let war = self.tally.rules.face_down && player_one_result == RoundResult::Draw;
+            let owed = if war {
+                face_down_for_war(self.tally.score.rounds_left())
+            } else {
+                0
+            };
+            self.tally.score.set_aside(owed);
+            self.state = State::AwaitingPlays {
+                played: [None; 2],
+                face_down: [owed; 2],
+            };
+        } else {
+            self.state = State::Over(Ending::Finished);
+            let (player_one_game_over, player_two_game_over) = self.tally.game_over();
+            outgoing.extend(self.unless_legacy([
+                (Seat::One, player_one_game_over),
+                (Seat::Two, player_two_game_over),
+            ]));
+        }
+        outgoing
+    }
+
+    /// Ends the game with `ending`, which has `quitter` losing it, and tells
+    /// both players.
+    fn forfeit(&mut self, quitter: Seat, ending: Ending) -> Outgoing {
+        self.state = State::Over(ending);
+        let opponent = quitter.other();
+        let (quitter_score, opponent_score) = self.tally.scores(quitter);
+        let opponent_message = if self.legacy[opponent.index()] {
+            // There's no GameOver in the original protocol, so winning this
+            // round is the best we can tell them.
+            Message::PlayResult(RoundResult::Win)
+        } else {
+            Message::GameOver {
+                result: RoundResult::Win,
+                my_score: opponent_score,
+                their_score: quitter_score,
+            }
+        };
+        let mut outgoing = vec![(opponent, opponent_message)];
+        outgoing.extend(self.unless_legacy([(
+            quitter,
+            Message::GameOver {
+                result: RoundResult::Lose,
+                my_score: quitter_score,
+                their_score: opponent_score,
+            },
+        )]));
+        outgoing
+    }
+
+    /// Leaves out the messages meant for legacy players.
+    fn unless_legacy(&self, messages: impl IntoIterator<Item = (Seat, Message)>) -> Outgoing {
+        messages
+            .into_iter()
+            .filter(|(seat, _)| !self.legacy[seat.index()])
+            .collect()
+    }
+}
+
+/// How many rounds each player has won so far.
+#[derive(Debug, Default)]
+struct Tally {
+    /// Player one's score. Player two's is the same, inverted.
+    score: Score,
+    rules: Rules,
+}
+
+impl Tally {
+    fn new(rules: Rules, rounds: usize) -> Tally {
+        Tally {
+            score: Score::new(rounds),
+            rules,
+        }
+    }
+
+    /// Scores a round, returning the result each player should be sent.
+    fn record(
+        &mut self,
+        player_one_card: Card,
+        player_two_card: Card,
+    ) -> (RoundResult, RoundResult) {
+        let Rules {
+            rank_order,
+            tie_break,
+            ..
+        } = self.rules;
+        let player_one_result =
+            RoundResult::from(player_one_card.cmp_with(player_two_card, rank_order, tie_break));
+        self.score.record(player_one_result);
+        (player_one_result, player_one_result.invert())
+    }
+
+    /// The scores from `seat`'s perspective: theirs, then their opponent's.
+    fn scores(&self, seat: Seat) -> (u8, u8) {
+        let score = match seat {
+            Seat::One => self.score,
+            Seat::Two => self.score.invert(),
+        };
+        (score.wins(), score.losses())
+    }
+
+    /// The GameOver message each player should be sent, once every round has
+    /// been played.
+    fn game_over(&self) -> (Message, Message) {
+        let game_over = |score: Score| Message::GameOver {
+            result: score.winner().expect("Every round has been played."),
+            my_score: score.wins(),
+            their_score: score.losses(),
+        };
+        (game_over(self.score), game_over(self.score.invert()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use rand::{SeedableRng, rngs::StdRng};
+
+    use super::*;
+
+    /// A game between two up-to-date players, already dealt.
+    fn started(legacy: [bool; 2]) -> (GameSession, [Hand; 2]) {
+        let (player_one_hand, player_two_hand) =
+            Deck::shuffled(&mut StdRng::seed_from_u64(39)).deal();
+        let mut session =
+            GameSession::new(Rules::default(), (player_one_hand, player_two_hand), legacy);
+        assert_eq!(session.start(), Ok(&[player_one_hand, player_two_hand]));
+        (session, [player_one_hand, player_two_hand])
+    }
+
+    #[test]
+    fn whole_game_in_memory() {
+        let (mut session, [player_one_hand, player_two_hand]) = started([false; 2]);
+        let mut tally = Tally::default();
+        for round in 0..HAND_SIZE {
+            let (player_one_card, player_two_card) = (
+                player_one_hand.cards()[round],
+                player_two_hand.cards()[round],
+            );
+            // Who plays first shouldn't matter.
+            let (first, second) = if round % 2 == 0 {
+                ((Seat::One, player_one_card), (Seat::Two, player_two_card))
+            } else {
+                ((Seat::Two, player_two_card), (Seat::One, player_one_card))
+            };
+            assert_eq!(session.waiting_on(), Some(Seat::One));
+            assert_eq!(
+                session.on_message(first.0, Message::PlayCard(first.1)),
+                vec![]
+            );
+            assert_eq!(session.waiting_on(), Some(second.0));
+            let outgoing = session.on_message(second.0, Message::PlayCard(second.1));
+
+            let (player_one_result, player_two_result) =
+                tally.record(player_one_card, player_two_card);
+            assert_eq!(
+                outgoing[..2],
+                [
+                    (Seat::One, Message::PlayResult(player_one_result)),
+                    (Seat::Two, Message::PlayResult(player_two_result)),
+                ]
+            );
+            if round + 1 < HAND_SIZE {
+                assert_eq!(outgoing.len(), 2);
+            } else {
+                let (player_one_game_over, player_two_game_over) = tally.game_over();
+                assert_eq!(
+                    outgoing[2..],
+                    [
+                        (Seat::One, player_one_game_over),
+                        (Seat::Two, player_two_game_over),
+                    ]
+                );
+            }
+        }
+        assert_eq!(session.waiting_on(), None);
+        assert_eq!(session.ending(), Some(Ending::Finished));
+        // Anything after that is for whoever's running the game to handle.
+        assert_eq!(session.on_message(Seat::One, Message::Rematch), vec![]);
+    }
+
+    #[test]
+    fn playing_twice_in_a_round_is_out_of_turn() {
+        let (mut session, [player_one_hand, _]) = started([false; 2]);
+        let card = player_one_hand.cards()[0];
+        assert_eq!(
+            session.on_message(Seat::One, Message::PlayCard(card)),
+            vec![]
+        );
+        assert_eq!(
+            session.on_message(Seat::One, Message::PlayCard(card)),
+            vec![
+                (Seat::One, Message::Error(ErrorCode::OutOfTurn)),
+                (Seat::Two, Message::Error(ErrorCode::OpponentGone)),
+            ]
+        );
+        assert_eq!(
+            session.ending(),
+            Some(Ending::Faulted {
+                culprit: Seat::One,
+                code: ErrorCode::OutOfTurn
+            })
+        );
+        assert_eq!(session.waiting_on(), None);
+    }
+
+    #[test]
+    fn strict_games_never_start_from_a_bad_deal() {
+        let (hand, _) = Deck::shuffled(&mut StdRng::seed_from_u64(33)).deal();
+        let rules = Rules {
+            strict: true,
+            ..Rules::default()
+        };
+        let mut session = GameSession::new(rules, (hand, hand), [false; 2]);
+        assert_eq!(session.start(), Err(DealError::Duplicate(hand.cards()[0])));
+        assert_eq!(session.waiting_on(), None);
+        assert_eq!(session.ending(), None);
+    }
+
+    #[test]
+    fn only_cards_that_were_dealt_can_be_played() {
+        let (mut session, [player_one_hand, player_two_hand]) = started([false; 2]);
+        let cheating = vec![
+            (Seat::One, Message::Error(ErrorCode::InvalidCard)),
+            (Seat::Two, Message::Error(ErrorCode::OpponentGone)),
+        ];
+        let theirs = player_two_hand.cards()[0];
+        assert_eq!(
+            session.on_message(Seat::One, Message::PlayCard(theirs)),
+            cheating
+        );
+        assert_eq!(
+            session.ending(),
+            Some(Ending::Cheated {
+                culprit: Seat::One,
+                cheat: Cheat::NotDealt(theirs)
+            })
+        );
+
+        // Cards compare by rank, so one of theirs that matches one of ours
+        // could pass for it, but mustn't.
+        let (mut session, _) = started([false; 2]);
+        let impostor = player_two_hand
+            .iter()
+            .find(|&theirs| player_one_hand.iter().any(|mine| mine == theirs))
+            .unwrap();
+        assert_eq!(
+            session.on_message(Seat::One, Message::PlayCard(impostor)),
+            cheating
+        );
+        assert!(matches!(
+            session.ending(),
+            Some(Ending::Cheated {
+                cheat: Cheat::NotDealt(card),
+                ..
+            }) if card.value() == impostor.value()
+        ));
+    }
+
+    #[test]
+    fn replaying_a_card_is_cheating() {
+        let (mut session, [player_one_hand, player_two_hand]) = started([false; 2]);
+        for round in 0..3 {
+            session.on_message(Seat::One, Message::PlayCard(player_one_hand.cards()[round]));
+            session.on_message(Seat::Two, Message::PlayCard(player_two_hand.cards()[round]));
+        }
+        // Honest, right up until they try to play their second card again.
+        let card = player_one_hand.cards()[1];
+        assert_eq!(
+            session.on_message(Seat::One, Message::PlayCard(card)),
+            vec![
+                (Seat::One, Message::Error(ErrorCode::InvalidCard)),
+                (Seat::Two, Message::Error(ErrorCode::OpponentGone)),
+            ]
+        );
+        assert_eq!(
+            session.ending(),
+            Some(Ending::Cheated {
+                culprit: Seat::One,
+                cheat: Cheat::Replayed(card)
+            })
+        );
+        assert_eq!(usize::from(session.score().rounds_left()), HAND_SIZE - 3);
+    }
+
+    #[test]
+    fn hanging_up_only_tells_the_opponent() {
+        let (mut session, [player_one_hand, _]) = started([false; 2]);
+        session.on_message(Seat::One, Message::PlayCard(player_one_hand.cards()[0]));
+        assert_eq!(
+            session.on_hang_up(Seat::Two),
+            vec![(Seat::One, Message::Error(ErrorCode::OpponentGone))]
+        );
+        assert_eq!(session.ending(), Some(Ending::Abandoned(Seat::Two)));
+        assert_eq!(session.waiting_on(), None);
+
+        let (mut session, _) = started([true, false]);
+        assert_eq!(session.on_hang_up(Seat::Two), vec![]);
+    }
+
+    #[test]
+    fn only_cards_and_forfeits_can_be_played() {
+        let (mut session, _) = started([false; 2]);
+        assert_eq!(
+            session.on_message(Seat::Two, Message::Rematch),
+            vec![
+                (Seat::Two, Message::Error(ErrorCode::OutOfTurn)),
+                (Seat::One, Message::Error(ErrorCode::OpponentGone)),
+            ]
+        );
+    }
+
+    #[test]
+    fn stalling_forfeits_even_to_a_legacy_opponent() {
+        let (mut session, [player_one_hand, _]) = started([true, false]);
+        session.on_message(Seat::One, Message::PlayCard(player_one_hand.cards()[0]));
+        assert_eq!(session.waiting_on(), Some(Seat::Two));
+        assert_eq!(
+            session.on_timeout(Seat::Two),
+            vec![
+                (Seat::One, Message::PlayResult(RoundResult::Win)),
+                (
+                    Seat::Two,
+                    Message::GameOver {
+                        result: RoundResult::Lose,
+                        my_score: 0,
+                        their_score: 0,
+                    }
+                ),
+            ]
+        );
+        assert_eq!(session.ending(), Some(Ending::TimedOut(Seat::Two)));
+        assert_eq!(session.waiting_on(), None);
+        // A game that's already over can't be lost again.
+        assert_eq!(session.on_timeout(Seat::One), vec![]);
+    }
+
+    #[test]
+    fn forfeits_keep_the_scores_from_before_the_round() {
+        let (mut session, [player_one_hand, player_two_hand]) = started([false; 2]);
+        let mut tally = Tally::default();
+        tally.record(player_one_hand.cards()[0], player_two_hand.cards()[0]);
+        session.on_message(Seat::One, Message::PlayCard(player_one_hand.cards()[0]));
+        session.on_message(Seat::Two, Message::PlayCard(player_two_hand.cards()[0]));
+
+        session.on_message(Seat::One, Message::PlayCard(player_one_hand.cards()[1]));
+        assert_eq!(
+            session.on_message(Seat::Two, Message::Forfeit),
+            vec![
+                (
+                    Seat::One,
+                    Message::GameOver {
+                        result: RoundResult::Win,
+                        my_score: tally.score.wins(),
+                        their_score: tally.score.losses(),
+                    }
+                ),
+                (
+                    Seat::Two,
+                    Message::GameOver {
+                        result: RoundResult::Lose,
+                        my_score: tally.score.losses(),
+                        their_score: tally.score.wins(),
+                    }
+                ),
+            ]
+        );
+        assert_eq!(session.ending(), Some(Ending::Forfeited(Seat::Two)));
+    }
+
+    #[test]
+    fn legacy_players_only_get_original_messages() {
+        let (mut session, _) = started([true, false]);
+        assert_eq!(
+            session.on_message(Seat::Two, Message::Forfeit),
+            vec![
+                (Seat::One, Message::PlayResult(RoundResult::Win)),
+                (
+                    Seat::Two,
+                    Message::GameOver {
+                        result: RoundResult::Lose,
+                        my_score: 0,
+                        their_score: 0
+                    }
+                ),
+            ]
+        );
+
+        let (mut session, _) = started([true, false]);
+        assert_eq!(
+            session.on_fault(Seat::Two, ErrorCode::InvalidCard),
+            vec![(Seat::Two, Message::Error(ErrorCode::InvalidCard))]
+        );
+        let (mut session, _) = started([true, false]);
+        assert_eq!(
+            session.on_fault(Seat::One, ErrorCode::Malformed),
+            vec![(Seat::Two, Message::Error(ErrorCode::OpponentGone))]
+        );
+    }
+
+    /// A game with wars, where every round is a draw: player one has the
+    /// clubs and diamonds, and player two the hearts and spades, in order.
+    fn all_wars() -> (GameSession, [Hand; 2]) {
+        let hands = [0, 1]
+            .map(|half| Hand::new(std::array::from_fn(|i| card((half * HAND_SIZE + i) as u8))));
+        let rules = Rules {
+            face_down: true,
+            ..Rules::default()
+        };
+        let mut session = GameSession::new(rules, hands.into(), [false; 2]);
+        session.start().unwrap();
+        (session, hands)
+    }
+
+    /// Plays the next card in `seat`'s hand.
+    fn play_next(
+        session: &mut GameSession,
+        cards: &mut [impl Iterator<Item = Card>; 2],
+        seat: Seat,
+    ) -> Outgoing {
+        let card = cards[seat.index()].next().unwrap();
+        session.on_message(seat, Message::PlayCard(card))
+    }
+
+    #[test]
+    fn wars_put_cards_face_down_first() {
+        let (mut session, hands) = all_wars();
+        let mut cards = hands.each_ref().map(Hand::iter);
+        play_next(&mut session, &mut cards, Seat::One);
+        let outgoing = play_next(&mut session, &mut cards, Seat::Two);
+        assert_eq!(
+            outgoing[0],
+            (Seat::One, Message::PlayResult(RoundResult::Draw))
+        );
+
+        // Both players owe three cards, in whichever order they get to it.
+        let three = Message::PlayFaceDown { count: 3 };
+        assert_eq!(session.on_message(Seat::Two, three.clone()), vec![]);
+        assert_eq!(session.on_message(Seat::One, three.clone()), vec![]);
+        for hand in &mut cards {
+            hand.nth(2);
+        }
+        play_next(&mut session, &mut cards, Seat::One);
+        play_next(&mut session, &mut cards, Seat::Two);
+        assert_eq!(session.score().rounds_left(), HAND_SIZE as u8 - 8);
+
+        // Putting them down twice is out of turn.
+        assert_eq!(session.on_message(Seat::One, three.clone()), vec![]);
+        assert_eq!(
+            session.on_message(Seat::One, three)[0],
+            (Seat::One, Message::Error(ErrorCode::OutOfTurn))
+        );
+    }
+
+    #[test]
+    fn wars_need_the_right_cards_face_down() {
+        // Skipping them...
+        let (mut session, hands) = all_wars();
+        let mut cards = hands.each_ref().map(Hand::iter);
+        play_next(&mut session, &mut cards, Seat::One);
+        play_next(&mut session, &mut cards, Seat::Two);
+        assert_eq!(
+            play_next(&mut session, &mut cards, Seat::One)[0],
+            (Seat::One, Message::Error(ErrorCode::OutOfTurn))
+        );
+
+        // ...or putting down too few is out of turn.
+        let (mut session, hands) = all_wars();
+        let mut cards = hands.each_ref().map(Hand::iter);
+        play_next(&mut session, &mut cards, Seat::One);
+        play_next(&mut session, &mut cards, Seat::Two);
+        assert_eq!(
+            session.on_message(Seat::Two, Message::PlayFaceDown { count: 1 })[0],
+            (Seat::Two, Message::Error(ErrorCode::OutOfTurn))
+        );
+
+        // Without wars, there's never anything to put face down.
+        let (mut session, _) = started([false; 2]);
+        assert_eq!(
+            session.on_message(Seat::One, Message::PlayFaceDown { count: 3 })[0],
+            (Seat::One, Message::Error(ErrorCode::OutOfTurn))
+        );
+    }
+
+    #[test]
+    fn face_down_cards_are_never_played() {
+        let (mut session, hands) = all_wars();
+        let mut cards = hands.each_ref().map(Hand::iter);
+        let (mut rounds, mut left) = (0, HAND_SIZE as u8);
+        while session.waiting_on().is_some() {
+            play_next(&mut session, &mut cards, Seat::One);
+            play_next(&mut session, &mut cards, Seat::Two);
+            rounds += 1;
+            let owed = face_down_for_war(left - 1);
+            left -= 1 + owed;
+            assert_eq!(session.score().rounds_left(), left);
+            if owed > 0 {
+                for seat in [Seat::One, Seat::Two] {
+                    session.on_message(seat, Message::PlayFaceDown { count: owed });
+                    cards[seat.index()].nth(usize::from(owed) - 1);
+                }
+            }
+        }
+        assert_eq!(session.ending(), Some(Ending::Finished));
+        // Six wars of one card up and three down, and then only enough left
+        // for two plain rounds.
+        assert_eq!(rounds, 8);
+        assert!(cards.iter_mut().all(|hand| hand.next().is_none()));
+        assert_eq!(session.score().winner(), Some(RoundResult::Draw));
+    }
+
+    fn card(value: u8) -> Card {
+        Card::try_from(value).unwrap()
+    }
+
+    /// Plays 26 rounds where player one wins `wins` rounds, loses `losses`, and
+    /// ties the rest.
+    fn simulate(wins: usize, losses: usize) -> Tally {
+        let mut tally = Tally::default();
+        for round in 0..HAND_SIZE {
+            let (player_one_card, player_two_card) = if round < wins {
+                (card(12), card(0))
+            } else if round < wins + losses {
+                (card(0), card(12))
+            } else {
+                (card(5), card(18))
+            };
+            tally.record(player_one_card, player_two_card);
+        }
+        tally
+    }
+
+    #[test]
+    fn game_over_for_a_win() {
+        let (player_one, player_two) = simulate(15, 9).game_over();
+        assert_eq!(
+            player_one,
+            Message::GameOver {
+                result: RoundResult::Win,
+                my_score: 15,
+                their_score: 9
+            }
+        );
+        assert_eq!(
+            player_two,
+            Message::GameOver {
+                result: RoundResult::Lose,
+                my_score: 9,
+                their_score: 15
+            }
+        );
+    }
+
+    #[test]
+    fn game_over_for_a_loss() {
+        let (player_one, player_two) = simulate(2, 20).game_over();
+        assert_eq!(
+            player_one,
+            Message::GameOver {
+                result: RoundResult::Lose,
+                my_score: 2,
+                their_score: 20
+            }
+        );
+        assert_eq!(
+            player_two,
+            Message::GameOver {
+                result: RoundResult::Win,
+                my_score: 20,
+                their_score: 2
+            }
+        );
+    }
+
+    #[test]
+    fn game_over_for_a_draw() {
+        let (player_one, player_two) = simulate(13, 13).game_over();
+        let draw = Message::GameOver {
+            result: RoundResult::Draw,
+            my_score: 13,
+            their_score: 13,
+        };
+        assert_eq!(player_one, draw);
+        assert_eq!(player_two, draw);
+    }
+
+    #[test]
+    fn suit_tie_break_decides_equal_ranks() {
+        let king_of_clubs = card(11);
+        let king_of_spades = card(50);
+        let mut tally = Tally::default();
+        assert_eq!(
+            tally.record(king_of_clubs, king_of_spades),
+            (RoundResult::Draw, RoundResult::Draw)
+        );
+
+        let mut tally = Tally::new(
+            Rules {
+                tie_break: TieBreak::Suits,
+                ..Rules::default()
+            },
+            HAND_SIZE,
+        );
+        assert_eq!(
+            tally.record(king_of_clubs, king_of_spades),
+            (RoundResult::Lose, RoundResult::Win)
+        );
+        assert_eq!((tally.score.wins(), tally.score.losses()), (0, 1));
+    }
+
+    #[test]
+    fn aces_low_changes_who_wins() {
+        let ace_of_clubs = card(12);
+        let two_of_diamonds = card(13);
+        let mut tally = Tally::default();
+        assert_eq!(
+            tally.record(ace_of_clubs, two_of_diamonds),
+            (RoundResult::Win, RoundResult::Lose)
+        );
+
+        let mut tally = Tally::new(
+            Rules {
+                rank_order: RankOrder::AcesLow,
+                ..Rules::default()
+            },
+            HAND_SIZE,
+        );
+        assert_eq!(
+            tally.record(ace_of_clubs, two_of_diamonds),
+            (RoundResult::Lose, RoundResult::Win)
+        );
+    }
+}
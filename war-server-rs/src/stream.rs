@@ -0,0 +1,210 @@
+//! The connections games are played over: TCP, TLS over TCP, or on Unix,
+//! Unix domain sockets. Everything past accepting them treats them all the
+//! same.
+
+use std::{
+    io::{self, IoSlice},
+    net::SocketAddr,
+    path::Path,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll, Waker},
+};
+
+#[cfg(unix)]
+use tokio::net::UnixStream;
+use tokio::{
+    io::{AsyncRead, AsyncWrite, ReadBuf},
+    net::TcpStream,
+};
+use tokio_rustls::server::TlsStream;
+
+/// A connection to a client, however they connected.
+pub enum Stream {
+    Tcp(TcpStream),
+    /// Boxed, since TLS keeps a lot of state per connection.
+    Tls(Box<TlsStream<TcpStream>>),
+    #[cfg(unix)]
+    Unix(UnixStream),
+}
+
+impl From<TcpStream> for Stream {
+    fn from(stream: TcpStream) -> Stream {
+        Stream::Tcp(stream)
+    }
+}
+
+impl From<TlsStream<TcpStream>> for Stream {
+    fn from(stream: TlsStream<TcpStream>) -> Stream {
+        Stream::Tls(Box::new(stream))
+    }
+}
+
+#[cfg(unix)]
+impl From<UnixStream> for Stream {
+    fn from(stream: UnixStream) -> Stream {
+        Stream::Unix(stream)
+    }
+}
+
+impl Stream {
+    /// Whether the other end has hung up, as far as we know right now,
+    /// without waiting to find out or reading anything they've sent.
+    pub fn has_hung_up(&self) -> bool {
+        let tcp_has_hung_up = |stream: &TcpStream| {
+            let mut peeked = [0];
+            let mut peeked = ReadBuf::new(&mut peeked);
+            let mut cx = Context::from_waker(Waker::noop());
+            matches!(
+                stream.poll_peek(&mut cx, &mut peeked),
+                Poll::Ready(Ok(0) | Err(_))
+            )
+        };
+        match self {
+            Stream::Tcp(stream) => tcp_has_hung_up(stream),
+            // What's waiting is encrypted, but the connection underneath
+            // closing is still the connection closing.
+            Stream::Tls(stream) => tcp_has_hung_up(stream.get_ref().0),
+            // tokio can't peek at a Unix socket, but since it's nonblocking,
+            // the socket itself can, and says so if there's nothing to see.
+            #[cfg(unix)]
+            Stream::Unix(stream) => {
+                let mut peeked = [std::mem::MaybeUninit::uninit()];
+                match socket2::SockRef::from(stream).peek(&mut peeked) {
+                    Ok(0) => true,
+                    Ok(_) => false,
+                    Err(error) => error.kind() != io::ErrorKind::WouldBlock,
+                }
+            }
+        }
+    }
+}
+
+impl AsyncRead for Stream {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Stream::Tcp(stream) => Pin::new(stream).poll_read(cx, buf),
+            Stream::Tls(stream) => Pin::new(stream).poll_read(cx, buf),
+            #[cfg(unix)]
+            Stream::Unix(stream) => Pin::new(stream).poll_read(cx, buf),
+        }
+    }
+}
+
+impl AsyncWrite for Stream {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        match self.get_mut() {
+            Stream::Tcp(stream) => Pin::new(stream).poll_write(cx, buf),
+            Stream::Tls(stream) => Pin::new(stream).poll_write(cx, buf),
+            #[cfg(unix)]
+            Stream::Unix(stream) => Pin::new(stream).poll_write(cx, buf),
+        }
+    }
+
+    fn poll_write_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        match self.get_mut() {
+            Stream::Tcp(stream) => Pin::new(stream).poll_write_vectored(cx, bufs),
+            Stream::Tls(stream) => Pin::new(stream).poll_write_vectored(cx, bufs),
+            #[cfg(unix)]
+            Stream::Unix(stream) => Pin::new(stream).poll_write_vectored(cx, bufs),
+        }
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        match self {
+            Stream::Tcp(stream) => stream.is_write_vectored(),
+            Stream::Tls(stream) => stream.is_write_vectored(),
+            #[cfg(unix)]
+            Stream::Unix(stream) => stream.is_write_vectored(),
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Stream::Tcp(stream) => Pin::new(stream).poll_flush(cx),
+            Stream::Tls(stream) => Pin::new(stream).poll_flush(cx),
+            #[cfg(unix)]
+            Stream::Unix(stream) => Pin::new(stream).poll_flush(cx),
+        }
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Stream::Tcp(stream) => Pin::new(stream).poll_shutdown(cx),
+            Stream::Tls(stream) => Pin::new(stream).poll_shutdown(cx),
+            #[cfg(unix)]
+            Stream::Unix(stream) => Pin::new(stream).poll_shutdown(cx),
+        }
+    }
+}
+
+/// Who's on the other end of a connection, as far as the logs are concerned.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PlayerId {
+    /// A TCP client, by their address.
+    Addr(SocketAddr),
+    /// A client of the Unix socket at `path`, which is all of them, so
+    /// they're numbered in the order they connected.
+    Unix { path: Arc<Path>, connection: u64 },
+    /// One end of an in-memory pipe, like in a test, which has no address,
+    /// by whatever it was called.
+    Label(&'static str),
+}
+
+impl From<SocketAddr> for PlayerId {
+    fn from(addr: SocketAddr) -> PlayerId {
+        PlayerId::Addr(addr)
+    }
+}
+
+/// Like `127.0.0.1:4444`, or `/run/war.sock#3`.
+impl std::fmt::Display for PlayerId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PlayerId::Addr(addr) => write!(f, "{addr}"),
+            PlayerId::Unix { path, connection } => write!(f, "{}#{connection}", path.display()),
+            PlayerId::Label(label) => write!(f, "{label}"),
+        }
+    }
+}
+
+#[cfg(all(test, unix))]
+mod test {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn unix_clients_that_hang_up_are_noticed() {
+        let (server, mut client) = UnixStream::pair().unwrap();
+        let mut server = Stream::from(server);
+        assert!(!server.has_hung_up());
+        client.write_all(&[0]).await.unwrap();
+        assert!(!server.has_hung_up());
+        // Peeking didn't take what they sent.
+        assert_eq!(server.read_u8().await.unwrap(), 0);
+        drop(client);
+        assert!(server.has_hung_up());
+    }
+
+    #[test]
+    fn unix_clients_are_numbered() {
+        let id = PlayerId::Unix {
+            path: Path::new("/run/war.sock").into(),
+            connection: 3,
+        };
+        assert_eq!(id.to_string(), "/run/war.sock#3");
+    }
+}
@@ -0,0 +1,99 @@
+//! Serving over TLS: the certificate the server shows clients, and the TLS
+//! handshake every connection goes through before anything's read from it.
+
+use std::{io, path::Path, sync::Arc, time::Duration};
+
+use rustls_pki_types::{CertificateDer, PrivateKeyDer, pem::PemObject};
+use tokio::net::TcpStream;
+use tokio_rustls::{TlsAcceptor, rustls::ServerConfig, server::TlsStream};
+
+#[derive(thiserror::Error, Debug)]
+pub enum TlsError {
+    #[error("Couldn't read a certificate chain from {path:?}: {error}")]
+    Cert {
+        path: Box<Path>,
+        error: rustls_pki_types::pem::Error,
+    },
+    #[error("{path:?} doesn't have any certificates in it")]
+    NoCerts { path: Box<Path> },
+    #[error("Couldn't read a private key from {path:?}: {error}")]
+    Key {
+        path: Box<Path>,
+        error: rustls_pki_types::pem::Error,
+    },
+    #[error("The certificate and key can't be used together: {0}")]
+    Rejected(tokio_rustls::rustls::Error),
+}
+
+/// What TLS connections are accepted with: the certificate chain in the PEM
+/// file at `cert`, leaf first, and its private key, in the PEM file at `key`.
+pub fn acceptor(cert: &Path, key: &Path) -> Result<TlsAcceptor, TlsError> {
+    let certs = CertificateDer::pem_file_iter(cert)
+        .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+        .map_err(|error| TlsError::Cert {
+            path: cert.into(),
+            error,
+        })?;
+    if certs.is_empty() {
+        return Err(TlsError::NoCerts { path: cert.into() });
+    }
+    let key = PrivateKeyDer::from_pem_file(key).map_err(|error| TlsError::Key {
+        path: key.into(),
+        error,
+    })?;
+    let config = ServerConfig::builder()
+        .with_no_client_auth()
+        .with_single_cert(certs, key)
+        .map_err(TlsError::Rejected)?;
+    Ok(TlsAcceptor::from(Arc::new(config)))
+}
+
+/// Goes through the TLS handshake with a client who just connected, giving
+/// up after `timeout`.
+pub async fn accept(
+    acceptor: &TlsAcceptor,
+    stream: TcpStream,
+    timeout: Duration,
+) -> io::Result<TlsStream<TcpStream>> {
+    match tokio::time::timeout(timeout, acceptor.accept(stream)).await {
+        Ok(accepted) => accepted,
+        Err(_) => Err(io::ErrorKind::TimedOut.into()),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn missing_and_mismatched_files_are_errors() {
+        let dir = std::env::temp_dir();
+        let id = std::process::id();
+        let cert = dir.join(format!("war-tls-test-{id}.crt"));
+        let key = dir.join(format!("war-tls-test-{id}.key"));
+        let nowhere = dir.join(format!("war-tls-test-{id}.missing"));
+        let signed = rcgen::generate_simple_self_signed(["localhost".to_string()]).unwrap();
+        let other = rcgen::generate_simple_self_signed(["localhost".to_string()]).unwrap();
+        std::fs::write(&cert, signed.cert.pem()).unwrap();
+        std::fs::write(&key, other.signing_key.serialize_pem()).unwrap();
+
+        assert!(matches!(
+            acceptor(&nowhere, &key),
+            Err(TlsError::Cert { .. })
+        ));
+        assert!(matches!(
+            acceptor(&key, &key),
+            Err(TlsError::NoCerts { .. })
+        ));
+        assert!(matches!(
+            acceptor(&cert, &nowhere),
+            Err(TlsError::Key { .. })
+        ));
+        assert!(matches!(acceptor(&cert, &key), Err(TlsError::Rejected(_))));
+        std::fs::write(&key, signed.signing_key.serialize_pem()).unwrap();
+        assert!(acceptor(&cert, &key).is_ok());
+
+        std::fs::remove_file(cert).unwrap();
+        std::fs::remove_file(key).unwrap();
+    }
+}
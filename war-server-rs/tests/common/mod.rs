@@ -0,0 +1,116 @@
+//! Running the server itself, for the tests that need the whole thing.
+
+// Each test file only uses some of these.
+#![allow(dead_code)]
+
+use std::{fmt::Debug, net::SocketAddr, process::Stdio, str::FromStr};
+
+use tokio::{
+    io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, BufReader},
+    net::TcpStream,
+    process::{Child, Command},
+};
+use war_protocol::*;
+
+/// Starts the server on an ephemeral port, with `args`, returning it and
+/// where it's listening.
+pub async fn start_server(args: &[&str]) -> (Child, SocketAddr) {
+    start_server_on("127.0.0.1", args).await
+}
+
+/// Like [`start_server`], but listening on `host`.
+pub async fn start_server_on(host: &str, args: &[&str]) -> (Child, SocketAddr) {
+    let (server, addrs) = start_listening(&[&[host, "0"], args].concat(), 1).await;
+    (server, addrs[0])
+}
+
+/// Starts the server with nothing but `args`, which should have it listen in
+/// `listeners` places, returning it and each place it's listening: an address,
+/// or a path for a Unix socket.
+pub async fn start_listening<A>(args: &[&str], listeners: usize) -> (Child, Vec<A>)
+where
+    A: FromStr<Err: Debug>,
+{
+    let mut server = Command::new(env!("CARGO_BIN_EXE_war-server-rs"))
+        .args(args)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .kill_on_drop(true)
+        .spawn()
+        .unwrap();
+    let mut stdout = BufReader::new(server.stdout.take().unwrap());
+    let mut addrs = Vec::new();
+    for _ in 0..listeners {
+        let mut line = String::new();
+        stdout.read_line(&mut line).await.unwrap();
+        let addr = line
+            .trim()
+            .strip_prefix("Listening on ")
+            .unwrap()
+            .parse()
+            .unwrap();
+        addrs.push(addr);
+    }
+    (server, addrs)
+}
+
+/// Asks for a game speaking protocol `version` (one without a Hello), and
+/// waits for it to start.
+pub async fn join(addr: SocketAddr, version: u8) -> (TcpStream, Hand) {
+    join_over(TcpStream::connect(addr).await.unwrap(), version).await
+}
+
+/// Like [`join`], but over a connection that's already open.
+pub async fn join_over<S: AsyncRead + AsyncWrite + Unpin>(mut stream: S, version: u8) -> (S, Hand) {
+    Message::WantGame { version }
+        .write_to(&mut stream)
+        .await
+        .unwrap();
+    let Message::GameStart(hand) = Message::read_from(&mut stream).await.unwrap() else {
+        panic!("Expected GameStart.");
+    };
+    (stream, hand)
+}
+
+/// Connects to a server serving over TLS at `addr`, trusting nothing but the
+/// certificate authority in `ca`, a PEM file's contents, to vouch that it's
+/// `name`.
+pub async fn connect_tls(
+    addr: SocketAddr,
+    ca: &str,
+    name: &str,
+) -> tokio_rustls::client::TlsStream<TcpStream> {
+    use rustls_pki_types::{CertificateDer, ServerName, pem::PemObject};
+    use tokio_rustls::rustls::{ClientConfig, RootCertStore};
+
+    let mut roots = RootCertStore::empty();
+    for cert in CertificateDer::pem_slice_iter(ca.as_bytes()) {
+        roots.add(cert.unwrap()).unwrap();
+    }
+    let config = ClientConfig::builder()
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+    let connector = tokio_rustls::TlsConnector::from(std::sync::Arc::new(config));
+    let name = ServerName::try_from(name.to_string()).unwrap();
+    let stream = TcpStream::connect(addr).await.unwrap();
+    connector.connect(name, stream).await.unwrap()
+}
+
+/// Plays `hand` in the order it was dealt, until the server hangs up.
+pub async fn play_out<S: AsyncRead + AsyncWrite + Unpin>(mut stream: S, hand: Hand) {
+    for card in hand.iter() {
+        Message::PlayCard(card).write_to(&mut stream).await.unwrap();
+        let message = Message::read_from(&mut stream).await.unwrap();
+        assert!(matches!(message, Message::PlayResult(_)));
+    }
+    assert_eq!(stream.read(&mut [0]).await.unwrap(), 0);
+}
+
+#[cfg(unix)]
+pub fn signal(server: &Child, signal: &str) {
+    let status = std::process::Command::new("kill")
+        .args([signal, &server.id().unwrap().to_string()])
+        .status()
+        .unwrap();
+    assert!(status.success());
+}
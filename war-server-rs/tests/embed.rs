@@ -0,0 +1,169 @@
+//! Running the server from inside another program, rather than as one of its
+//! own.
+
+mod common;
+
+use std::time::Duration;
+
+use common::{join, play_out};
+use tokio::{
+    io::AsyncReadExt,
+    net::{TcpListener, TcpStream},
+    sync::oneshot,
+};
+use war_protocol::{ErrorCode, Message, PROTOCOL_VERSION, PlayerName};
+use war_server_rs::{Heartbeat, ServerConfig, ServerStats, run_server, serve};
+
+#[tokio::test]
+async fn a_game_against_a_server_in_the_same_process() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let (stop, stopped) = oneshot::channel::<()>();
+    let server = tokio::spawn(run_server(listener, async {
+        let _ = stopped.await;
+    }));
+    let ((one, one_hand), (two, two_hand)) = tokio::join!(join(addr, 0), join(addr, 0));
+    tokio::join!(play_out(one, one_hand), play_out(two, two_hand));
+    stop.send(()).unwrap();
+    let stats = server.await.unwrap().unwrap();
+    assert_eq!(
+        stats,
+        ServerStats {
+            connections: 2,
+            games_served: 1,
+            games_failed: 0,
+            games_panicked: 0,
+            games_aborted: 0,
+        }
+    );
+}
+
+#[tokio::test]
+async fn games_left_at_the_drain_deadline_are_counted_as_aborted() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let config = ServerConfig {
+        drain_timeout: Duration::ZERO,
+        ..ServerConfig::default()
+    };
+    let (stop, stopped) = oneshot::channel::<()>();
+    let server = tokio::spawn(serve(config, vec![listener.into()], async {
+        let _ = stopped.await;
+    }));
+    let ((mut one, _), (mut two, _)) = tokio::join!(join(addr, 0), join(addr, 0));
+    stop.send(()).unwrap();
+    // Legacy clients are hung up on without being told why.
+    assert_eq!(one.read(&mut [0]).await.unwrap(), 0);
+    assert_eq!(two.read(&mut [0]).await.unwrap(), 0);
+    let stats = server.await.unwrap().unwrap();
+    assert_eq!(
+        stats,
+        ServerStats {
+            connections: 2,
+            games_served: 0,
+            games_failed: 0,
+            games_panicked: 0,
+            games_aborted: 1,
+        }
+    );
+}
+
+#[tokio::test]
+async fn playing_once_stops_once_the_game_is_over() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let config = ServerConfig {
+        once: true,
+        ..ServerConfig::default()
+    };
+    let server = tokio::spawn(serve(config, vec![listener.into()], std::future::pending()));
+    let ((one, one_hand), (two, two_hand)) = tokio::join!(join(addr, 0), join(addr, 0));
+    tokio::join!(play_out(one, one_hand), play_out(two, two_hand));
+    let stats = tokio::time::timeout(Duration::from_secs(5), server)
+        .await
+        .expect("The server should've stopped once the game was over.")
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        stats,
+        ServerStats {
+            connections: 2,
+            games_served: 1,
+            games_failed: 0,
+            games_panicked: 0,
+            games_aborted: 0,
+        }
+    );
+    assert!(TcpStream::connect(addr).await.is_err());
+}
+
+#[tokio::test]
+async fn lobbies_time_out_on_their_own() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let config = ServerConfig {
+        lobby_timeout: Duration::from_millis(100),
+        ..ServerConfig::default()
+    };
+    tokio::spawn(serve(config, vec![listener.into()], std::future::pending()));
+    let mut waiting = TcpStream::connect(addr).await.unwrap();
+    Message::WantGame { version: 1 }
+        .write_to(&mut waiting)
+        .await
+        .unwrap();
+    let mut lonely = TcpStream::connect(addr).await.unwrap();
+    Message::JoinLobby {
+        version: 1,
+        code: *b"solo",
+    }
+    .write_to(&mut lonely)
+    .await
+    .unwrap();
+    let timed_out = tokio::time::timeout(Duration::from_secs(2), Message::read_from(&mut lonely))
+        .await
+        .expect("The lobby should've timed out.");
+    assert!(matches!(timed_out, Ok(Message::Error(ErrorCode::Timeout))));
+    // Everyone else still waits the pairing timeout.
+    assert!(
+        tokio::time::timeout(Duration::from_millis(100), waiting.read(&mut [0]))
+            .await
+            .is_err()
+    );
+}
+
+#[tokio::test]
+async fn waiting_players_are_pinged_as_often_as_configured() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let config = ServerConfig {
+        heartbeat: Heartbeat {
+            interval: Duration::from_millis(50),
+            timeout: Duration::from_millis(100),
+        },
+        ..ServerConfig::default()
+    };
+    tokio::spawn(serve(config, vec![listener.into()], std::future::pending()));
+    let mut quiet = TcpStream::connect(addr).await.unwrap();
+    for message in [
+        Message::WantGame {
+            version: PROTOCOL_VERSION,
+        },
+        Message::Hello(PlayerName::try_from("quiet").unwrap()),
+    ] {
+        message.write_to(&mut quiet).await.unwrap();
+    }
+    let heard = tokio::time::timeout(Duration::from_secs(2), async {
+        let mut heard = Vec::new();
+        while let Ok(message) = Message::read_from(&mut quiet).await {
+            heard.push(message);
+        }
+        heard
+    })
+    .await
+    .expect("A player who never answers should've been hung up on.");
+    assert!(matches!(heard[0], Message::ServerInfo { .. }));
+    assert_eq!(
+        heard[1..],
+        [Message::Ping(1), Message::Error(ErrorCode::Timeout)]
+    );
+}
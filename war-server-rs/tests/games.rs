@@ -0,0 +1,66 @@
+//! Playing games through the library, over in-memory pipes, with no server
+//! or sockets involved.
+
+mod common;
+
+use common::{join_over, play_out};
+use tokio::io::{AsyncReadExt, DuplexStream};
+use war_protocol::*;
+use war_server_rs::{
+    Game, GameError, GameId, GameOutcome, Player, PlayerId, serve_game, server::EndReason,
+    session::Rules,
+};
+
+/// Greets the player at the other end of each pipe, known by its label, then
+/// serves them one game.
+async fn seat(
+    [one, two]: [(DuplexStream, &'static str); 2],
+    rules: Rules,
+) -> Result<GameOutcome, GameError> {
+    let mut player_one = Player::new((one.0, PlayerId::Label(one.1)), WireFormat::Binary);
+    let mut player_two = Player::new((two.0, PlayerId::Label(two.1)), WireFormat::Binary);
+    player_one.greet().await.unwrap();
+    player_two.greet().await.unwrap();
+    let game = Game {
+        id: GameId::next(),
+        player_one,
+        player_two,
+        rules,
+    };
+    serve_game(game, std::future::pending()).await
+}
+
+#[tokio::test]
+async fn a_game_over_in_memory_pipes() {
+    let (one, one_server) = tokio::io::duplex(MAX_FRAME_LEN);
+    let (two, two_server) = tokio::io::duplex(MAX_FRAME_LEN);
+    let server = tokio::spawn(seat(
+        [(one_server, "one"), (two_server, "two")],
+        Rules::default(),
+    ));
+    let ((one, one_hand), (two, two_hand)) = tokio::join!(join_over(one, 0), join_over(two, 0));
+    tokio::join!(play_out(one, one_hand), play_out(two, two_hand));
+
+    let outcome = server.await.unwrap().unwrap();
+    assert_eq!(outcome.reason, EndReason::Finished);
+    assert_eq!(usize::from(outcome.rounds_played), HAND_SIZE);
+}
+
+#[tokio::test]
+async fn hanging_up_is_blamed_on_whoever_hung_up() {
+    let (quitter, quitter_server) = tokio::io::duplex(MAX_FRAME_LEN);
+    let (stayer, stayer_server) = tokio::io::duplex(MAX_FRAME_LEN);
+    let server = tokio::spawn(seat(
+        [(quitter_server, "quitter"), (stayer_server, "stayer")],
+        Rules::default(),
+    ));
+    let ((quitter, _), (mut stayer, _)) = tokio::join!(join_over(quitter, 0), join_over(stayer, 0));
+    drop(quitter);
+
+    // Legacy clients are hung up on without being told why.
+    assert_eq!(stayer.read(&mut [0]).await.unwrap(), 0);
+    let Err(GameError::PlayerDisconnected { player, .. }) = server.await.unwrap() else {
+        panic!("The game should've ended when a player hung up.");
+    };
+    assert_eq!(player, PlayerId::Label("quitter"));
+}
@@ -0,0 +1,69 @@
+//! How long the last round of a game takes over loopback, with and without
+//! TCP_NODELAY on the server's end. That round is the one where the server
+//! has the most to say, a PlayResult and then a GameOver, and the players'
+//! own connections leave Nagle's algorithm on, like most clients would. If
+//! the server wrote those two separately, without TCP_NODELAY, the GameOver
+//! would wait for the PlayResult to be acknowledged, which a delayed ACK can
+//! hold up by 40ms. They're written together, so it never does.
+//!
+//! Timing depends on the machine, so this only runs when asked for, with
+//! `cargo test --test latency -- --ignored`.
+
+mod common;
+
+use std::time::Duration;
+
+use common::{join, start_server};
+use tokio::{net::TcpStream, time::Instant};
+use war_protocol::*;
+
+/// How many games to average the last round over.
+const GAMES: u32 = 20;
+
+/// Plays [`GAMES`] games against a server started with `args`, returning how
+/// long their last rounds took on average, from both players sending their
+/// last cards until both of them have heard how the game ended.
+async fn last_round(args: &[&str]) -> Duration {
+    let (_server, addr) = start_server(args).await;
+    let mut total = Duration::ZERO;
+    for _ in 0..GAMES {
+        // Version 1 is the oldest one with a GameOver.
+        let ((mut one, one_hand), (mut two, two_hand)) = tokio::join!(join(addr, 1), join(addr, 1));
+        let mut rounds = one_hand.iter().zip(two_hand.iter()).peekable();
+        let (last_one, last_two) = loop {
+            let (one_card, two_card) = rounds.next().unwrap();
+            if rounds.peek().is_none() {
+                break (one_card, two_card);
+            }
+            tokio::join!(play(&mut one, one_card), play(&mut two, two_card));
+        };
+        let started = Instant::now();
+        tokio::join!(finish(&mut one, last_one), finish(&mut two, last_two));
+        total += started.elapsed();
+    }
+    total / GAMES
+}
+
+async fn play(stream: &mut TcpStream, card: Card) {
+    Message::PlayCard(card).write_to(stream).await.unwrap();
+    let message = Message::read_from(stream).await.unwrap();
+    assert!(matches!(message, Message::PlayResult(_)));
+}
+
+/// Plays the last card of a game, and waits to hear the game's over.
+async fn finish(stream: &mut TcpStream, card: Card) {
+    play(stream, card).await;
+    let message = Message::read_from(stream).await.unwrap();
+    assert!(matches!(message, Message::GameOver { .. }));
+}
+
+#[tokio::test]
+#[ignore = "timing depends on the machine"]
+async fn nagle_doesnt_hold_up_the_last_round() {
+    let nodelay = last_round(&[]).await;
+    let nagle = last_round(&["--no-nodelay"]).await;
+    assert!(
+        nagle < nodelay + Duration::from_millis(5),
+        "The last round took {nodelay:?} with TCP_NODELAY, and {nagle:?} without it."
+    );
+}
@@ -0,0 +1,83 @@
+//! Where the server listens, and who it takes connections from there.
+
+mod common;
+
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr};
+
+use common::{join, start_listening, start_server_on};
+#[cfg(unix)]
+use common::{join_over, play_out, signal};
+
+#[tokio::test]
+async fn dual_stack_pairs_ipv4_and_ipv6_players() {
+    let (_server, addr) = start_server_on("::", &["--dual-stack"]).await;
+    let ipv4 = SocketAddr::new(Ipv4Addr::LOCALHOST.into(), addr.port());
+    let ipv6 = SocketAddr::new(Ipv6Addr::LOCALHOST.into(), addr.port());
+    let ((_, one), (_, two)) = tokio::join!(join(ipv4, 0), join(ipv6, 0));
+    // Dealt from the same deck, so it's the same game.
+    assert!(one.iter().all(|card| !two.contains(card)));
+}
+
+#[tokio::test]
+async fn players_on_different_listeners_are_paired() {
+    let (_server, addrs) =
+        start_listening::<SocketAddr>(&["--listen", "127.0.0.1:0", "--listen", "[::1]:0"], 2).await;
+    assert!(addrs[0].is_ipv4() && addrs[1].is_ipv6(), "{addrs:?}");
+    let ((_, one), (_, two)) = tokio::join!(join(addrs[0], 0), join(addrs[1], 0));
+    assert!(one.iter().all(|card| !two.contains(card)));
+}
+
+#[tokio::test]
+async fn the_port_file_says_where_to_connect() {
+    let path = std::env::temp_dir().join(format!("war-port-{}", std::process::id()));
+    let port_file = path.to_str().unwrap();
+    let (_server, printed) = start_listening::<SocketAddr>(
+        &[
+            "--listen",
+            "127.0.0.1:0",
+            "--listen",
+            "[::1]:0",
+            "--port-file",
+            port_file,
+        ],
+        2,
+    )
+    .await;
+    let written: Vec<SocketAddr> = std::fs::read_to_string(&path)
+        .unwrap()
+        .lines()
+        .map(|line| line.parse().unwrap())
+        .collect();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(written, printed);
+    assert_ne!(written[0].port(), 0);
+    let ((_, one), (_, two)) = tokio::join!(join(written[0], 0), join(written[1], 0));
+    assert!(one.iter().all(|card| !two.contains(card)));
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn a_whole_game_over_a_unix_socket() {
+    use std::path::PathBuf;
+
+    use tokio::net::{UnixListener, UnixStream};
+
+    let path = std::env::temp_dir().join(format!("war-listen-{}.sock", std::process::id()));
+    // Left behind, as if by a server that was killed.
+    let _ = std::fs::remove_file(&path);
+    drop(UnixListener::bind(&path).unwrap());
+
+    let (mut server, paths) =
+        start_listening::<PathBuf>(&["--unix", path.to_str().unwrap()], 1).await;
+    assert_eq!(paths.len(), 1);
+    assert_eq!(paths[0], path);
+    let ((one, one_hand), (two, two_hand)) = tokio::join!(
+        join_over(UnixStream::connect(&path).await.unwrap(), 0),
+        join_over(UnixStream::connect(&path).await.unwrap(), 0)
+    );
+    tokio::join!(play_out(one, one_hand), play_out(two, two_hand));
+
+    signal(&server, "-INT");
+    assert!(server.wait().await.unwrap().success());
+    assert!(!path.exists());
+}
@@ -0,0 +1,33 @@
+//! Running the server for a single game, and exiting with how it went.
+
+mod common;
+
+use std::time::Duration;
+
+use common::{join, play_out, start_server};
+
+#[tokio::test]
+async fn once_exits_cleanly_after_its_game() {
+    let (mut server, addr) = start_server(&["--once"]).await;
+    let ((one, one_hand), (two, two_hand)) = tokio::join!(join(addr, 0), join(addr, 0));
+    tokio::join!(play_out(one, one_hand), play_out(two, two_hand));
+    let status = tokio::time::timeout(Duration::from_secs(5), server.wait())
+        .await
+        .expect("The server should've quit once the game was over.")
+        .unwrap();
+    assert!(status.success(), "{status}");
+}
+
+#[tokio::test]
+async fn once_fails_if_its_game_does() {
+    let (mut server, addr) = start_server(&["--once"]).await;
+    let ((one, _), (two, _)) = tokio::join!(join(addr, 0), join(addr, 0));
+    // Both of them, since whoever the game's waiting on first would just
+    // forfeit if only the other one did.
+    drop((one, two));
+    let status = tokio::time::timeout(Duration::from_secs(5), server.wait())
+        .await
+        .expect("The server should've quit once the game was over.")
+        .unwrap();
+    assert_eq!(status.code(), Some(1));
+}
@@ -0,0 +1,41 @@
+//! Running the server itself, and asking it to stop partway through a game.
+
+#![cfg(unix)]
+
+mod common;
+
+use std::time::Duration;
+
+use common::{join, play_out, signal, start_server};
+use tokio::io::AsyncReadExt;
+use war_protocol::*;
+
+#[tokio::test]
+async fn interrupting_lets_the_game_in_progress_finish() {
+    let (mut server, addr) = start_server(&[]).await;
+    let ((one, one_hand), (two, two_hand)) = tokio::join!(join(addr, 0), join(addr, 0));
+    signal(&server, "-INT");
+    tokio::join!(play_out(one, one_hand), play_out(two, two_hand));
+    let status = tokio::time::timeout(Duration::from_secs(5), server.wait())
+        .await
+        .expect("The server should've quit once the game was over.")
+        .unwrap();
+    assert!(status.success(), "{status}");
+}
+
+#[tokio::test]
+async fn games_still_going_at_the_drain_deadline_are_aborted() {
+    let (mut server, addr) = start_server(&["--drain-timeout", "1"]).await;
+    let ((mut one, _), (mut two, _)) = tokio::join!(join(addr, 1), join(addr, 1));
+    signal(&server, "-TERM");
+    for player in [&mut one, &mut two] {
+        let message = Message::read_from(player).await.unwrap();
+        assert!(matches!(message, Message::Error(ErrorCode::OpponentGone)));
+        assert_eq!(player.read(&mut [0]).await.unwrap(), 0);
+    }
+    let status = tokio::time::timeout(Duration::from_secs(5), server.wait())
+        .await
+        .expect("The server should've quit once the games were aborted.")
+        .unwrap();
+    assert_eq!(status.code(), Some(3));
+}
@@ -0,0 +1,76 @@
+//! Serving over TLS, with a self-signed certificate.
+
+mod common;
+
+use std::{path::PathBuf, time::Duration};
+
+use common::{connect_tls, join_over, play_out, start_server};
+use tokio::{io::AsyncWriteExt, net::TcpStream};
+use war_protocol::Message;
+
+/// A certificate for localhost, signed by itself, with its key, each written
+/// to a file named after `test`. Returns the certificate as PEM, and the two
+/// files.
+fn self_signed(test: &str) -> (String, PathBuf, PathBuf) {
+    let signed = rcgen::generate_simple_self_signed(["localhost".to_string()]).unwrap();
+    let dir = std::env::temp_dir();
+    let id = std::process::id();
+    let cert = dir.join(format!("war-{test}-{id}.crt"));
+    let key = dir.join(format!("war-{test}-{id}.key"));
+    std::fs::write(&cert, signed.cert.pem()).unwrap();
+    std::fs::write(&key, signed.signing_key.serialize_pem()).unwrap();
+    (signed.cert.pem(), cert, key)
+}
+
+#[tokio::test]
+async fn a_game_is_played_over_tls() {
+    let (ca, cert, key) = self_signed("tls-game");
+    let (_server, addr) = start_server(&[
+        "--tls-cert",
+        cert.to_str().unwrap(),
+        "--tls-key",
+        key.to_str().unwrap(),
+    ])
+    .await;
+    let (one, two) = tokio::join!(
+        connect_tls(addr, &ca, "localhost"),
+        connect_tls(addr, &ca, "localhost")
+    );
+    let ((one, one_hand), (two, two_hand)) = tokio::join!(join_over(one, 0), join_over(two, 0));
+    tokio::join!(play_out(one, one_hand), play_out(two, two_hand));
+    std::fs::remove_file(cert).unwrap();
+    std::fs::remove_file(key).unwrap();
+}
+
+#[tokio::test]
+async fn plaintext_clients_are_hung_up_on() {
+    let (_ca, cert, key) = self_signed("tls-plaintext");
+    let (_server, addr) = start_server(&[
+        "--tls-cert",
+        cert.to_str().unwrap(),
+        "--tls-key",
+        key.to_str().unwrap(),
+    ])
+    .await;
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    let mut want_game = Vec::new();
+    Message::WantGame { version: 0 }
+        .write_to(&mut want_game)
+        .await
+        .unwrap();
+    stream.write_all(&want_game).await.unwrap();
+    // A TLS alert, if anything, and never a GameStart.
+    let hung_up = tokio::time::timeout(Duration::from_secs(5), async {
+        let mut buf = [0; 256];
+        loop {
+            match tokio::io::AsyncReadExt::read(&mut stream, &mut buf).await {
+                Ok(0) | Err(_) => break,
+                Ok(_) => {}
+            }
+        }
+    })
+    .await;
+    assert!(hung_up.is_ok(), "The server should've hung up.");
+    std::fs::remove_file(cert).unwrap();
+    std::fs::remove_file(key).unwrap();
+}
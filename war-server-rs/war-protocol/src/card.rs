@@ -0,0 +1,835 @@
+//! Cards: the 52 in a deck, how they compare, and how they're written down.
+
+use zerocopy::{Immutable, IntoBytes, KnownLayout, Unaligned};
+
+const NUM_CARDS_IN_SUIT: u8 = 13;
+const NUM_SUITS: u8 = 4;
+pub const NUM_CARDS_TOTAL: u8 = NUM_CARDS_IN_SUIT * NUM_SUITS;
+/// A card's value is `suit * 13 + rank`, where both [`Suit`] and [`Rank`]
+/// count from zero in the order they're declared. So the clubs are 0 through
+/// 12, from the two up to the ace, then the diamonds, hearts, and spades.
+///
+/// The `#[repr(transparent)]` keeps a card exactly as big as its byte on the
+/// wire. That byte is one of 52 enum variants rather than any `u8`, so a card
+/// can't be anything but one of the deck's, and `Option<Card>` is still one
+/// byte, with `None` somewhere a card could never be.
+#[repr(transparent)]
+// Deliberately not `FromBytes`, since most bytes aren't cards.
+#[derive(Debug, Clone, Copy, IntoBytes, KnownLayout, Immutable, Unaligned)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(try_from = "u8", into = "u8")
+)]
+pub struct Card(Value);
+
+#[derive(thiserror::Error, Debug)]
+#[error("Card's value was {value}, the maximum is {max}", max = NUM_CARDS_TOTAL - 1)]
+pub struct CardValueTooBig {
+    pub(crate) value: u8,
+}
+
+/// Any card in the deck, and nothing else.
+#[cfg(feature = "arbitrary")]
+impl<'a> arbitrary::Arbitrary<'a> for Card {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        Ok(Card::from_value(u.int_in_range(0..=NUM_CARDS_TOTAL - 1)?))
+    }
+
+    fn size_hint(_depth: usize) -> (usize, Option<usize>) {
+        (1, Some(1))
+    }
+}
+
+impl From<Card> for u8 {
+    fn from(card: Card) -> Self {
+        card.value()
+    }
+}
+
+/// Shown as the card's value, like the byte it used to be.
+impl core::fmt::Debug for Value {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        (*self as u8).fmt(f)
+    }
+}
+
+impl TryFrom<u8> for Card {
+    type Error = CardValueTooBig;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        if value >= NUM_CARDS_TOTAL {
+            Err(CardValueTooBig { value })
+        } else {
+            Ok(Card::from_value(value))
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[repr(u8)]
+pub enum Rank {
+    Two,
+    Three,
+    Four,
+    Five,
+    Six,
+    Seven,
+    Eight,
+    Nine,
+    Ten,
+    Jack,
+    Queen,
+    King,
+    Ace,
+}
+
+impl Rank {
+    /// Every rank, lowest first, so `Rank::ALL[rank as usize] == rank`.
+    pub const ALL: [Rank; NUM_CARDS_IN_SUIT as usize] = [
+        Rank::Two,
+        Rank::Three,
+        Rank::Four,
+        Rank::Five,
+        Rank::Six,
+        Rank::Seven,
+        Rank::Eight,
+        Rank::Nine,
+        Rank::Ten,
+        Rank::Jack,
+        Rank::Queen,
+        Rank::King,
+        Rank::Ace,
+    ];
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[repr(u8)]
+pub enum Suit {
+    Clubs,
+    Diamonds,
+    Hearts,
+    Spades,
+}
+
+impl Suit {
+    /// Every suit, in the order their cards' values go, so
+    /// `Suit::ALL[suit as usize] == suit`.
+    pub const ALL: [Suit; NUM_SUITS as usize] =
+        [Suit::Clubs, Suit::Diamonds, Suit::Hearts, Suit::Spades];
+}
+
+impl Card {
+    /// The whole deck, in order of value.
+    pub const ALL: [Card; NUM_CARDS_TOTAL as usize] = {
+        let mut all = [Card::from_value(0); NUM_CARDS_TOTAL as usize];
+        let mut value = 0;
+        while value < NUM_CARDS_TOTAL {
+            all[value as usize] = Card::from_value(value);
+            value += 1;
+        }
+        all
+    };
+
+    /// Every card in the deck, in order of value.
+    pub fn all() -> impl Iterator<Item = Card> {
+        Card::ALL.into_iter()
+    }
+
+    /// Same as [`Card::with_rank_suit`].
+    pub const fn new(rank: Rank, suit: Suit) -> Card {
+        Card::with_rank_suit(rank, suit)
+    }
+
+    /// The `rank` of `suit`, worth `suit * 13 + rank`.
+    pub const fn with_rank_suit(rank: Rank, suit: Suit) -> Card {
+        Card::from_value(suit as u8 * NUM_CARDS_IN_SUIT + rank as u8)
+    }
+
+    /// All 13 cards of `suit`, lowest first.
+    pub fn cards_of_suit(suit: Suit) -> impl Iterator<Item = Card> {
+        Rank::ALL
+            .into_iter()
+            .map(move |rank| Card::with_rank_suit(rank, suit))
+    }
+
+    /// All 4 cards of `rank`, in order of suit.
+    pub fn cards_of_rank(rank: Rank) -> impl Iterator<Item = Card> {
+        Suit::ALL
+            .into_iter()
+            .map(move |suit| Card::with_rank_suit(rank, suit))
+    }
+
+    /// The card worth `value`, which callers have already checked is one.
+    pub(crate) const fn from_value(value: u8) -> Card {
+        Card(VALUES[value as usize])
+    }
+
+    /// The card's value on the wire. See [`Card`] for how that's laid out.
+    pub const fn value(self) -> u8 {
+        self.0 as u8
+    }
+
+    pub const fn rank(self) -> Rank {
+        Rank::ALL[(self.value() % NUM_CARDS_IN_SUIT) as usize]
+    }
+
+    pub const fn suit(self) -> Suit {
+        Suit::ALL[(self.value() / NUM_CARDS_IN_SUIT) as usize]
+    }
+}
+
+/// Lets `rng.random::<Card>()` pick any card from the deck, each as likely as
+/// the next.
+#[cfg(feature = "rand")]
+impl rand::distr::Distribution<Card> for rand::distr::StandardUniform {
+    fn sample<R: rand::Rng + ?Sized>(&self, rng: &mut R) -> Card {
+        Card::from_value(rng.random_range(0..NUM_CARDS_TOTAL))
+    }
+}
+
+/// Compares by rank, and nothing else.
+impl Ord for Card {
+    fn cmp(&self, other: &Self) -> core::cmp::Ordering {
+        self.rank().cmp(&other.rank())
+    }
+}
+
+impl PartialOrd for Card {
+    fn partial_cmp(&self, other: &Self) -> Option<core::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Card {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == core::cmp::Ordering::Equal
+    }
+}
+impl Eq for Card {}
+
+/// What decides a round between two cards of the same rank.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "clap", derive(clap::ValueEnum))]
+pub enum TieBreak {
+    /// Nothing: equal ranks are a draw, just like `Ord for Card` says.
+    #[default]
+    Draw,
+    /// The higher suit wins, going clubs, diamonds, hearts, then spades.
+    Suits,
+}
+
+/// Where the ace goes among the ranks. Cards are always sent as the same
+/// values either way.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RankOrder {
+    /// The ace is above the king, like `Ord for Card` says.
+    #[default]
+    AcesHigh,
+    /// The ace is below the two.
+    AcesLow,
+}
+
+impl RankOrder {
+    pub fn cmp(self, rank: Rank, other: Rank) -> core::cmp::Ordering {
+        match self {
+            RankOrder::AcesHigh => rank.cmp(&other),
+            RankOrder::AcesLow => (rank != Rank::Ace, rank).cmp(&(other != Rank::Ace, other)),
+        }
+    }
+}
+
+impl Card {
+    /// Compares by rank (ordered by `rank_order`), then by whatever
+    /// `tie_break` says. `Ord for Card` is the same as this with the defaults.
+    pub fn cmp_with(
+        self,
+        other: Card,
+        rank_order: RankOrder,
+        tie_break: TieBreak,
+    ) -> core::cmp::Ordering {
+        let by_rank = rank_order.cmp(self.rank(), other.rank());
+        match tie_break {
+            TieBreak::Draw => by_rank,
+            TieBreak::Suits => by_rank.then(self.suit().cmp(&other.suit())),
+        }
+    }
+}
+
+/// Hashes by rank, and nothing else, to agree with equality.
+impl core::hash::Hash for Card {
+    fn hash<H: core::hash::Hasher>(&self, state: &mut H) {
+        self.rank().hash(state);
+    }
+}
+
+/// A card that's only equal to itself, for when the suit matters too (like
+/// keeping track of exactly which cards a player has left).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(try_from = "Card", into = "Card")
+)]
+pub struct CardByValue(u8);
+
+impl From<Card> for CardByValue {
+    fn from(card: Card) -> Self {
+        CardByValue(card.value())
+    }
+}
+
+impl From<CardByValue> for Card {
+    fn from(card: CardByValue) -> Self {
+        Card::from_value(card.0)
+    }
+}
+
+/// Declares a constant for every card, the byte inside it, and the name
+/// [`Card`]'s `Display` gives it, from the same table, so they can never drift
+/// apart. Each line is the constant, then the card's rank and suit spelled
+/// just like in [`Rank`] and [`Suit`], which is also its name:
+/// `QUEEN_OF_HEARTS: Queen of Hearts`.
+macro_rules! cards {
+    ($($constant:ident: $rank:ident of $suit:ident,)*) => {
+        /// What's inside a [`Card`]: one variant per card, numbered by value.
+        #[repr(u8)]
+        #[allow(non_camel_case_types)]
+        #[derive(Clone, Copy, IntoBytes, KnownLayout, Immutable, Unaligned)]
+        enum Value {
+            $($constant,)*
+        }
+
+        /// Every card's byte, in order of value.
+        const VALUES: [Value; NUM_CARDS_TOTAL as usize] = [$(Value::$constant),*];
+
+        $(pub const $constant: Card = Card::with_rank_suit(Rank::$rank, Suit::$suit);)*
+
+        /// Every card's name, in order of value.
+        const CARD_NAMES: [&str; NUM_CARDS_TOTAL as usize] =
+            [$(concat!(stringify!($rank), " of ", stringify!($suit))),*];
+
+        /// Every card's constant, along with its name, for tests to check
+        /// against.
+        #[cfg(all(test, feature = "std"))]
+        const NAMED_CARDS: [(Card, &str); NUM_CARDS_TOTAL as usize] =
+            [$(($constant, concat!(stringify!($rank), " of ", stringify!($suit)))),*];
+
+        // `CARD_NAMES` only lines up with the cards if the table is in order
+        // of value, and each constant should be its card's name, shouted.
+        const _: () = {
+            /// Whether `constant` is `name` in upper case, with underscores
+            /// for spaces.
+            const fn shouts(constant: &str, name: &str) -> bool {
+                let (constant, name) = (constant.as_bytes(), name.as_bytes());
+                if constant.len() != name.len() {
+                    return false;
+                }
+                let mut i = 0;
+                while i < name.len() {
+                    let expected = match name[i] {
+                        b' ' => b'_',
+                        byte => byte.to_ascii_uppercase(),
+                    };
+                    if constant[i] != expected {
+                        return false;
+                    }
+                    i += 1;
+                }
+                true
+            }
+
+            let cards = [$($constant),*];
+            let constants = [$(stringify!($constant)),*];
+            let mut value = 0;
+            while value < cards.len() {
+                assert!(cards[value].value() as usize == value, "The cards must be in order of value.");
+                assert!(
+                    shouts(constants[value], CARD_NAMES[value]),
+                    "Each card's constant must be its name, shouted."
+                );
+                value += 1;
+            }
+        };
+    };
+}
+
+cards! {
+    TWO_OF_CLUBS: Two of Clubs,
+    THREE_OF_CLUBS: Three of Clubs,
+    FOUR_OF_CLUBS: Four of Clubs,
+    FIVE_OF_CLUBS: Five of Clubs,
+    SIX_OF_CLUBS: Six of Clubs,
+    SEVEN_OF_CLUBS: Seven of Clubs,
+    EIGHT_OF_CLUBS: Eight of Clubs,
+    NINE_OF_CLUBS: Nine of Clubs,
+    TEN_OF_CLUBS: Ten of Clubs,
+    JACK_OF_CLUBS: Jack of Clubs,
+    QUEEN_OF_CLUBS: Queen of Clubs,
+    KING_OF_CLUBS: King of Clubs,
+    ACE_OF_CLUBS: Ace of Clubs,
+    TWO_OF_DIAMONDS: Two of Diamonds,
+    THREE_OF_DIAMONDS: Three of Diamonds,
+    FOUR_OF_DIAMONDS: Four of Diamonds,
+    FIVE_OF_DIAMONDS: Five of Diamonds,
+    SIX_OF_DIAMONDS: Six of Diamonds,
+    SEVEN_OF_DIAMONDS: Seven of Diamonds,
+    EIGHT_OF_DIAMONDS: Eight of Diamonds,
+    NINE_OF_DIAMONDS: Nine of Diamonds,
+    TEN_OF_DIAMONDS: Ten of Diamonds,
+    JACK_OF_DIAMONDS: Jack of Diamonds,
+    QUEEN_OF_DIAMONDS: Queen of Diamonds,
+    KING_OF_DIAMONDS: King of Diamonds,
+    ACE_OF_DIAMONDS: Ace of Diamonds,
+    TWO_OF_HEARTS: Two of Hearts,
+    THREE_OF_HEARTS: Three of Hearts,
+    FOUR_OF_HEARTS: Four of Hearts,
+    FIVE_OF_HEARTS: Five of Hearts,
+    SIX_OF_HEARTS: Six of Hearts,
+    SEVEN_OF_HEARTS: Seven of Hearts,
+    EIGHT_OF_HEARTS: Eight of Hearts,
+    NINE_OF_HEARTS: Nine of Hearts,
+    TEN_OF_HEARTS: Ten of Hearts,
+    JACK_OF_HEARTS: Jack of Hearts,
+    QUEEN_OF_HEARTS: Queen of Hearts,
+    KING_OF_HEARTS: King of Hearts,
+    ACE_OF_HEARTS: Ace of Hearts,
+    TWO_OF_SPADES: Two of Spades,
+    THREE_OF_SPADES: Three of Spades,
+    FOUR_OF_SPADES: Four of Spades,
+    FIVE_OF_SPADES: Five of Spades,
+    SIX_OF_SPADES: Six of Spades,
+    SEVEN_OF_SPADES: Seven of Spades,
+    EIGHT_OF_SPADES: Eight of Spades,
+    NINE_OF_SPADES: Nine of Spades,
+    TEN_OF_SPADES: Ten of Spades,
+    JACK_OF_SPADES: Jack of Spades,
+    QUEEN_OF_SPADES: Queen of Spades,
+    KING_OF_SPADES: King of Spades,
+    ACE_OF_SPADES: Ace of Spades,
+}
+
+/// A card's name, like `Queen of Hearts`.
+impl core::fmt::Display for Card {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str(CARD_NAMES[usize::from(self.value())])
+    }
+}
+
+/// How each rank is written in a short code, lowest first.
+const RANK_CODES: [&str; NUM_CARDS_IN_SUIT as usize] = [
+    "2", "3", "4", "5", "6", "7", "8", "9", "10", "J", "Q", "K", "A",
+];
+/// How each suit is written in a short code, in the order their cards' values
+/// go.
+const SUIT_CODES: [char; NUM_SUITS as usize] = ['C', 'D', 'H', 'S'];
+
+impl Card {
+    /// Writes this card as a rank and a suit, like `10H` or `AS`. Parsing the
+    /// result gives back the same card.
+    pub fn short_code(self) -> ShortCode {
+        ShortCode(self)
+    }
+}
+
+/// A card written as a short code. See [`Card::short_code`].
+pub struct ShortCode(Card);
+
+impl core::fmt::Display for ShortCode {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let rank = RANK_CODES[self.0.rank() as usize];
+        let suit = SUIT_CODES[self.0.suit() as usize];
+        write!(f, "{rank}{suit}")
+    }
+}
+
+impl core::fmt::Debug for ShortCode {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        core::fmt::Display::fmt(self, f)
+    }
+}
+
+/// How each suit is drawn when there isn't room for a card's whole name, in
+/// the order their cards' values go.
+const SUIT_SYMBOLS: [char; NUM_SUITS as usize] = ['♣', '♦', '♥', '♠'];
+
+/// A card written as its rank and its suit's symbol, like `10♥`.
+pub(crate) struct Symbol(pub(crate) Card);
+
+impl core::fmt::Display for Symbol {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let rank = RANK_CODES[self.0.rank() as usize];
+        let suit = SUIT_SYMBOLS[self.0.suit() as usize];
+        write!(f, "{rank}{suit}")
+    }
+}
+
+#[derive(thiserror::Error, Debug, PartialEq, Eq)]
+pub enum ParseCardError {
+    #[error("Card codes are 2 or 3 characters long, but this one was {0}")]
+    WrongLength(usize),
+    #[error("Card code didn't start with a rank from 2 to 10, or J, Q, K, or A")]
+    BadRank,
+    #[error("Card code ended with {0:?}, which isn't C, D, H, or S")]
+    BadSuit(char),
+}
+
+/// Parses short codes like `2c`, `10H`, or `AS`: a rank and then a suit, in
+/// either case.
+impl core::str::FromStr for Card {
+    type Err = ParseCardError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let len = s.chars().count();
+        if !(2..=3).contains(&len) {
+            return Err(ParseCardError::WrongLength(len));
+        }
+        let mut chars = s.chars();
+        let suit = chars.next_back().expect("There are at least 2 characters.");
+        let rank = chars.as_str();
+        let rank = RANK_CODES
+            .iter()
+            .position(|code| code.eq_ignore_ascii_case(rank))
+            .ok_or(ParseCardError::BadRank)?;
+        let suit = SUIT_CODES
+            .iter()
+            .position(|code| code.eq_ignore_ascii_case(&suit))
+            .ok_or(ParseCardError::BadSuit(suit))?;
+        Ok(Card::new(Rank::ALL[rank], Suit::ALL[suit]))
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod test {
+    #[cfg(feature = "rand")]
+    use rand::{Rng, SeedableRng, rngs::StdRng};
+
+    use super::*;
+    use crate::format::*;
+
+    /// We are dealing with **PLAYING CARDS**.
+    ///
+    /// (This is some verbose 'idiot-proof' brainrot, but that's how I'm feeling
+    /// rn).
+    #[test]
+    fn card_constants() {
+        assert_eq!(NUM_CARDS_IN_SUIT, 13);
+        assert_eq!(NUM_SUITS, 4);
+        assert_eq!(NUM_CARDS_TOTAL, 52);
+    }
+
+    #[test]
+    fn card_names_match_their_constants() {
+        for (value, (card, name)) in NAMED_CARDS.into_iter().enumerate() {
+            assert_eq!(usize::from(card.value()), value);
+            // The constants are built from their rank and suit, so check
+            // they come back out, and build the same card again.
+            assert_eq!(
+                Card::with_rank_suit(card.rank(), card.suit()).value(),
+                card.value()
+            );
+            assert_eq!(format!("{card}"), name);
+            assert_eq!(format!("{:?} of {:?}", card.rank(), card.suit()), name);
+        }
+        assert_eq!(QUEEN_OF_HEARTS.to_string(), "Queen of Hearts");
+    }
+
+    #[test]
+    fn card_format() {
+        assert_eq!(
+            Card::try_from(2 * NUM_CARDS_IN_SUIT + 10).unwrap(),
+            QUEEN_OF_HEARTS
+        );
+        assert_eq!(
+            Card::try_from(2 * NUM_CARDS_IN_SUIT + 11).unwrap(),
+            KING_OF_HEARTS
+        );
+        assert_eq!(
+            Card::try_from(2 * NUM_CARDS_IN_SUIT + 12).unwrap(),
+            ACE_OF_HEARTS
+        );
+
+        // Card's equality only looks at rank, so these check the exact value.
+        assert_eq!(Card::with_rank_suit(Rank::Two, Suit::Clubs).value(), 0);
+        assert_eq!(Card::with_rank_suit(Rank::Ace, Suit::Clubs).value(), 12);
+        assert_eq!(Card::with_rank_suit(Rank::Two, Suit::Diamonds).value(), 13);
+        assert_eq!(
+            Card::with_rank_suit(Rank::Queen, Suit::Hearts).value(),
+            QUEEN_OF_HEARTS.value()
+        );
+        assert_eq!(Card::with_rank_suit(Rank::Ace, Suit::Spades).value(), 51);
+        for value in 0..NUM_CARDS_TOTAL {
+            let card = Card::try_from(value).unwrap();
+            assert_eq!(
+                Card::with_rank_suit(card.rank(), card.suit()).value(),
+                value
+            );
+        }
+        assert_eq!(KING_OF_SPADES.rank(), Rank::King);
+        assert_eq!(KING_OF_SPADES.suit(), Suit::Spades);
+        assert_eq!(THREE_OF_DIAMONDS.suit(), Suit::Diamonds);
+    }
+
+    #[test]
+    fn cards_of_a_suit_or_rank() {
+        for suit in Suit::ALL {
+            let cards: Vec<Card> = Card::cards_of_suit(suit).collect();
+            assert_eq!(cards.len(), NUM_CARDS_IN_SUIT as usize);
+            for (rank, card) in Rank::ALL.into_iter().zip(cards) {
+                assert_eq!((card.rank(), card.suit()), (rank, suit));
+                assert_eq!(card.value(), suit as u8 * NUM_CARDS_IN_SUIT + rank as u8);
+            }
+        }
+        for rank in Rank::ALL {
+            let cards: Vec<Card> = Card::cards_of_rank(rank).collect();
+            assert_eq!(cards.len(), NUM_SUITS as usize);
+            for (suit, card) in Suit::ALL.into_iter().zip(cards) {
+                assert_eq!((card.rank(), card.suit()), (rank, suit));
+                assert_eq!(card.value(), suit as u8 * NUM_CARDS_IN_SUIT + rank as u8);
+            }
+        }
+        // Between them, the suits hold the whole deck, once each.
+        let values: Vec<u8> = Suit::ALL
+            .into_iter()
+            .flat_map(Card::cards_of_suit)
+            .map(Card::value)
+            .collect();
+        assert_eq!(values, (0..NUM_CARDS_TOTAL).collect::<Vec<_>>());
+        assert_eq!(
+            Card::cards_of_rank(Rank::Queen)
+                .map(Card::value)
+                .collect::<Vec<_>>(),
+            [10, 23, 36, 49]
+        );
+        for (i, rank) in Rank::ALL.into_iter().enumerate() {
+            assert_eq!(rank as usize, i);
+        }
+        for (i, suit) in Suit::ALL.into_iter().enumerate() {
+            assert_eq!(suit as usize, i);
+        }
+    }
+
+    /// This test really only exists because I was gonna write it to test how
+    /// the derive macros for (Partial)?(Eq|Ord) work, and I might as well keep
+    /// it.
+    #[test]
+    fn card_comparison() {
+        // Duality of PartialOrd
+        assert!(TWO_OF_CLUBS < THREE_OF_CLUBS);
+        assert!(THREE_OF_CLUBS > TWO_OF_CLUBS);
+
+        assert!(TWO_OF_CLUBS < FOUR_OF_CLUBS);
+        assert!(FOUR_OF_CLUBS > TWO_OF_CLUBS);
+
+        assert!(THREE_OF_CLUBS < FOUR_OF_CLUBS);
+        assert!(FOUR_OF_CLUBS > THREE_OF_CLUBS);
+
+        assert!(THREE_OF_DIAMONDS < QUEEN_OF_SPADES);
+        assert!(QUEEN_OF_SPADES > THREE_OF_DIAMONDS);
+
+        // Reflexivity of equality
+        assert_eq!(KING_OF_CLUBS, KING_OF_SPADES);
+        assert_eq!(KING_OF_SPADES, KING_OF_CLUBS);
+
+        assert_eq!(ACE_OF_CLUBS, ACE_OF_SPADES);
+        assert_eq!(ACE_OF_SPADES, ACE_OF_CLUBS);
+
+        assert_eq!(TWO_OF_CLUBS, TWO_OF_DIAMONDS);
+        assert_eq!(TWO_OF_DIAMONDS, TWO_OF_CLUBS);
+
+        // Transitivity of equality
+        assert_eq!(KING_OF_CLUBS, KING_OF_HEARTS);
+        assert_eq!(KING_OF_HEARTS, KING_OF_SPADES);
+        assert_eq!(KING_OF_CLUBS, KING_OF_SPADES);
+
+        // Suit never matters, only rank.
+        assert!(Card::new(Rank::Ace, Suit::Clubs) > Card::new(Rank::King, Suit::Spades));
+        assert!(Card::new(Rank::Two, Suit::Spades) < Card::new(Rank::Three, Suit::Clubs));
+        assert_eq!(
+            Card::new(Rank::Ten, Suit::Diamonds),
+            Card::new(Rank::Ten, Suit::Hearts)
+        );
+    }
+
+    #[test]
+    fn parse_card_codes() {
+        assert_eq!("2C".parse::<Card>().unwrap().value(), 0);
+        assert_eq!("2c".parse::<Card>().unwrap().value(), TWO_OF_CLUBS.value());
+        assert_eq!(
+            "10h".parse::<Card>().unwrap().value(),
+            2 * NUM_CARDS_IN_SUIT + 8
+        );
+        assert_eq!(
+            "qH".parse::<Card>().unwrap().value(),
+            QUEEN_OF_HEARTS.value()
+        );
+        assert_eq!("AS".parse::<Card>().unwrap().value(), ACE_OF_SPADES.value());
+
+        assert_eq!("".parse::<Card>(), Err(ParseCardError::WrongLength(0)));
+        assert_eq!("A".parse::<Card>(), Err(ParseCardError::WrongLength(1)));
+        assert_eq!("10HS".parse::<Card>(), Err(ParseCardError::WrongLength(4)));
+        assert_eq!("1C".parse::<Card>(), Err(ParseCardError::BadRank));
+        assert_eq!("11C".parse::<Card>(), Err(ParseCardError::BadRank));
+        assert_eq!("TC".parse::<Card>(), Err(ParseCardError::BadRank));
+        assert_eq!("KX".parse::<Card>(), Err(ParseCardError::BadSuit('X')));
+        assert_eq!("K♠".parse::<Card>(), Err(ParseCardError::BadSuit('♠')));
+    }
+
+    #[test]
+    fn short_codes_round_trip() {
+        assert_eq!(KING_OF_HEARTS.short_code().to_string(), "KH");
+        assert_eq!(TWO_OF_CLUBS.short_code().to_string(), "2C");
+        for value in 0..NUM_CARDS_TOTAL {
+            let card = Card::try_from(value).unwrap();
+            let code = card.short_code().to_string();
+            assert_eq!(code.parse::<Card>().unwrap().value(), value);
+            assert_eq!(code.to_lowercase().parse::<Card>().unwrap().value(), value);
+        }
+    }
+
+    #[test]
+    fn all_is_the_whole_deck() {
+        assert_eq!(Card::ALL.len(), 52);
+        for (value, card) in Card::ALL.iter().enumerate() {
+            assert_eq!(usize::from(card.value()), value);
+        }
+        let values: std::collections::BTreeSet<u8> = Card::all().map(|card| card.value()).collect();
+        assert_eq!(values, (0..=51).collect());
+    }
+
+    #[cfg(feature = "rand")]
+    #[test]
+    fn random_cards_are_valid() {
+        let mut rng = StdRng::seed_from_u64(52);
+        for _ in 0..100 {
+            let card: Card = rng.random();
+            assert!(Card::try_from(card.value()).is_ok());
+        }
+    }
+
+    #[cfg(feature = "rand")]
+    #[test]
+    fn random_cards_cover_the_deck() {
+        let mut rng = StdRng::seed_from_u64(0x3a7);
+        let mut counts = [0u32; NUM_CARDS_TOTAL as usize];
+        for _ in 0..100_000 {
+            counts[usize::from(rng.random::<Card>().value())] += 1;
+        }
+        // Each card should come up about 1923 times.
+        for (value, &count) in counts.iter().enumerate() {
+            assert!(
+                (1600..2250).contains(&count),
+                "{value} came up {count} times"
+            );
+        }
+    }
+
+    /// Every way to get a card from outside this module goes through a check,
+    /// so none of them can make one that isn't in the deck.
+    #[test]
+    fn no_card_is_out_of_range() {
+        for value in 0..=u8::MAX {
+            if let Ok(card) = Card::try_from(value) {
+                assert!(card.value() < NUM_CARDS_TOTAL);
+            }
+            if let Ok(Message::PlayCard(card)) = Message::try_from(&[PLAY_CARD, value][..]) {
+                assert_eq!(card.value(), value);
+            }
+        }
+        for card in Card::all() {
+            assert!(card.value() < NUM_CARDS_TOTAL);
+        }
+        for rank in Rank::ALL {
+            for suit in Suit::ALL {
+                assert!(Card::new(rank, suit).value() < NUM_CARDS_TOTAL);
+            }
+        }
+    }
+
+    #[test]
+    fn hashing_agrees_with_equality() {
+        use std::{
+            collections::HashSet,
+            hash::{BuildHasher, RandomState},
+        };
+
+        let hasher = RandomState::new();
+        assert_eq!(
+            hasher.hash_one(KING_OF_CLUBS),
+            hasher.hash_one(KING_OF_SPADES)
+        );
+        assert_ne!(
+            CardByValue::from(KING_OF_CLUBS),
+            CardByValue::from(KING_OF_SPADES)
+        );
+
+        let by_rank: HashSet<Card> = Card::all().collect();
+        assert_eq!(by_rank.len(), 13);
+        let by_value: HashSet<CardByValue> = Card::all().map(CardByValue::from).collect();
+        assert_eq!(by_value.len(), 52);
+        assert_eq!(
+            Card::from(CardByValue::from(ACE_OF_HEARTS)).value(),
+            ACE_OF_HEARTS.value()
+        );
+    }
+
+    #[test]
+    fn suits_break_ties() {
+        use std::cmp::Ordering;
+
+        assert_eq!(
+            KING_OF_CLUBS.cmp_with(KING_OF_SPADES, RankOrder::AcesHigh, TieBreak::Draw),
+            Ordering::Equal
+        );
+        assert_eq!(
+            KING_OF_CLUBS.cmp_with(KING_OF_SPADES, RankOrder::AcesHigh, TieBreak::Suits),
+            Ordering::Less
+        );
+        assert_eq!(
+            KING_OF_SPADES.cmp_with(KING_OF_CLUBS, RankOrder::AcesHigh, TieBreak::Suits),
+            Ordering::Greater
+        );
+        // Rank still comes first.
+        assert_eq!(
+            ACE_OF_CLUBS.cmp_with(KING_OF_SPADES, RankOrder::AcesHigh, TieBreak::Suits),
+            Ordering::Greater
+        );
+        // Only a card and itself are ever a draw.
+        for card in Card::all() {
+            for other in Card::all() {
+                assert_eq!(
+                    card.cmp_with(other, RankOrder::AcesHigh, TieBreak::Suits) == Ordering::Equal,
+                    card.value() == other.value()
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn aces_low_puts_the_ace_under_the_two() {
+        use std::cmp::Ordering;
+
+        let two_of_diamonds = Card::new(Rank::Two, Suit::Diamonds);
+        assert_eq!(
+            ACE_OF_CLUBS.cmp_with(two_of_diamonds, RankOrder::AcesHigh, TieBreak::Draw),
+            Ordering::Greater
+        );
+        assert_eq!(
+            ACE_OF_CLUBS.cmp_with(two_of_diamonds, RankOrder::AcesLow, TieBreak::Draw),
+            Ordering::Less
+        );
+        assert_eq!(
+            ACE_OF_CLUBS.cmp_with(ACE_OF_SPADES, RankOrder::AcesLow, TieBreak::Draw),
+            Ordering::Equal
+        );
+        // Nothing else moves.
+        for rank in Rank::ALL.into_iter().filter(|&rank| rank != Rank::Ace) {
+            for other in Rank::ALL.into_iter().filter(|&rank| rank != Rank::Ace) {
+                assert_eq!(RankOrder::AcesLow.cmp(rank, other), rank.cmp(&other));
+            }
+            assert_eq!(RankOrder::AcesLow.cmp(Rank::Ace, rank), Ordering::Less);
+        }
+    }
+}
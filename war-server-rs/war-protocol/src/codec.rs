@@ -0,0 +1,110 @@
+use bytes::BytesMut;
+use tokio_util::codec::{Decoder, Encoder};
+
+use crate::{error::*, format::*};
+
+/// Lets the protocol be driven with `Framed` streams instead of reading
+/// messages by hand.
+///
+/// The decoder figures out how long each message is from its tag byte, so it
+/// waits for more bytes when only part of a message has arrived, and it fails
+/// (rather than panics) on tags it doesn't know.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct WarCodec;
+
+impl Decoder for WarCodec {
+    type Item = Message;
+    type Error = MessageReadError;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        Ok(Message::decode_from(src)?)
+    }
+}
+
+impl Encoder<Message> for WarCodec {
+    type Error = std::io::Error;
+
+    fn encode(&mut self, item: Message, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        item.encode_to(dst);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use futures::{SinkExt, StreamExt};
+    use tokio::io::AsyncWriteExt;
+    use tokio_util::codec::{Framed, FramedRead};
+
+    use super::*;
+    use crate::{card::*, hand::*};
+
+    fn encoded(message: &Message) -> Vec<u8> {
+        let (buf, len) = message.encode();
+        buf[..len].to_vec()
+    }
+
+    #[test]
+    fn decode_waits_for_whole_frames() {
+        let hand = Hand::new(std::array::from_fn(|i| Card::try_from(i as u8).unwrap()));
+        let mut bytes = encoded(&Message::GameStart(hand));
+        bytes.extend(encoded(&Message::PlayCard(Card::try_from(3).unwrap())));
+
+        let mut codec = WarCodec;
+        let mut src = BytesMut::new();
+        src.extend_from_slice(&bytes[..20]);
+        assert!(codec.decode(&mut src).unwrap().is_none());
+        assert_eq!(src.len(), 20, "Nothing should be consumed yet.");
+
+        src.extend_from_slice(&bytes[20..28]);
+        let decoded = codec.decode(&mut src).unwrap().unwrap();
+        assert_eq!(encoded(&decoded), encoded(&Message::GameStart(hand)));
+        assert!(codec.decode(&mut src).unwrap().is_none());
+
+        src.extend_from_slice(&bytes[28..]);
+        assert_eq!(
+            codec.decode(&mut src).unwrap(),
+            Some(Message::PlayCard(Card::try_from(3).unwrap()))
+        );
+        assert!(src.is_empty());
+    }
+
+    #[test]
+    fn decode_rejects_unknown_tags() {
+        let mut src = BytesMut::from(&[0xfe, 0][..]);
+        assert!(matches!(
+            WarCodec.decode(&mut src),
+            Err(MessageReadError::Parse(MessageParseError::UnknownTag {
+                tag: 0xfe
+            }))
+        ));
+    }
+
+    #[tokio::test]
+    async fn framed_round_trip() {
+        let (client, mut server) = tokio::io::duplex(64);
+        let mut client = Framed::new(client, WarCodec);
+        client.send(Message::WantGame { version: 1 }).await.unwrap();
+        client
+            .send(Message::PlayResult(RoundResult::Lose))
+            .await
+            .unwrap();
+        // Dribble a PlayCard in a byte at a time from the other side.
+        server.write_all(&[2]).await.unwrap();
+        server.write_all(&[17]).await.unwrap();
+
+        let mut server = FramedRead::new(server, WarCodec);
+        assert_eq!(
+            server.next().await.unwrap().unwrap(),
+            Message::WantGame { version: 1 }
+        );
+        assert_eq!(
+            server.next().await.unwrap().unwrap(),
+            Message::PlayResult(RoundResult::Lose)
+        );
+        assert_eq!(
+            client.next().await.unwrap().unwrap(),
+            Message::PlayCard(Card::try_from(17).unwrap())
+        );
+    }
+}
@@ -0,0 +1,294 @@
+//! Dealing: shuffling a deck into two hands, and checking that two hands are
+//! a fair split of one.
+
+use crate::{card::*, hand::*};
+
+/// What's wrong with a deal.
+#[derive(thiserror::Error, Debug, PartialEq, Eq)]
+pub enum DealError {
+    /// The same card was dealt more times than there are decks it could have
+    /// come from, whether to one player or to both. When the whole shoe is
+    /// dealt, that also means some other card was never dealt at all.
+    #[error("{card} was dealt more times than there are decks", card = .0.short_code())]
+    Duplicate(Card),
+}
+
+/// Checks that `hand` could have come from as few decks as it takes to deal
+/// that many cards: with up to 26 cards, no card is in it twice; with up to 52,
+/// no card is in it three times; and so on. Meant for clients, to check what
+/// the server dealt them.
+///
+/// Every [`Card`] is one of the 52 by construction, so there's no need to
+/// check that here.
+pub fn validate_hand(hand: &Hand) -> Result<(), DealError> {
+    validate_cards(hand.cards(), hand.len().div_ceil(HAND_SIZE))
+}
+
+/// Checks that `player_one` and `player_two` split the shoe between them:
+/// every card was dealt to one of them at most as many times as there are
+/// decks in the shoe. For a single deck, that's exactly once.
+pub fn validate_deal(player_one: &Hand, player_two: &Hand) -> Result<(), DealError> {
+    let decks = (player_one.len() + player_two.len()).div_ceil(NUM_CARDS_TOTAL.into());
+    validate_deal_from(decks as u8, player_one, player_two)
+}
+
+/// Like [`validate_deal`], for hands that were dealt from `decks` decks
+/// without using all of them up, which can hold more copies of a card than
+/// their size alone would suggest.
+pub fn validate_deal_from(
+    decks: u8,
+    player_one: &Hand,
+    player_two: &Hand,
+) -> Result<(), DealError> {
+    validate_cards(
+        player_one.cards().iter().chain(player_two.cards()),
+        decks.into(),
+    )
+}
+
+fn validate_cards<'a>(
+    cards: impl IntoIterator<Item = &'a Card>,
+    decks: usize,
+) -> Result<(), DealError> {
+    let mut dealt = [0; NUM_CARDS_TOTAL as usize];
+    for &card in cards {
+        let copies = &mut dealt[usize::from(card.value())];
+        *copies += 1;
+        if *copies > decks {
+            return Err(DealError::Duplicate(card));
+        }
+    }
+    Ok(())
+}
+
+/// Some number of whole decks shuffled together (a shoe, at a casino), ready
+/// to be dealt.
+#[cfg(feature = "rand")]
+#[derive(Debug, Clone)]
+pub struct Deck {
+    cards: Vec<Card>,
+}
+
+#[cfg(feature = "rand")]
+impl Deck {
+    /// A whole deck, shuffled with `rng`. The same seed always gives the same
+    /// order.
+    pub fn shuffled(rng: &mut impl rand::Rng) -> Deck {
+        Deck::shuffled_together(1, rng)
+    }
+
+    /// `decks` whole decks, shuffled together with `rng`.
+    ///
+    /// # Panics
+    ///
+    /// If `decks` is 0, or more than [`MAX_DECKS`].
+    pub fn shuffled_together(decks: u8, rng: &mut impl rand::Rng) -> Deck {
+        assert!(
+            (1..=MAX_DECKS).contains(&decks),
+            "Can't play with {decks} decks."
+        );
+        let mut cards = Card::ALL.repeat(decks.into());
+        rand::seq::SliceRandom::shuffle(&mut cards[..], rng);
+        Deck { cards }
+    }
+
+    /// Splits the deck in half, top half to player one.
+    pub fn deal(self) -> (Hand, Hand) {
+        let half = self.cards.len() / 2;
+        self.deal_hands(half)
+    }
+
+    /// Deals `hand_size` cards off the top to player one, and the next
+    /// `hand_size` to player two, leaving the rest out of the game.
+    ///
+    /// # Panics
+    ///
+    /// If `hand_size` is 0, or there aren't enough cards for two hands that
+    /// big.
+    pub fn deal_hands(self, hand_size: usize) -> (Hand, Hand) {
+        let [player_one, player_two] = self.deal_around(hand_size);
+        (player_one, player_two)
+    }
+
+    /// Deals `hand_size` cards off the top to each of `N` players in turn,
+    /// leaving the rest out of the game.
+    ///
+    /// # Panics
+    ///
+    /// If `hand_size` is 0, or there aren't enough cards for `N` hands that
+    /// big.
+    pub fn deal_around<const N: usize>(self, hand_size: usize) -> [Hand; N] {
+        assert!(
+            hand_size > 0 && hand_size * N <= self.cards.len(),
+            "Can't deal {N} hands of {hand_size} from {} cards.",
+            self.cards.len()
+        );
+        let mut hands = self.cards.chunks_exact(hand_size);
+        core::array::from_fn(|_| {
+            let cards = hands.next().expect("There are enough cards.");
+            Hand::try_from(cards).expect("Hands are never that big.")
+        })
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod test {
+    #[cfg(feature = "rand")]
+    use rand::{SeedableRng, rngs::StdRng};
+    #[cfg(feature = "rand")]
+    use zerocopy::IntoBytes;
+
+    use super::*;
+    use crate::hand::test::*;
+
+    #[cfg(feature = "rand")]
+    #[test]
+    fn a_shuffled_deck_is_a_valid_deal() {
+        for seed in 0..100 {
+            let (first, second) = Deck::shuffled(&mut StdRng::seed_from_u64(seed)).deal();
+            assert_eq!(validate_hand(&first), Ok(()));
+            assert_eq!(validate_hand(&second), Ok(()));
+            assert_eq!(validate_deal(&first, &second), Ok(()));
+            assert_eq!(validate_deal(&second, &first), Ok(()));
+        }
+    }
+
+    /// Checks the deal by counting, rather than with `validate_deal`, so the
+    /// two can't go wrong the same way.
+    #[cfg(feature = "rand")]
+    #[test]
+    fn every_card_is_dealt_exactly_once() {
+        for seed in 0..100 {
+            let (first, second) = Deck::shuffled(&mut StdRng::seed_from_u64(seed)).deal();
+            let mut dealt = [0; NUM_CARDS_TOTAL as usize];
+            for hand in [&first, &second] {
+                let mut in_hand = [0; NUM_CARDS_TOTAL as usize];
+                for card in hand.cards() {
+                    in_hand[usize::from(card.value())] += 1;
+                    dealt[usize::from(card.value())] += 1;
+                }
+                assert!(in_hand.iter().all(|&count| count <= 1), "{hand:?}");
+            }
+            assert_eq!(dealt, [1; NUM_CARDS_TOTAL as usize], "seed {seed}");
+        }
+    }
+
+    #[cfg(feature = "rand")]
+    #[test]
+    fn short_hands_come_off_the_top() {
+        let deck = || Deck::shuffled(&mut StdRng::seed_from_u64(34));
+        let top = deck().cards;
+        let (first, second) = deck().deal_hands(5);
+        assert_eq!(first.cards().as_bytes(), top[..5].as_bytes());
+        assert_eq!(second.cards().as_bytes(), top[5..10].as_bytes());
+        assert_eq!(validate_deal(&first, &second), Ok(()));
+
+        let (first, second) = deck().deal_hands(HAND_SIZE);
+        assert_eq!((first.len(), second.len()), (HAND_SIZE, HAND_SIZE));
+
+        let hands: [Hand; 4] = deck().deal_around(13);
+        for (i, hand) in hands.iter().enumerate() {
+            assert_eq!(
+                hand.cards().as_bytes(),
+                top[13 * i..13 * (i + 1)].as_bytes()
+            );
+        }
+    }
+
+    #[cfg(feature = "rand")]
+    #[test]
+    #[should_panic = "Can't deal 2 hands of 27 from 52 cards."]
+    fn hands_cant_be_bigger_than_half_the_deck() {
+        Deck::shuffled(&mut StdRng::seed_from_u64(34)).deal_hands(HAND_SIZE + 1);
+    }
+
+    #[cfg(feature = "rand")]
+    #[test]
+    fn shuffling_depends_only_on_the_seed() {
+        let values = |seed| {
+            Deck::shuffled(&mut StdRng::seed_from_u64(seed))
+                .cards
+                .as_bytes()
+                .to_vec()
+        };
+        assert_eq!(values(34), values(34));
+        assert_ne!(values(34), values(35));
+        assert_ne!(values(34), Card::ALL.as_bytes());
+
+        let (first, second) = Deck::shuffled(&mut StdRng::seed_from_u64(34)).deal();
+        assert_eq!(first.cards().as_bytes(), &values(34)[..HAND_SIZE]);
+        assert_eq!(second.cards().as_bytes(), &values(34)[HAND_SIZE..]);
+    }
+
+    #[cfg(feature = "rand")]
+    #[test]
+    fn more_decks_allow_more_copies() {
+        for seed in 0..100 {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let (first, second) = Deck::shuffled_together(2, &mut rng).deal();
+            assert_eq!((first.len(), second.len()), (52, 52));
+            assert_eq!(validate_hand(&first), Ok(()));
+            assert_eq!(validate_deal(&first, &second), Ok(()));
+        }
+
+        // A few cards off the top of a shoe can still hold copies.
+        let doubled = [TWO_OF_CLUBS; 3];
+        let (first, second) = (
+            Hand::try_from(&doubled[..1]).unwrap(),
+            Hand::try_from(&doubled[1..]).unwrap(),
+        );
+        assert_eq!(
+            validate_deal(&first, &second),
+            Err(DealError::Duplicate(TWO_OF_CLUBS))
+        );
+        assert_eq!(validate_deal_from(3, &first, &second), Ok(()));
+
+        let doubled = Card::ALL[..HAND_SIZE].repeat(2);
+        let mut hand = Hand::try_from(&doubled[..]).unwrap();
+        assert_eq!(validate_hand(&hand), Ok(()));
+        assert!(hand.take(TWO_OF_CLUBS).is_some());
+        assert!(hand.contains(TWO_OF_CLUBS));
+        assert!(hand.take(TWO_OF_CLUBS).is_some());
+        assert!(!hand.contains(TWO_OF_CLUBS));
+        assert!(hand.take(TWO_OF_CLUBS).is_none());
+        assert_eq!(hand.remaining(), 50);
+
+        let mut tripled = doubled;
+        tripled[HAND_SIZE + 1] = TWO_OF_CLUBS;
+        let hand = Hand::try_from(tripled).unwrap();
+        assert_eq!(
+            validate_hand(&hand),
+            Err(DealError::Duplicate(TWO_OF_CLUBS))
+        );
+    }
+
+    #[test]
+    fn deals_with_repeats_are_invalid() {
+        // The old off-by-one: the ace of spades is never dealt, and the two of
+        // clubs is dealt twice.
+        let mut cards = Card::ALL;
+        cards.rotate_right(1);
+        cards[0] = TWO_OF_CLUBS;
+        let (first, second) = cards.split_at(HAND_SIZE);
+        let first = Hand::new(first.try_into().unwrap());
+        let second = Hand::new(second.try_into().unwrap());
+        assert_eq!(
+            validate_hand(&first),
+            Err(DealError::Duplicate(TWO_OF_CLUBS))
+        );
+        assert_eq!(validate_hand(&second), Ok(()));
+        let error = validate_deal(&first, &second).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "2C was dealt more times than there are decks"
+        );
+
+        // Each hand is fine on its own, but they overlap.
+        let overlapping = low_hand();
+        assert_eq!(validate_hand(&overlapping), Ok(()));
+        assert_eq!(
+            validate_deal(&overlapping, &overlapping),
+            Err(DealError::Duplicate(TWO_OF_CLUBS))
+        );
+    }
+}
@@ -0,0 +1,248 @@
+//! Everything that can go wrong decoding a message, reading one from a
+//! connection, or playing by the protocol.
+
+use crate::{card::*, deck::*, format::*, hand::*, table::TableError};
+
+/// Why some bytes aren't a message, with enough detail to say exactly what
+/// was wrong with them in one line.
+#[derive(Debug, thiserror::Error)]
+pub enum MessageParseError {
+    #[error("Message tag {tag} does not name any WAR message.")]
+    UnknownTag { tag: u8 },
+    /// Not necessarily a problem: when reading from a stream, it just means
+    /// waiting for `needed - got` more bytes.
+    #[error("Message needs {needed} bytes, but only {got} were available.")]
+    Truncated { needed: usize, got: usize },
+    #[error("Message was {expected} bytes long, but {got} bytes were given.")]
+    TrailingBytes { expected: usize, got: usize },
+    #[error("Frame claimed to be {0} bytes long, which is longer than any message.")]
+    FrameTooLong(usize),
+    #[error("Connection started with something that looked like, but wasn't, the v2 magic.")]
+    BadMagic,
+    #[error("Frame's checksum was {got:#04x}, but its bytes add up to {expected:#04x}.")]
+    ChecksumMismatch { expected: u8, got: u8 },
+    /// `offset` is how far into the message (counting the tag) the card is,
+    /// or for JSON, would be if the same message were sent as binary.
+    #[error("Byte {offset} of the message should be a card: {source}.")]
+    InvalidCard {
+        offset: usize,
+        source: CardValueTooBig,
+    },
+    #[error("Round result was {value}, the maximum is 2.")]
+    InvalidRoundResult { value: u8 },
+    #[error("Tried to put {count} cards face down, but it has to be 1 to {MAX_FACE_DOWN}.")]
+    InvalidFaceDownCount { count: u8 },
+    #[error(transparent)]
+    InvalidErrorCode(#[from] InvalidErrorCode),
+    #[error(transparent)]
+    InvalidPlayerName(#[from] InvalidPlayerName),
+    #[error(transparent)]
+    InvalidHandSize(#[from] InvalidHandSize),
+    #[error(transparent)]
+    InvalidPosition(#[from] InvalidPosition),
+    #[cfg(feature = "json")]
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+}
+
+impl From<InvalidRoundResult> for MessageParseError {
+    fn from(InvalidRoundResult { value }: InvalidRoundResult) -> Self {
+        MessageParseError::InvalidRoundResult { value }
+    }
+}
+
+#[cfg(feature = "std")]
+#[derive(Debug, thiserror::Error)]
+pub enum MessageReadError {
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error(transparent)]
+    Parse(#[from] MessageParseError),
+}
+
+/// Anything that can go wrong in the protocol layer, gathered up so a server
+/// or client can handle them all in one place. Each kind says what it's about
+/// before the details, since these usually end up in a log.
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("Not a card: {0}")]
+    Card(#[from] CardValueTooBig),
+    #[error("Not a card code: {0}")]
+    CardCode(#[from] ParseCardError),
+    #[error("Bad hand: {0}")]
+    Hand(#[from] InvalidHandSize),
+    #[error("Bad deal: {0}")]
+    Deal(#[from] DealError),
+    #[error("Bad player name: {0}")]
+    PlayerName(#[from] InvalidPlayerName),
+    #[error("Bad seat or place: {0}")]
+    Position(#[from] InvalidPosition),
+    #[error("Bad play: {0}")]
+    Table(#[from] TableError),
+    #[error("Couldn't encode a message: {0}")]
+    Encode(#[from] BufferTooSmall),
+    /// Everything wrong with a message except a bad checksum, which gets its
+    /// own variant, since it means the bytes were damaged rather than wrong.
+    #[error("Malformed message: {0}")]
+    Parse(MessageParseError),
+    #[error(
+        "Corrupted frame: its checksum was {got:#04x}, but its bytes add up to {expected:#04x}"
+    )]
+    Checksum { expected: u8, got: u8 },
+    #[cfg(feature = "std")]
+    #[error("Connection failed: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+impl From<MessageParseError> for Error {
+    fn from(error: MessageParseError) -> Self {
+        match error {
+            MessageParseError::ChecksumMismatch { expected, got } => {
+                Error::Checksum { expected, got }
+            }
+            error => Error::Parse(error),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<MessageReadError> for Error {
+    fn from(error: MessageReadError) -> Self {
+        match error {
+            MessageReadError::Io(error) => Error::Io(error),
+            MessageReadError::Parse(error) => error.into(),
+        }
+    }
+}
+
+impl Error {
+    /// What to tell the other side about this, if they're the one who caused
+    /// it. Anything that isn't about a particular card or turn is just
+    /// [`ErrorCode::Malformed`].
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            Error::Card(_)
+            | Error::CardCode(_)
+            | Error::Parse(MessageParseError::InvalidCard { .. })
+            | Error::Table(TableError::NotInHand { .. }) => ErrorCode::InvalidCard,
+            Error::Table(_) => ErrorCode::OutOfTurn,
+            #[cfg(feature = "std")]
+            Error::Io(error) if error.kind() == std::io::ErrorKind::TimedOut => ErrorCode::Timeout,
+            _ => ErrorCode::Malformed,
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error, PartialEq, Eq)]
+#[error("Buffer was {got} bytes long, but {needed} bytes were needed.")]
+pub struct BufferTooSmall {
+    pub needed: usize,
+    pub got: usize,
+}
+
+#[cfg(all(test, feature = "std"))]
+mod test {
+    use super::*;
+
+    #[test]
+    fn every_error_says_what_its_about() {
+        let errors: Vec<(Error, &str)> = vec![
+            (
+                Card::try_from(52).unwrap_err().into(),
+                "Not a card: Card's value was 52, the maximum is 51",
+            ),
+            (
+                "1X".parse::<Card>().unwrap_err().into(),
+                "Not a card code: Card code didn't start with a rank from 2 to 10, or J, Q, K, or A",
+            ),
+            (
+                Hand::try_from(&[][..]).unwrap_err().into(),
+                "Bad hand: A hand can't hold 0 cards, only 1 to 104",
+            ),
+            (
+                DealError::Duplicate(TWO_OF_CLUBS).into(),
+                "Bad deal: 2C was dealt more times than there are decks",
+            ),
+            (
+                PlayerName::try_from("\n").unwrap_err().into(),
+                "Bad player name: Player name had byte 0x0a at 0, but only printable ASCII is allowed",
+            ),
+            (
+                Position::new(0, 2).unwrap_err().into(),
+                "Bad seat or place: Can't be 0 of 2: tables seat 2 to 4, counting from 1",
+            ),
+            (
+                TableError::AlreadyPlayed { seat: 1 }.into(),
+                "Bad play: Seat 1 already played this round.",
+            ),
+            (
+                Message::Forfeit.encode_into(&mut []).unwrap_err().into(),
+                "Couldn't encode a message: Buffer was 0 bytes long, but 1 bytes were needed.",
+            ),
+            (
+                MessageParseError::UnknownTag { tag: 0xfe }.into(),
+                "Malformed message: Message tag 254 does not name any WAR message.",
+            ),
+            (
+                MessageParseError::ChecksumMismatch {
+                    expected: 0x12,
+                    got: 0x34,
+                }
+                .into(),
+                "Corrupted frame: its checksum was 0x34, but its bytes add up to 0x12",
+            ),
+            (
+                std::io::Error::new(std::io::ErrorKind::TimedOut, "too slow").into(),
+                "Connection failed: too slow",
+            ),
+        ];
+        for (error, display) in errors {
+            assert_eq!(error.to_string(), display);
+        }
+    }
+
+    #[test]
+    fn errors_map_to_codes() {
+        let code = |error: Error| error.code();
+        assert_eq!(
+            code(Card::try_from(52).unwrap_err().into()),
+            ErrorCode::InvalidCard
+        );
+        assert_eq!(
+            code(Message::decode(&[PLAY_CARD, 52]).unwrap_err().into()),
+            ErrorCode::InvalidCard
+        );
+        let not_in_hand = TableError::NotInHand {
+            seat: 0,
+            card: TWO_OF_CLUBS,
+        };
+        assert_eq!(code(not_in_hand.into()), ErrorCode::InvalidCard);
+        assert_eq!(code(TableError::GameOver.into()), ErrorCode::OutOfTurn);
+        assert_eq!(
+            code(Message::decode(&[0xfe]).unwrap_err().into()),
+            ErrorCode::Malformed
+        );
+        let timeout = std::io::Error::from(std::io::ErrorKind::TimedOut);
+        assert_eq!(
+            code(MessageReadError::from(timeout).into()),
+            ErrorCode::Timeout
+        );
+        let hung_up = std::io::Error::from(std::io::ErrorKind::UnexpectedEof);
+        assert_eq!(code(hung_up.into()), ErrorCode::Malformed);
+
+        // Checksums are told apart from everything else that's malformed.
+        let mismatch = MessageParseError::ChecksumMismatch {
+            expected: 0x12,
+            got: 0x34,
+        };
+        let error = Error::from(MessageReadError::from(mismatch));
+        assert!(matches!(
+            error,
+            Error::Checksum {
+                expected: 0x12,
+                got: 0x34
+            }
+        ));
+        assert_eq!(error.code(), ErrorCode::Malformed);
+    }
+}
@@ -0,0 +1,687 @@
+//! How messages are framed on a connection: v1's bare messages, v2's length
+//! prefixes and checksums, batches of frames sent together, and JSON lines.
+
+#[cfg(feature = "tokio")]
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+use zerocopy::IntoBytes;
+
+use crate::{error::*, format::*};
+
+/// What a v2 client sends before anything else, and what a v2 server sends
+/// back to agree to it. v1 clients always open with WantGame or JoinLobby,
+/// whose tags are nowhere near `b'W'`, so the first byte alone tells the two
+/// apart.
+pub const V2_MAGIC: [u8; 4] = *b"WAR2";
+
+/// The size of the length prefix in front of every v2 message.
+pub const V2_LEN_PREFIX_LEN: usize = size_of::<u16>();
+
+/// The longest any v2 frame can be, length prefix included.
+pub const MAX_V2_FRAME_LEN: usize = V2_LEN_PREFIX_LEN + MAX_MESSAGE_SIZE;
+
+/// What a client sends before anything else to ask for v2 framing with a
+/// checksum after every frame, and what the server sends back to agree to it.
+/// It only differs from [`V2_MAGIC`] in its last byte.
+pub const CHECKED_MAGIC: [u8; 4] = *b"WARC";
+
+/// The size of the checksum after every checked frame.
+pub const CHECKSUM_LEN: usize = size_of::<u8>();
+
+/// The longest any frame can be, in any [`WireVersion`].
+pub const MAX_FRAME_LEN: usize = MAX_V2_FRAME_LEN + CHECKSUM_LEN;
+
+/// The CRC-8 of `bytes`, with polynomial 0x07 and nothing fancy (the variant
+/// also known as CRC-8/SMBUS). That's enough to catch any single flipped bit,
+/// and most bursts of them, which is all a lossy link in a lab is going to
+/// throw at it.
+pub const fn crc8(bytes: &[u8]) -> u8 {
+    crc8_continue(0, bytes)
+}
+
+/// The [`crc8`] of whatever came before `bytes`, given that its CRC was `crc`,
+/// followed by `bytes`.
+const fn crc8_continue(mut crc: u8, bytes: &[u8]) -> u8 {
+    let mut i = 0;
+    while i < bytes.len() {
+        crc ^= bytes[i];
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 0x80 != 0 {
+                (crc << 1) ^ 0x07
+            } else {
+                crc << 1
+            };
+            bit += 1;
+        }
+        i += 1;
+    }
+    crc
+}
+
+/// How messages are framed on a connection. Which one is in use is decided
+/// once per connection, by how the client opens it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum WireVersion {
+    /// Messages back to back, with each one's length implied by its tag.
+    #[default]
+    V1,
+    /// Every message is preceded by its length (tag included) as a big-endian
+    /// `u16`: `[len: u16][tag][payload]`. The connection starts with
+    /// [`V2_MAGIC`] in each direction.
+    V2,
+    /// Like v2, but every frame is followed by the [`crc8`] of the whole frame
+    /// (length prefix included): `[len: u16][tag][payload][crc: u8]`. The
+    /// connection starts with [`CHECKED_MAGIC`] in each direction.
+    V2Checked,
+}
+
+impl WireVersion {
+    /// What each side sends before anything else to agree on this version, if
+    /// anything.
+    pub const fn magic(self) -> Option<[u8; 4]> {
+        match self {
+            WireVersion::V1 => None,
+            WireVersion::V2 => Some(V2_MAGIC),
+            WireVersion::V2Checked => Some(CHECKED_MAGIC),
+        }
+    }
+
+    /// Frames `message` for this version, returning the frame along with how
+    /// many of its bytes are actually part of it.
+    pub fn encode(self, message: &Message) -> ([u8; MAX_FRAME_LEN], usize) {
+        let mut frame = [0; MAX_FRAME_LEN];
+        let len = self
+            .encode_into(message, &mut frame)
+            .expect("Every frame fits in MAX_FRAME_LEN bytes.");
+        (frame, len)
+    }
+
+    /// Frames `message` for this version at the start of `buf`, returning how
+    /// many bytes the frame took. Like [`Message::encode_into`], this never
+    /// allocates, and leaves `buf` untouched if it's too short.
+    pub fn encode_into<'a>(
+        self,
+        message: impl Into<MessageRef<'a>>,
+        buf: &mut [u8],
+    ) -> Result<usize, BufferTooSmall> {
+        let message = message.into();
+        let len = message.wire_len();
+        let needed = match self {
+            WireVersion::V1 => len,
+            WireVersion::V2 => V2_LEN_PREFIX_LEN + len,
+            WireVersion::V2Checked => V2_LEN_PREFIX_LEN + len + CHECKSUM_LEN,
+        };
+        let Some(frame) = buf.get_mut(..needed) else {
+            return Err(BufferTooSmall {
+                needed,
+                got: buf.len(),
+            });
+        };
+        if self == WireVersion::V1 {
+            return message.encode_into(frame);
+        }
+        let prefix = u16::try_from(len).expect("Messages are much shorter than u16::MAX.");
+        frame[..V2_LEN_PREFIX_LEN].copy_from_slice(&prefix.to_be_bytes());
+        message.encode_into(&mut frame[V2_LEN_PREFIX_LEN..])?;
+        let len = V2_LEN_PREFIX_LEN + len;
+        if self == WireVersion::V2Checked {
+            frame[len] = crc8(&frame[..len]);
+        }
+        Ok(needed)
+    }
+
+    /// Frames `message` for this version, like [`WireVersion::encode_into`],
+    /// but leaves a GameStart's cards where they are in its hand. Returns the
+    /// frame in three parts, to be sent one after the other: everything before
+    /// the cards (encoded into `buf`), the cards, and everything after them
+    /// (just the checksum, if there is one). Anything but a GameStart is all
+    /// in the first part.
+    pub fn encode_split<'a: 'buf, 'buf>(
+        self,
+        message: impl Into<MessageRef<'a>>,
+        buf: &'buf mut [u8; MAX_FRAME_LEN],
+    ) -> [&'buf [u8]; 3] {
+        let message = message.into();
+        let MessageRef::GameStart(hand) = message else {
+            let len = self
+                .encode_into(message, buf)
+                .expect("Every frame fits in MAX_FRAME_LEN bytes.");
+            return [&buf[..len], &[], &[]];
+        };
+        let cards = hand.cards().as_bytes();
+        let prefix_len = match self {
+            WireVersion::V1 => 0,
+            WireVersion::V2 | WireVersion::V2Checked => V2_LEN_PREFIX_LEN,
+        };
+        let len = game_start_len(hand);
+        let (head, tail) = buf.split_at_mut(prefix_len + len - cards.len());
+        if prefix_len != 0 {
+            let prefix = u16::try_from(len).expect("Messages are much shorter than u16::MAX.");
+            head[..prefix_len].copy_from_slice(&prefix.to_be_bytes());
+        }
+        put_game_start_header(hand, &mut head[prefix_len..]);
+        let tail = match self {
+            WireVersion::V2Checked => {
+                tail[0] = crc8_continue(crc8(head), cards);
+                &tail[..CHECKSUM_LEN]
+            }
+            WireVersion::V1 | WireVersion::V2 => &tail[..0],
+        };
+        [head, cards, tail]
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl WireVersion {
+    pub async fn read<R: AsyncRead + Unpin>(
+        self,
+        reader: &mut R,
+    ) -> Result<Message, MessageReadError> {
+        match self {
+            WireVersion::V1 => Message::read_from(reader).await,
+            WireVersion::V2 | WireVersion::V2Checked => {
+                let mut frame = [0; MAX_FRAME_LEN];
+                let (prefix, rest) = frame.split_at_mut(V2_LEN_PREFIX_LEN);
+                reader.read_exact(prefix).await?;
+                let len = usize::from(u16::from_be_bytes([prefix[0], prefix[1]]));
+                if len > MAX_MESSAGE_SIZE {
+                    return Err(MessageParseError::FrameTooLong(len).into());
+                }
+                if self == WireVersion::V2 {
+                    reader.read_exact(&mut rest[..len]).await?;
+                    return Ok(Message::try_from(&rest[..len])?);
+                }
+                reader.read_exact(&mut rest[..len + CHECKSUM_LEN]).await?;
+                let end = V2_LEN_PREFIX_LEN + len;
+                let (expected, got) = (crc8(&frame[..end]), frame[end]);
+                if expected != got {
+                    return Err(MessageParseError::ChecksumMismatch { expected, got }.into());
+                }
+                Ok(Message::try_from(&frame[V2_LEN_PREFIX_LEN..end])?)
+            }
+        }
+    }
+
+    pub async fn write<W: AsyncWrite + Unpin>(
+        self,
+        message: &Message,
+        writer: &mut W,
+    ) -> std::io::Result<()> {
+        match self {
+            WireVersion::V1 => message.write_to(writer).await,
+            WireVersion::V2 | WireVersion::V2Checked => {
+                let (frame, len) = self.encode(message);
+                writer.write_all(&frame[..len]).await
+            }
+        }
+    }
+
+    /// Reads the first message a client sends, working out from how it starts
+    /// which version the client is speaking. For v2 (checked or not), this
+    /// consumes the magic too, but doesn't send it back: that's up to the
+    /// caller.
+    pub async fn read_opening<R: AsyncRead + Unpin>(
+        reader: &mut R,
+    ) -> Result<(WireVersion, Message), MessageReadError> {
+        let first = reader.read_u8().await?;
+        if first != V2_MAGIC[0] {
+            return Ok((
+                WireVersion::V1,
+                Message::read_after_tag(first, reader).await?,
+            ));
+        }
+        let mut rest = [0; V2_MAGIC.len() - 1];
+        reader.read_exact(&mut rest).await?;
+        let wire = if rest == V2_MAGIC[1..] {
+            WireVersion::V2
+        } else if rest == CHECKED_MAGIC[1..] {
+            WireVersion::V2Checked
+        } else {
+            return Err(MessageParseError::BadMagic.into());
+        };
+        Ok((wire, wire.read(reader).await?))
+    }
+}
+
+/// What a whole connection speaks, picked by the server for every connection
+/// it accepts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "clap", derive(clap::ValueEnum))]
+pub enum WireFormat {
+    /// The bytes each [`Message`] is encoded as, framed however the client
+    /// asks for with its [`WireVersion`].
+    #[default]
+    Binary,
+    /// One JSON object per line, like `{"type":"play_card","card":17}`, for
+    /// poking at the server by hand. The same messages, checked the same way.
+    #[cfg(feature = "json")]
+    Json,
+}
+
+/// The longest line a JSON client can send, newline included. A GameStart is
+/// the longest message, at well under this even with [`MAX_DECKS`](crate::MAX_DECKS) decks.
+#[cfg(feature = "json")]
+pub const MAX_JSON_LINE_LEN: usize = 512;
+
+#[cfg(feature = "tokio")]
+impl WireFormat {
+    /// Reads the first message a client sends. For binary clients, this works
+    /// out which [`WireVersion`] they're speaking, just like
+    /// [`WireVersion::read_opening`]; JSON clients have no such choice.
+    pub async fn read_opening<R: AsyncRead + Unpin>(
+        self,
+        reader: &mut R,
+    ) -> Result<(WireVersion, Message), MessageReadError> {
+        match self {
+            WireFormat::Binary => WireVersion::read_opening(reader).await,
+            #[cfg(feature = "json")]
+            WireFormat::Json => Ok((WireVersion::default(), read_json(reader).await?)),
+        }
+    }
+
+    pub async fn read<R: AsyncRead + Unpin>(
+        self,
+        wire: WireVersion,
+        reader: &mut R,
+    ) -> Result<Message, MessageReadError> {
+        match self {
+            WireFormat::Binary => wire.read(reader).await,
+            #[cfg(feature = "json")]
+            WireFormat::Json => read_json(reader).await,
+        }
+    }
+
+    pub async fn write<W: AsyncWrite + Unpin>(
+        self,
+        wire: WireVersion,
+        message: &Message,
+        writer: &mut W,
+    ) -> std::io::Result<()> {
+        match self {
+            WireFormat::Binary => wire.write(message, writer).await,
+            #[cfg(feature = "json")]
+            WireFormat::Json => {
+                let mut line = message.to_json();
+                line.push('\n');
+                writer.write_all(line.as_bytes()).await
+            }
+        }
+    }
+}
+
+/// Reads one line of JSON and parses the message in it. This goes a byte at a
+/// time, since nothing buffers the connection, and reading past the newline
+/// would lose whatever came after it.
+#[cfg(all(feature = "tokio", feature = "json"))]
+async fn read_json<R: AsyncRead + Unpin>(reader: &mut R) -> Result<Message, MessageReadError> {
+    let mut line = Vec::new();
+    loop {
+        let byte = reader.read_u8().await?;
+        if byte == b'\n' {
+            return Ok(Message::from_json(&line)?);
+        }
+        if line.len() + 1 == MAX_JSON_LINE_LEN {
+            return Err(MessageParseError::FrameTooLong(line.len() + 1).into());
+        }
+        line.push(byte);
+    }
+}
+
+/// Builds up several messages back to back, so they can go out in one write
+/// (and so, usually, one TCP segment) instead of one write each.
+///
+/// There's room for `CAPACITY` bytes, and nothing is ever allocated. By
+/// default, that's enough for any two messages.
+pub struct MessageBuffer<const CAPACITY: usize = { 2 * MAX_MESSAGE_SIZE }> {
+    buf: [u8; CAPACITY],
+    len: usize,
+}
+
+impl<const CAPACITY: usize> MessageBuffer<CAPACITY> {
+    pub const fn new() -> Self {
+        MessageBuffer {
+            buf: [0; CAPACITY],
+            len: 0,
+        }
+    }
+
+    /// Adds `message` after whatever's already here, or leaves everything as
+    /// it was if there isn't room for it.
+    pub fn push(&mut self, message: &Message) -> Result<(), BufferTooSmall> {
+        self.push_frame(WireVersion::V1, message)
+    }
+
+    /// Like [`MessageBuffer::push`], but frames `message` for `wire`, for
+    /// connections that aren't speaking v1.
+    pub fn push_frame<'a>(
+        &mut self,
+        wire: WireVersion,
+        message: impl Into<MessageRef<'a>>,
+    ) -> Result<(), BufferTooSmall> {
+        let len = wire
+            .encode_into(message, &mut self.buf[self.len..])
+            .map_err(|BufferTooSmall { needed, .. }| BufferTooSmall {
+                needed: self.len + needed,
+                got: CAPACITY,
+            })?;
+        self.len += len;
+        Ok(())
+    }
+
+    /// Everything pushed since the last [`MessageBuffer::clear`].
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.buf[..self.len]
+    }
+
+    /// Empties the buffer, so it can be reused for the next batch.
+    pub fn clear(&mut self) {
+        self.len = 0;
+    }
+}
+
+impl<const CAPACITY: usize> Default for MessageBuffer<CAPACITY> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod test {
+    use super::*;
+    use crate::{card::*, format::test::*, hand::*};
+
+    #[test]
+    fn split_frames_are_the_same_frames() {
+        let full = Hand::new(std::array::from_fn(|i| {
+            Card::try_from(i as u8 * 2 + 1).unwrap()
+        }));
+        let short = Hand::try_from(&full.cards()[..3]).unwrap();
+        for wire in [WireVersion::V1, WireVersion::V2, WireVersion::V2Checked] {
+            for hand in [full, short] {
+                let mut buf = [0; MAX_FRAME_LEN];
+                let [head, cards, tail] = wire.encode_split(MessageRef::GameStart(&hand), &mut buf);
+                assert_eq!(cards, hand.cards().as_bytes());
+                let (frame, len) = wire.encode(&Message::GameStart(hand));
+                assert_eq!([head, cards, tail].concat(), frame[..len]);
+            }
+            for message in all_variants() {
+                let mut buf = [0; MAX_FRAME_LEN];
+                let parts = wire.encode_split(&message, &mut buf);
+                let (frame, len) = wire.encode(&message);
+                assert_eq!(parts.concat(), frame[..len]);
+            }
+        }
+    }
+
+    #[test]
+    fn v2_frames() {
+        let (frame, len) = WireVersion::V2.encode(&Message::PlayCard(Card::try_from(9).unwrap()));
+        assert_eq!(frame[..len], [0, 2, PLAY_CARD, 9]);
+        let (frame, len) = WireVersion::V2.encode(&all_variants()[1]);
+        assert_eq!(len, 2 + Message::GAME_START_LEN);
+        assert_eq!(frame[..2], [0, 27]);
+        let (frame, len) = WireVersion::V1.encode(&Message::WantGame { version: 0 });
+        assert_eq!(frame[..len], [0, 0]);
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn v2_round_trips() {
+        let (mut client, mut server) = tokio::io::duplex(256);
+        for message in all_variants() {
+            WireVersion::V2.write(&message, &mut client).await.unwrap();
+        }
+        for message in all_variants() {
+            let read = WireVersion::V2.read(&mut server).await.unwrap();
+            assert_eq!(encoded(&read), encoded(&message));
+        }
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn v2_frame_length_must_match_message() {
+        let (mut client, mut server) = tokio::io::duplex(64);
+        client.write_all(&[0, 3, PLAY_CARD, 9, 9]).await.unwrap();
+        assert!(matches!(
+            WireVersion::V2.read(&mut server).await,
+            Err(MessageReadError::Parse(
+                MessageParseError::TrailingBytes { .. }
+            ))
+        ));
+        let too_long = MAX_MESSAGE_SIZE as u16 + 1;
+        client.write_all(&too_long.to_be_bytes()).await.unwrap();
+        assert!(matches!(
+            WireVersion::V2.read(&mut server).await,
+            Err(MessageReadError::Parse(MessageParseError::FrameTooLong(len))) if len == MAX_MESSAGE_SIZE + 1
+        ));
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn huge_frames_are_rejected_right_away() {
+        for wire in [WireVersion::V2, WireVersion::V2Checked] {
+            let (mut client, mut server) = tokio::io::duplex(64);
+            client.write_all(&u16::MAX.to_be_bytes()).await.unwrap();
+            // Nothing comes after the length, so this would hang if the reader
+            // went looking for the rest of the frame.
+            let read =
+                tokio::time::timeout(std::time::Duration::from_secs(1), wire.read(&mut server))
+                    .await
+                    .expect("The frame should be rejected from its length alone.");
+            assert!(matches!(
+                read,
+                Err(MessageReadError::Parse(MessageParseError::FrameTooLong(
+                    65535
+                )))
+            ));
+        }
+    }
+
+    #[test]
+    fn crc8_check_value() {
+        assert_eq!(crc8(b""), 0);
+        assert_eq!(crc8(b"123456789"), 0xf4);
+        assert_eq!(crc8_continue(crc8(b"1234"), b"56789"), 0xf4);
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn checked_frames_round_trip() {
+        let (frame, len) = WireVersion::V2Checked.encode(&Message::Forfeit);
+        assert_eq!(frame[..len], [0, 1, FORFEIT, crc8(&[0, 1, FORFEIT])]);
+
+        let (mut client, mut server) = tokio::io::duplex(1024);
+        for message in all_variants() {
+            WireVersion::V2Checked
+                .write(&message, &mut client)
+                .await
+                .unwrap();
+        }
+        for message in all_variants() {
+            let read = WireVersion::V2Checked.read(&mut server).await.unwrap();
+            assert_eq!(encoded(&read), encoded(&message));
+        }
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn checked_frames_catch_flipped_bits() {
+        let game_start = &all_variants()[1];
+        let (mut frame, len) = WireVersion::V2Checked.encode(game_start);
+        // Still a perfectly good card, just not the one that was dealt.
+        frame[10] ^= 1;
+        let (mut client, mut server) = tokio::io::duplex(64);
+        client.write_all(&frame[..len]).await.unwrap();
+        assert!(matches!(
+            WireVersion::V2Checked.read(&mut server).await,
+            Err(MessageReadError::Parse(
+                MessageParseError::ChecksumMismatch { .. }
+            ))
+        ));
+        // Without the checksum, nothing would've noticed.
+        client
+            .write_all(&frame[..len - CHECKSUM_LEN])
+            .await
+            .unwrap();
+        let Message::GameStart(corrupted) = WireVersion::V2.read(&mut server).await.unwrap() else {
+            panic!("Expected GameStart.");
+        };
+        assert_ne!(encoded(&Message::GameStart(corrupted)), encoded(game_start));
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn opening_detects_version() {
+        let (mut client, mut server) = tokio::io::duplex(64);
+        Message::WantGame { version: 0 }
+            .write_to(&mut client)
+            .await
+            .unwrap();
+        let (version, message) = WireVersion::read_opening(&mut server).await.unwrap();
+        assert_eq!(
+            (version, message),
+            (WireVersion::V1, Message::WantGame { version: 0 })
+        );
+
+        let join = Message::JoinLobby {
+            version: 1,
+            code: *b"WAR2",
+        };
+        join.write_to(&mut client).await.unwrap();
+        let (version, message) = WireVersion::read_opening(&mut server).await.unwrap();
+        assert_eq!((version, message), (WireVersion::V1, join));
+
+        client.write_all(&V2_MAGIC).await.unwrap();
+        WireVersion::V2
+            .write(&Message::WantGame { version: 0 }, &mut client)
+            .await
+            .unwrap();
+        let (version, message) = WireVersion::read_opening(&mut server).await.unwrap();
+        assert_eq!(
+            (version, message),
+            (WireVersion::V2, Message::WantGame { version: 0 })
+        );
+
+        client.write_all(&CHECKED_MAGIC).await.unwrap();
+        WireVersion::V2Checked
+            .write(&Message::WantGame { version: 0 }, &mut client)
+            .await
+            .unwrap();
+        let (version, message) = WireVersion::read_opening(&mut server).await.unwrap();
+        assert_eq!(
+            (version, message),
+            (WireVersion::V2Checked, Message::WantGame { version: 0 })
+        );
+
+        client.write_all(b"WAR1").await.unwrap();
+        assert!(matches!(
+            WireVersion::read_opening(&mut server).await,
+            Err(MessageReadError::Parse(MessageParseError::BadMagic))
+        ));
+    }
+
+    #[test]
+    fn message_buffer_coalesces_messages() {
+        let mut buffer = MessageBuffer::<64>::new();
+        let round = [
+            Message::PlayResult(RoundResult::Win),
+            Message::GameOver {
+                result: RoundResult::Win,
+                my_score: 14,
+                their_score: 12,
+            },
+        ];
+        for message in &round {
+            buffer.push(message).unwrap();
+        }
+        assert_eq!(buffer.as_bytes(), [PLAY_RESULT, 0, GAME_OVER, 0, 14, 12]);
+        let decoded: Vec<_> = decode_all(buffer.as_bytes())
+            .into_iter()
+            .map(Result::unwrap)
+            .collect();
+        assert_eq!(decoded, round);
+
+        buffer.clear();
+        assert!(buffer.as_bytes().is_empty());
+        buffer.push(&Message::Forfeit).unwrap();
+        assert_eq!(buffer.as_bytes(), [FORFEIT]);
+    }
+
+    #[test]
+    fn message_buffer_frames_messages() {
+        let round = [
+            Message::PlayResult(RoundResult::Lose),
+            Message::GameOver {
+                result: RoundResult::Lose,
+                my_score: 12,
+                their_score: 14,
+            },
+        ];
+        for wire in [WireVersion::V1, WireVersion::V2, WireVersion::V2Checked] {
+            let mut buffer = MessageBuffer::<{ 2 * MAX_FRAME_LEN }>::new();
+            let mut expected = Vec::new();
+            for message in &round {
+                buffer.push_frame(wire, message).unwrap();
+                let (frame, len) = wire.encode(message);
+                expected.extend_from_slice(&frame[..len]);
+            }
+            assert_eq!(buffer.as_bytes(), expected);
+        }
+        let mut buffer = MessageBuffer::<4>::new();
+        buffer.push_frame(WireVersion::V2, &round[0]).unwrap();
+        assert_eq!(
+            buffer.push_frame(WireVersion::V2, &round[0]),
+            Err(BufferTooSmall { needed: 8, got: 4 })
+        );
+    }
+
+    #[test]
+    fn message_buffer_checks_capacity() {
+        let mut buffer = MessageBuffer::<28>::new();
+        buffer.push(&all_variants()[1]).unwrap();
+        assert_eq!(
+            buffer.push(&Message::Error(ErrorCode::Timeout)),
+            Err(BufferTooSmall {
+                needed: Message::GAME_START_LEN + Message::ERROR_LEN,
+                got: 28
+            })
+        );
+        assert_eq!(buffer.as_bytes().len(), Message::GAME_START_LEN);
+        buffer.push(&Message::Forfeit).unwrap();
+
+        // The default always has room for any two messages.
+        let mut buffer: MessageBuffer = MessageBuffer::default();
+        let biggest = Message::GameStart(
+            Hand::try_from(&Card::ALL.repeat(MAX_DECKS.into())[..MAX_HAND_SIZE]).unwrap(),
+        );
+        assert_eq!(biggest.wire_len(), MAX_MESSAGE_SIZE);
+        buffer.push(&biggest).unwrap();
+        buffer.push(&biggest).unwrap();
+        assert!(buffer.push(&Message::Forfeit).is_err());
+    }
+
+    #[cfg(all(feature = "tokio", feature = "json"))]
+    #[tokio::test]
+    async fn json_lines_are_read_one_at_a_time() {
+        let (mut client, mut server) = tokio::io::duplex(1024);
+        client
+            .write_all(b"{\"type\":\"want_game\",\"version\":2}\n{\"type\":\"rematch\"}\n")
+            .await
+            .unwrap();
+        let (wire, message) = WireFormat::Json.read_opening(&mut server).await.unwrap();
+        assert_eq!(message, Message::WantGame { version: 2 });
+        assert_eq!(
+            WireFormat::Json.read(wire, &mut server).await.unwrap(),
+            Message::Rematch
+        );
+
+        client.write_all(&[b' '; MAX_JSON_LINE_LEN]).await.unwrap();
+        assert!(matches!(
+            WireFormat::Json.read(wire, &mut server).await,
+            Err(MessageReadError::Parse(MessageParseError::FrameTooLong(
+                MAX_JSON_LINE_LEN
+            )))
+        ));
+    }
+}
@@ -0,0 +1,248 @@
+//! A player's hand: the cards they were dealt, and which of them they still
+//! have.
+
+use crate::card::*;
+
+pub const HAND_SIZE: usize = NUM_CARDS_TOTAL as usize / 2;
+
+/// The most decks a game can be played with.
+pub const MAX_DECKS: u8 = 4;
+
+/// The most cards anyone can be dealt: half of [`MAX_DECKS`] decks.
+pub const MAX_HAND_SIZE: usize = HAND_SIZE * MAX_DECKS as usize;
+
+/// The cards a player was dealt, and which of them they've played so far.
+///
+/// Only the dealt cards go over the wire, so a freshly decoded hand has
+/// nothing taken out of it. Unlike comparing cards, everything here goes by a
+/// card's exact value: the two of clubs and the two of spades are not the same
+/// card to a hand. When playing with more than one deck, a hand can hold the
+/// same card more than once, and each copy has to be taken separately.
+///
+/// The `#[repr(C)]` keeps the cards at the very start, so a GameStart's hand
+/// still comes right after its tag in memory. There's always room for
+/// [`MAX_HAND_SIZE`] cards, so a hand never needs to allocate, and only the
+/// first `len` of them were actually dealt.
+#[repr(C)]
+#[derive(Clone, Copy)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(try_from = "Vec<Card>", into = "Vec<Card>")
+)]
+pub struct Hand {
+    cards: [Card; MAX_HAND_SIZE],
+    len: u8,
+    /// Each card in `cards` until it's taken, and `None` after. No bigger than
+    /// a flag per card, since `Option<Card>` is still a byte.
+    left: [Option<Card>; MAX_HAND_SIZE],
+}
+
+#[derive(thiserror::Error, Debug, PartialEq, Eq)]
+#[error("A hand can't hold {len} cards, only 1 to {max}", max = MAX_HAND_SIZE)]
+pub struct InvalidHandSize {
+    pub(crate) len: usize,
+}
+
+impl Hand {
+    /// A hand holding all of `dealt`: half of a single deck.
+    pub const fn new(dealt: [Card; HAND_SIZE]) -> Hand {
+        let mut cards = [Card::from_value(0); MAX_HAND_SIZE];
+        let mut left = [None; MAX_HAND_SIZE];
+        let mut i = 0;
+        while i < HAND_SIZE {
+            cards[i] = dealt[i];
+            left[i] = Some(dealt[i]);
+            i += 1;
+        }
+        Hand {
+            cards,
+            len: HAND_SIZE as u8,
+            left,
+        }
+    }
+
+    /// How many cards were dealt into this hand, whether or not they've been
+    /// taken since.
+    pub const fn len(&self) -> usize {
+        self.len as usize
+    }
+
+    /// Never true, since every hand is dealt at least one card. Only here
+    /// because there's a `len`.
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Every card that was dealt, in the order it was dealt, whether or not
+    /// it's been taken since.
+    pub fn cards(&self) -> &[Card] {
+        &self.cards[..self.len()]
+    }
+
+    /// Where the first copy of `card` still in this hand is, if there is one.
+    fn position(&self, card: Card) -> Option<usize> {
+        self.left[..self.len()]
+            .iter()
+            .position(|held| held.is_some_and(|held| held.value() == card.value()))
+    }
+
+    /// Whether `card` (exactly that card, not just one of the same rank) is
+    /// still in this hand.
+    pub fn contains(&self, card: Card) -> bool {
+        self.position(card).is_some()
+    }
+
+    /// Takes a copy of `card` out of this hand, or returns `None` if there
+    /// isn't one in it (anymore).
+    pub fn take(&mut self, card: Card) -> Option<Card> {
+        let index = self.position(card)?;
+        self.left[index].take()
+    }
+
+    /// How many cards haven't been taken yet.
+    pub fn remaining(&self) -> usize {
+        self.iter().count()
+    }
+
+    /// The cards that haven't been taken yet, in the order they were dealt.
+    pub fn iter(&self) -> impl Iterator<Item = Card> + '_ {
+        self.left[..self.len()].iter().flatten().copied()
+    }
+}
+
+impl From<[Card; HAND_SIZE]> for Hand {
+    fn from(cards: [Card; HAND_SIZE]) -> Self {
+        Hand::new(cards)
+    }
+}
+
+impl TryFrom<&[Card]> for Hand {
+    type Error = InvalidHandSize;
+
+    fn try_from(dealt: &[Card]) -> Result<Self, Self::Error> {
+        if dealt.is_empty() || dealt.len() > MAX_HAND_SIZE {
+            return Err(InvalidHandSize { len: dealt.len() });
+        }
+        let mut cards = [Card::from_value(0); MAX_HAND_SIZE];
+        let mut left = [None; MAX_HAND_SIZE];
+        cards[..dealt.len()].copy_from_slice(dealt);
+        for (left, &card) in left.iter_mut().zip(dealt) {
+            *left = Some(card);
+        }
+        Ok(Hand {
+            cards,
+            len: dealt.len() as u8,
+            left,
+        })
+    }
+}
+
+#[cfg(feature = "std")]
+impl TryFrom<Vec<Card>> for Hand {
+    type Error = InvalidHandSize;
+
+    fn try_from(dealt: Vec<Card>) -> Result<Self, Self::Error> {
+        Hand::try_from(&dealt[..])
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<Hand> for Vec<Card> {
+    fn from(hand: Hand) -> Self {
+        hand.cards().to_vec()
+    }
+}
+
+/// A hand of any cards, of any size. The fuzzer only ever sees hands fresh off
+/// the wire, so nothing is taken out of it.
+#[cfg(feature = "arbitrary")]
+impl<'a> arbitrary::Arbitrary<'a> for Hand {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        let len = u.int_in_range(1..=MAX_HAND_SIZE)?;
+        let cards = (0..len)
+            .map(|_| u.arbitrary())
+            .collect::<arbitrary::Result<Vec<Card>>>()?;
+        Ok(Hand::try_from(cards).expect("The length is in range."))
+    }
+}
+
+/// Just the cards still in the hand, by short code, like `Hand[2C, 10H, AS]`.
+/// Two hands are equal when they were dealt exactly the same cards, in the
+/// same order, and have had exactly the same ones taken out of them. Nothing
+/// past the dealt cards counts.
+impl PartialEq for Hand {
+    fn eq(&self, other: &Self) -> bool {
+        let len = usize::from(self.len);
+        self.len == other.len
+            && self.cards[..len]
+                .iter()
+                .map(|card| card.value())
+                .eq(other.cards[..len].iter().map(|card| card.value()))
+            && self.left[..len]
+                .iter()
+                .map(|card| card.map(Card::value))
+                .eq(other.left[..len].iter().map(|card| card.map(Card::value)))
+    }
+}
+impl Eq for Hand {}
+
+impl core::fmt::Debug for Hand {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str("Hand")?;
+        f.debug_list()
+            .entries(self.iter().map(Card::short_code))
+            .finish()
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+pub(crate) mod test {
+    use super::*;
+
+    /// The first half of the deck: all the clubs and diamonds.
+    pub(crate) fn low_hand() -> Hand {
+        Hand::new(std::array::from_fn(|i| Card::try_from(i as u8).unwrap()))
+    }
+
+    #[test]
+    fn cards_can_only_be_taken_once() {
+        let mut hand = low_hand();
+        assert_eq!(hand.remaining(), HAND_SIZE);
+        assert!(hand.contains(TWO_OF_DIAMONDS));
+
+        assert_eq!(hand.take(TWO_OF_DIAMONDS).map(Card::value), Some(13));
+        assert!(!hand.contains(TWO_OF_DIAMONDS));
+        assert_eq!(hand.remaining(), HAND_SIZE - 1);
+        assert!(hand.take(TWO_OF_DIAMONDS).is_none());
+        assert_eq!(hand.remaining(), HAND_SIZE - 1);
+
+        assert_eq!(hand.iter().count(), HAND_SIZE - 1);
+        assert!(
+            hand.iter()
+                .all(|card| card.value() != TWO_OF_DIAMONDS.value())
+        );
+        // The dealt cards are still all there for the wire.
+        assert_eq!(hand.cards()[13].value(), TWO_OF_DIAMONDS.value());
+    }
+
+    #[test]
+    fn hands_go_by_exact_cards() {
+        let mut hand = low_hand();
+        // Equal to the king of clubs as far as the game is concerned, but not
+        // the same card.
+        assert_eq!(KING_OF_HEARTS, KING_OF_CLUBS);
+        assert!(hand.contains(KING_OF_CLUBS));
+        assert!(!hand.contains(KING_OF_HEARTS));
+        assert!(hand.take(KING_OF_HEARTS).is_none());
+        assert_eq!(hand.remaining(), HAND_SIZE);
+
+        // So is comparing hands.
+        let mut swapped = hand.cards().to_vec();
+        swapped[11] = KING_OF_HEARTS;
+        assert_ne!(Hand::try_from(swapped.as_slice()).unwrap(), hand);
+        assert_eq!(hand, low_hand());
+        hand.take(KING_OF_CLUBS).unwrap();
+        assert_ne!(hand, low_hand());
+    }
+}
@@ -0,0 +1,114 @@
+//! Every message a War client and server can send each other, and how each
+//! one looks on the wire.
+//!
+//! Encoding and decoding only ever touch byte slices. Reading and writing
+//! whole messages over a connection needs the `tokio` feature, dealing hands
+//! needs `rand`, and the JSON wire format needs `json`. Without the (default)
+//! `std` feature, the crate is `no_std`, for clients on microcontrollers: the
+//! binary format is all there, but JSON isn't.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+mod card;
+#[cfg(feature = "codec")]
+pub mod codec;
+mod deck;
+mod error;
+mod format;
+mod framing;
+mod hand;
+#[cfg(any(all(test, feature = "std"), feature = "test-util"))]
+pub mod strategies;
+mod table;
+
+pub use card::*;
+pub use deck::*;
+pub use error::*;
+pub use format::*;
+pub use framing::*;
+pub use hand::*;
+pub use table::*;
+
+/// Everything here sticks to `core`, so these still build and run with
+/// `--no-default-features`, where the tests in `format` don't.
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn card(value: u8) -> Card {
+        Card::try_from(value).unwrap()
+    }
+
+    fn messages() -> [Message; 4] {
+        [
+            Message::WantGame {
+                version: PROTOCOL_VERSION,
+            },
+            Message::GameStart(Hand::new(core::array::from_fn(|i| card(2 * i as u8)))),
+            Message::PlayCard(card(51)),
+            Message::GameOver {
+                result: RoundResult::Win,
+                my_score: 14,
+                their_score: 12,
+            },
+        ]
+    }
+
+    #[test]
+    fn messages_round_trip() {
+        for message in messages() {
+            let (buf, len) = message.encode();
+            let (decoded, decoded_len) = Message::decode(&buf[..len]).unwrap();
+            assert_eq!(decoded_len, len);
+            assert_eq!(decoded.encode().0[..len], buf[..len]);
+        }
+    }
+
+    #[test]
+    fn frames_end_with_the_message() {
+        for message in messages() {
+            let (buf, len) = message.encode();
+            for wire in [WireVersion::V1, WireVersion::V2] {
+                let (frame, frame_len) = wire.encode(&message);
+                assert!(frame[..frame_len].ends_with(&buf[..len]));
+            }
+            let (frame, frame_len) = WireVersion::V2Checked.encode(&message);
+            let (checked, checksum) = frame[..frame_len].split_at(frame_len - CHECKSUM_LEN);
+            assert!(checked.ends_with(&buf[..len]));
+            assert_eq!(checksum, [crc8(checked)]);
+        }
+    }
+
+    #[test]
+    fn batches_fit_in_a_fixed_buffer() {
+        let mut batch = MessageBuffer::<{ 4 * MAX_MESSAGE_SIZE }>::new();
+        for message in messages() {
+            batch.push(&message).unwrap();
+        }
+        let mut decoded = Message::decode_stream(batch.as_bytes());
+        for message in messages() {
+            let (buf, len) = message.encode();
+            assert_eq!(
+                decoded.next().unwrap().unwrap().encode().0[..len],
+                buf[..len]
+            );
+        }
+        assert!(decoded.next().is_none());
+    }
+
+    #[test]
+    fn decode_errors() {
+        assert!(matches!(
+            Message::decode(&[0xfe]),
+            Err(MessageParseError::UnknownTag { tag: 0xfe })
+        ));
+        assert!(matches!(
+            Message::decode(&[2]),
+            Err(MessageParseError::Truncated { needed: 2, got: 1 })
+        ));
+        assert!(matches!(
+            Message::decode(&[2, 52]),
+            Err(MessageParseError::InvalidCard { offset: 1, .. })
+        ));
+    }
+}
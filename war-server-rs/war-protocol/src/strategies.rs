@@ -0,0 +1,78 @@
+//! proptest strategies that only ever generate valid protocol values, so
+//! properties can be about what the code does with them rather than about
+//! whether they were well-formed to begin with.
+
+use proptest::{array, collection, prelude::*};
+
+use crate::{card::*, format::*, hand::*};
+
+pub fn card() -> impl Strategy<Value = Card> {
+    (0..NUM_CARDS_TOTAL).prop_map(|value| Card::try_from(value).unwrap())
+}
+
+/// Mostly hands of the usual size, but sometimes any other size a hand can
+/// be.
+pub fn hand() -> impl Strategy<Value = Hand> {
+    prop_oneof![
+        3 => array::uniform26(card()).prop_map(Hand::new),
+        1 => collection::vec(card(), 1..=MAX_HAND_SIZE)
+            .prop_map(|cards| Hand::try_from(cards).unwrap()),
+    ]
+}
+
+pub fn round_result() -> impl Strategy<Value = RoundResult> {
+    prop_oneof![
+        Just(RoundResult::Win),
+        Just(RoundResult::Draw),
+        Just(RoundResult::Lose),
+    ]
+}
+
+pub fn error_code() -> impl Strategy<Value = ErrorCode> {
+    (0..=ErrorCode::OpponentGone as u8).prop_map(|code| ErrorCode::try_from(code).unwrap())
+}
+
+pub fn player_name() -> impl Strategy<Value = PlayerName> {
+    "[ -~]{0,16}".prop_map(|name| PlayerName::try_from(name.as_str()).unwrap())
+}
+
+pub fn position() -> impl Strategy<Value = Position> {
+    (MIN_PLAYERS..=MAX_PLAYERS)
+        .prop_flat_map(|players| (1..=players, Just(players)))
+        .prop_map(|(nth, players)| Position::new(nth, players).unwrap())
+}
+
+/// Any message, of any kind, with any valid payload.
+pub fn message() -> impl Strategy<Value = Message> {
+    prop_oneof![
+        any::<u8>().prop_map(|version| Message::WantGame { version }),
+        hand().prop_map(Message::GameStart),
+        card().prop_map(Message::PlayCard),
+        round_result().prop_map(Message::PlayResult),
+        (round_result(), any::<u8>(), any::<u8>()).prop_map(|(result, my_score, their_score)| {
+            Message::GameOver {
+                result,
+                my_score,
+                their_score,
+            }
+        }),
+        error_code().prop_map(Message::Error),
+        Just(Message::Forfeit),
+        Just(Message::Rematch),
+        (any::<u8>(), any::<LobbyCode>())
+            .prop_map(|(version, code)| Message::JoinLobby { version, code }),
+        player_name().prop_map(Message::Hello),
+        (1..=MAX_FACE_DOWN).prop_map(|count| Message::PlayFaceDown { count }),
+        any::<u8>().prop_map(Message::Ping),
+        any::<u8>().prop_map(Message::Pong),
+        (any::<u8>(), any::<u8>(), any::<[u8; BUILD_LEN]>()).prop_map(
+            |(proto_version, flags, build)| Message::ServerInfo {
+                proto_version,
+                flags: Capabilities::from_bits(flags),
+                build,
+            }
+        ),
+        position().prop_map(Message::Seated),
+        position().prop_map(Message::Placed),
+    ]
+}
@@ -0,0 +1,288 @@
+//! Groundwork for games of more than two players: how a game goes at a table
+//! of any size, one PlayCard at a time. The server still only seats two, with
+//! its own session; this is the part of a bigger game that doesn't care about
+//! sockets, so it can be tried out (and tested) before anything deals one.
+
+use core::cmp::Ordering;
+
+use crate::{card::*, format::*, hand::*};
+
+/// One game at a table of `N` players. Seats count from 0 here, and from 1 in
+/// the [`Position`]s sent to clients.
+#[derive(Debug, Clone)]
+pub struct Table<const N: usize> {
+    hands: [Hand; N],
+    /// What each seat has played this round, if anything yet.
+    played: [Option<Card>; N],
+    /// How many rounds each seat has come first in, without a tie.
+    wins: [u8; N],
+    rounds_left: usize,
+    rank_order: RankOrder,
+    tie_break: TieBreak,
+}
+
+#[derive(thiserror::Error, Debug, PartialEq, Eq)]
+pub enum TableError {
+    #[error("There's no seat {seat} at a table of {players}.")]
+    NoSuchSeat { seat: usize, players: usize },
+    #[error("Seat {seat} already played this round.")]
+    AlreadyPlayed { seat: usize },
+    #[error("Seat {seat} doesn't hold {card}.", card = .card.short_code())]
+    NotInHand { seat: usize, card: Card },
+    #[error("Every round was already played.")]
+    GameOver,
+}
+
+impl<const N: usize> Table<N> {
+    /// How many players sit at the table, checked when the table is built.
+    const PLAYERS: u8 = {
+        assert!(
+            MIN_PLAYERS as usize <= N && N <= MAX_PLAYERS as usize,
+            "Tables seat MIN_PLAYERS to MAX_PLAYERS."
+        );
+        N as u8
+    };
+
+    /// A game with `hands` dealt, one to each seat in order.
+    ///
+    /// # Panics
+    ///
+    /// If the hands aren't all the same size.
+    pub fn new(hands: [Hand; N], rank_order: RankOrder, tie_break: TieBreak) -> Table<N> {
+        // Refuses to build for tables that are too big or too small.
+        let _ = Self::PLAYERS;
+        let rounds = hands[0].len();
+        assert!(
+            hands.iter().all(|hand| hand.len() == rounds),
+            "Every seat has to be dealt the same number of cards."
+        );
+        Table {
+            hands,
+            played: [None; N],
+            wins: [0; N],
+            rounds_left: rounds,
+            rank_order,
+            tie_break,
+        }
+    }
+
+    /// Where `seat` sits, as sent in their Seated.
+    pub fn seat(&self, seat: usize) -> Result<Position, TableError> {
+        self.check_seat(seat)?;
+        Ok(Position::new(seat as u8 + 1, Self::PLAYERS).expect("The seat is at the table."))
+    }
+
+    /// What `seat` has left to play.
+    pub fn hand(&self, seat: usize) -> Result<&Hand, TableError> {
+        self.check_seat(seat)?;
+        Ok(&self.hands[seat])
+    }
+
+    pub fn rounds_left(&self) -> usize {
+        self.rounds_left
+    }
+
+    /// How many rounds each seat has come first in, without a tie.
+    pub fn wins(&self) -> [u8; N] {
+        self.wins
+    }
+
+    /// The seats that have yet to play this round, in order. None, once the
+    /// game is over.
+    pub fn waiting_on(&self) -> impl Iterator<Item = usize> + '_ {
+        let over = self.rounds_left == 0;
+        (0..N).filter(move |&seat| !over && self.played[seat].is_none())
+    }
+
+    /// Plays `card` from `seat`'s hand. Once every seat has played this round,
+    /// returns where each of their cards placed, to send in their Placed.
+    pub fn play(&mut self, seat: usize, card: Card) -> Result<Option<[Position; N]>, TableError> {
+        self.check_seat(seat)?;
+        if self.rounds_left == 0 {
+            return Err(TableError::GameOver);
+        }
+        if self.played[seat].is_some() {
+            return Err(TableError::AlreadyPlayed { seat });
+        }
+        let card = self.hands[seat]
+            .take(card)
+            .ok_or(TableError::NotInHand { seat, card })?;
+        self.played[seat] = Some(card);
+        if self.played.iter().any(Option::is_none) {
+            return Ok(None);
+        }
+
+        let cards = self
+            .played
+            .map(|card| card.expect("Every seat has played."));
+        self.played = [None; N];
+        self.rounds_left -= 1;
+        let places = self.place(cards);
+        let mut firsts = (0..N).filter(|&seat| places[seat].nth() == 1);
+        if let (Some(winner), None) = (firsts.next(), firsts.next()) {
+            self.wins[winner] += 1;
+        }
+        Ok(Some(places))
+    }
+
+    /// Where each of `cards` comes among all of them: first for the best,
+    /// with cards that tie sharing a place, and the next place skipped for
+    /// each card it was shared with.
+    fn place(&self, cards: [Card; N]) -> [Position; N] {
+        core::array::from_fn(|seat| {
+            let beaten_by = cards
+                .iter()
+                .filter(|&&other| {
+                    other.cmp_with(cards[seat], self.rank_order, self.tie_break)
+                        == Ordering::Greater
+                })
+                .count();
+            Position::new(beaten_by as u8 + 1, Self::PLAYERS)
+                .expect("Nobody is beaten by everyone.")
+        })
+    }
+
+    fn check_seat(&self, seat: usize) -> Result<(), TableError> {
+        if seat >= N {
+            return Err(TableError::NoSuchSeat { seat, players: N });
+        }
+        Ok(())
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod test {
+    use super::*;
+
+    fn hand(cards: &[Card]) -> Hand {
+        Hand::try_from(cards).unwrap()
+    }
+
+    fn places<const N: usize>(places: [Position; N]) -> [u8; N] {
+        places.map(Position::nth)
+    }
+
+    /// Three seats, two rounds each: the first has a clear winner, and the
+    /// second a tie for first.
+    fn three_seats() -> Table<3> {
+        Table::new(
+            [
+                hand(&[KING_OF_HEARTS, TWO_OF_CLUBS]),
+                hand(&[ACE_OF_SPADES, NINE_OF_DIAMONDS]),
+                hand(&[FIVE_OF_CLUBS, NINE_OF_SPADES]),
+            ],
+            RankOrder::default(),
+            TieBreak::default(),
+        )
+    }
+
+    #[test]
+    fn a_round_waits_for_every_seat() {
+        let mut table = three_seats();
+        assert_eq!(table.waiting_on().collect::<Vec<_>>(), [0, 1, 2]);
+        assert_eq!(table.play(1, ACE_OF_SPADES), Ok(None));
+        assert_eq!(table.play(0, KING_OF_HEARTS), Ok(None));
+        assert_eq!(table.waiting_on().collect::<Vec<_>>(), [2]);
+        let placed = table.play(2, FIVE_OF_CLUBS).unwrap().unwrap();
+        assert_eq!(places(placed), [2, 1, 3]);
+        assert!(placed.iter().all(|place| place.players() == 3));
+        assert_eq!(table.wins(), [0, 1, 0]);
+        assert_eq!(table.rounds_left(), 1);
+        assert_eq!(table.waiting_on().collect::<Vec<_>>(), [0, 1, 2]);
+    }
+
+    #[test]
+    fn ties_share_a_place() {
+        let mut table = three_seats();
+        for (seat, card) in [(0, KING_OF_HEARTS), (1, ACE_OF_SPADES), (2, FIVE_OF_CLUBS)] {
+            table.play(seat, card).unwrap();
+        }
+        table.play(0, TWO_OF_CLUBS).unwrap();
+        table.play(1, NINE_OF_DIAMONDS).unwrap();
+        let placed = table.play(2, NINE_OF_SPADES).unwrap().unwrap();
+        assert_eq!(places(placed), [3, 1, 1]);
+        // Nobody came first alone, so nobody won the round.
+        assert_eq!(table.wins(), [0, 1, 0]);
+        assert_eq!(table.waiting_on().count(), 0);
+        assert_eq!(table.play(0, TWO_OF_CLUBS), Err(TableError::GameOver));
+    }
+
+    #[test]
+    fn suits_break_ties_at_the_table_too() {
+        let mut table = Table::new(
+            [
+                hand(&[NINE_OF_DIAMONDS]),
+                hand(&[NINE_OF_SPADES]),
+                hand(&[NINE_OF_CLUBS]),
+            ],
+            RankOrder::default(),
+            TieBreak::Suits,
+        );
+        table.play(0, NINE_OF_DIAMONDS).unwrap();
+        table.play(1, NINE_OF_SPADES).unwrap();
+        let placed = table.play(2, NINE_OF_CLUBS).unwrap().unwrap();
+        assert_eq!(places(placed), [2, 1, 3]);
+        assert_eq!(table.wins(), [0, 1, 0]);
+    }
+
+    #[test]
+    fn plays_are_checked() {
+        let mut table = three_seats();
+        assert_eq!(
+            table.play(3, TWO_OF_CLUBS),
+            Err(TableError::NoSuchSeat {
+                seat: 3,
+                players: 3
+            })
+        );
+        // Same rank, wrong suit.
+        assert_eq!(
+            table.play(0, KING_OF_SPADES),
+            Err(TableError::NotInHand {
+                seat: 0,
+                card: KING_OF_SPADES
+            })
+        );
+        table.play(0, KING_OF_HEARTS).unwrap();
+        assert_eq!(
+            table.play(0, TWO_OF_CLUBS),
+            Err(TableError::AlreadyPlayed { seat: 0 })
+        );
+        assert_eq!(table.hand(0).unwrap().remaining(), 1);
+    }
+
+    #[test]
+    fn seats_count_from_one_on_the_wire() {
+        let table = three_seats();
+        let seats: Vec<_> = (0..3).map(|seat| table.seat(seat).unwrap()).collect();
+        assert_eq!(seats, [1, 2, 3].map(|nth| Position::new(nth, 3).unwrap()));
+        let (bytes, len) = Message::Seated(seats[2]).encode();
+        assert_eq!(bytes[1..len], [3, 3]);
+    }
+
+    #[cfg(feature = "rand")]
+    #[test]
+    fn four_seats_play_out_a_whole_deck() {
+        use rand::{SeedableRng, rngs::StdRng};
+
+        use crate::deck::Deck;
+
+        let hands: [Hand; 4] = Deck::shuffled(&mut StdRng::seed_from_u64(7)).deal_around(13);
+        let mut table = Table::new(hands, RankOrder::default(), TieBreak::Suits);
+        let mut rounds = 0;
+        while table.rounds_left() > 0 {
+            let mut placed = None;
+            for seat in 0..4 {
+                let card = table.hand(seat).unwrap().iter().next().unwrap();
+                placed = table.play(seat, card).unwrap();
+            }
+            // With suits breaking ties, every place is taken exactly once.
+            let mut placed = places(placed.unwrap());
+            placed.sort();
+            assert_eq!(placed, [1, 2, 3, 4]);
+            rounds += 1;
+        }
+        assert_eq!(rounds, 13);
+        assert_eq!(table.wins().iter().sum::<u8>(), 13);
+    }
+}
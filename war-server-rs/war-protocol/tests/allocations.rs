@@ -0,0 +1,68 @@
+//! Encoding never allocates. This lives in its own test binary, since it
+//! needs its own global allocator to count with.
+
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    cell::Cell,
+};
+
+use war_protocol::*;
+
+/// The system allocator, counting how many times each thread allocates.
+struct Counting;
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.with(|count| count.set(count.get() + 1));
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) }
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: Counting = Counting;
+
+fn allocations() -> usize {
+    ALLOCATIONS.with(Cell::get)
+}
+
+#[test]
+fn encoding_a_whole_game_never_allocates() {
+    let hand = Hand::new(core::array::from_fn(|i| {
+        Card::try_from(i as u8 * 2).unwrap()
+    }));
+    let name = PlayerName::try_from("alice").unwrap();
+    let mut buf = [0; MAX_FRAME_LEN];
+
+    let before = allocations();
+    for wire in [WireVersion::V1, WireVersion::V2, WireVersion::V2Checked] {
+        let opening = [
+            Message::WantGame { version: 2 },
+            Message::Hello(name),
+            Message::GameStart(hand),
+        ];
+        let rounds = hand.iter().flat_map(|card| {
+            [
+                Message::PlayCard(card),
+                Message::PlayResult(RoundResult::Draw),
+            ]
+        });
+        let ending = Message::GameOver {
+            result: RoundResult::Draw,
+            my_score: 0,
+            their_score: 0,
+        };
+        for message in opening.into_iter().chain(rounds).chain([ending]) {
+            let len = wire.encode_into(&message, &mut buf).unwrap();
+            assert!(len > message.wire_len() || wire == WireVersion::V1);
+        }
+    }
+    assert_eq!(allocations() - before, 0);
+}
@@ -0,0 +1,215 @@
+//! Golden wire-format vectors: bytes written by `vectors/generate.py` straight
+//! from the documented format, which this crate has to produce and accept
+//! exactly. Unlike the round-trip tests, these catch the encoder and decoder
+//! drifting the same way at once.
+
+use war_protocol::*;
+
+fn encoded(message: &Message) -> Vec<u8> {
+    let (bytes, len) = message.encode();
+    bytes[..len].to_vec()
+}
+
+/// The generator's fixed shuffle: every 7th card, wrapping around.
+fn deck() -> Vec<Card> {
+    (0..NUM_CARDS_TOTAL as usize)
+        .map(|i| Card::try_from((i * 7 % NUM_CARDS_TOTAL as usize) as u8).unwrap())
+        .collect()
+}
+
+fn hands() -> (Hand, Hand) {
+    let deck = deck();
+    let (one, two) = deck.split_at(HAND_SIZE);
+    (Hand::try_from(one).unwrap(), Hand::try_from(two).unwrap())
+}
+
+fn vectors() -> Vec<(&'static str, &'static [u8], Message)> {
+    vec![
+        (
+            "want_game_legacy",
+            include_bytes!("vectors/want_game_legacy.bin"),
+            Message::WantGame { version: 0 },
+        ),
+        (
+            "want_game_v3",
+            include_bytes!("vectors/want_game_v3.bin"),
+            Message::WantGame { version: 3 },
+        ),
+        (
+            "game_start",
+            include_bytes!("vectors/game_start.bin"),
+            Message::GameStart(hands().0),
+        ),
+        (
+            "long_game_start",
+            include_bytes!("vectors/long_game_start.bin"),
+            Message::GameStart(
+                Hand::try_from(&[ACE_OF_SPADES, TWO_OF_CLUBS, SEVEN_OF_HEARTS][..]).unwrap(),
+            ),
+        ),
+        (
+            "play_card",
+            include_bytes!("vectors/play_card.bin"),
+            Message::PlayCard(QUEEN_OF_HEARTS),
+        ),
+        (
+            "play_result_win",
+            include_bytes!("vectors/play_result_win.bin"),
+            Message::PlayResult(RoundResult::Win),
+        ),
+        (
+            "play_result_draw",
+            include_bytes!("vectors/play_result_draw.bin"),
+            Message::PlayResult(RoundResult::Draw),
+        ),
+        (
+            "play_result_lose",
+            include_bytes!("vectors/play_result_lose.bin"),
+            Message::PlayResult(RoundResult::Lose),
+        ),
+        (
+            "game_over",
+            include_bytes!("vectors/game_over.bin"),
+            Message::GameOver {
+                result: RoundResult::Lose,
+                my_score: 9,
+                their_score: 17,
+            },
+        ),
+        (
+            "error",
+            include_bytes!("vectors/error.bin"),
+            Message::Error(ErrorCode::OutOfTurn),
+        ),
+        (
+            "forfeit",
+            include_bytes!("vectors/forfeit.bin"),
+            Message::Forfeit,
+        ),
+        (
+            "rematch",
+            include_bytes!("vectors/rematch.bin"),
+            Message::Rematch,
+        ),
+        (
+            "join_lobby",
+            include_bytes!("vectors/join_lobby.bin"),
+            Message::JoinLobby {
+                version: 2,
+                code: *b"WAR\n",
+            },
+        ),
+        (
+            "hello",
+            include_bytes!("vectors/hello.bin"),
+            Message::Hello(PlayerName::try_from("alice").unwrap()),
+        ),
+        (
+            "play_face_down",
+            include_bytes!("vectors/play_face_down.bin"),
+            Message::PlayFaceDown { count: 3 },
+        ),
+        (
+            "ping",
+            include_bytes!("vectors/ping.bin"),
+            Message::Ping(0x2a),
+        ),
+        (
+            "pong",
+            include_bytes!("vectors/pong.bin"),
+            Message::Pong(0x2a),
+        ),
+        (
+            "server_info",
+            include_bytes!("vectors/server_info.bin"),
+            Message::ServerInfo {
+                proto_version: 5,
+                flags: Capabilities::GAME_OVER | Capabilities::LOBBIES,
+                build: *b"0.1.0\0\0\0",
+            },
+        ),
+        (
+            "seated",
+            include_bytes!("vectors/seated.bin"),
+            Message::Seated(Position::new(2, 3).unwrap()),
+        ),
+        (
+            "placed",
+            include_bytes!("vectors/placed.bin"),
+            Message::Placed(Position::new(3, 4).unwrap()),
+        ),
+    ]
+}
+
+#[test]
+fn every_message_encodes_to_its_vector() {
+    for (name, bytes, message) in vectors() {
+        assert_eq!(encoded(&message), bytes, "{name}");
+    }
+}
+
+#[test]
+fn every_vector_decodes_to_its_message() {
+    for (name, bytes, message) in vectors() {
+        let (decoded, len) =
+            Message::decode(bytes).unwrap_or_else(|error| panic!("{name}: {error}"));
+        assert_eq!(len, bytes.len(), "{name}");
+        assert_eq!(decoded, message, "{name}");
+        // Cards compare by rank alone, so check the suits made it too.
+        assert_eq!(encoded(&decoded), bytes, "{name}");
+    }
+}
+
+#[test]
+fn every_tag_has_a_vector() {
+    let mut tags: Vec<u8> = vectors().iter().map(|(_, bytes, _)| bytes[0]).collect();
+    tags.sort();
+    tags.dedup();
+    let known: Vec<u8> = (0..=u8::MAX)
+        .filter(|&tag| {
+            !matches!(
+                Message::decode(&[tag]),
+                Err(MessageParseError::UnknownTag { .. })
+            )
+        })
+        .collect();
+    assert_eq!(tags, known);
+}
+
+/// One scripted legacy game, both players, both directions: each sends a
+/// WantGame and then their hand in the order it was dealt, and gets back
+/// their hand and how each round went.
+#[test]
+fn a_whole_game_matches_its_transcript() {
+    let (one, two) = hands();
+    let transcripts = [
+        (
+            &one,
+            &two,
+            &include_bytes!("vectors/player_one_sent.bin")[..],
+            &include_bytes!("vectors/player_one_received.bin")[..],
+        ),
+        (
+            &two,
+            &one,
+            include_bytes!("vectors/player_two_sent.bin"),
+            include_bytes!("vectors/player_two_received.bin"),
+        ),
+    ];
+    for (mine, theirs, sent, received) in transcripts {
+        let mut expected_sent = vec![Message::WantGame { version: 0 }];
+        let mut expected_received = vec![Message::GameStart(*mine)];
+        for (my_card, their_card) in mine.iter().zip(theirs.iter()) {
+            expected_sent.push(Message::PlayCard(my_card));
+            expected_received.push(Message::PlayResult(play_round(my_card, their_card).0));
+        }
+
+        for (bytes, expected) in [(sent, expected_sent), (received, expected_received)] {
+            let decoded: Vec<Message> = Message::decode_stream(bytes)
+                .collect::<Result<_, _>>()
+                .unwrap();
+            assert_eq!(decoded, expected);
+            assert_eq!(expected.iter().flat_map(encoded).collect::<Vec<_>>(), bytes);
+        }
+    }
+}
@@ -0,0 +1,94 @@
+#!/usr/bin/env python3
+"""Writes the golden wire-format vectors in this directory.
+
+This deliberately shares no code with the Rust crate: every byte here comes
+from the wire format as the assignment (and the docs in format.rs) describe
+it, so the Rust encoder and decoder are checked against something they didn't
+write themselves. The vectors are checked in; only rerun this if the format
+itself changes, and then look hard at the diff.
+
+    python3 generate.py
+"""
+
+from pathlib import Path
+
+HERE = Path(__file__).parent
+
+WANT_GAME, GAME_START, PLAY_CARD, PLAY_RESULT = 0, 1, 2, 3
+GAME_OVER, ERROR, FORFEIT, REMATCH = 4, 5, 6, 7
+JOIN_LOBBY, HELLO, LONG_GAME_START, PLAY_FACE_DOWN = 8, 9, 10, 11
+PING, PONG, SERVER_INFO, SEATED, PLACED = 12, 13, 14, 15, 16
+
+WIN, DRAW, LOSE = 0, 1, 2
+HAND_SIZE = 26
+PLAYER_NAME_LEN = 16
+
+# A card is suit * 13 + rank, with clubs, diamonds, hearts, spades and two
+# through ace each counting up from 0. Only the rank matters in a round.
+RANKS_IN_SUIT = 13
+
+
+def card(rank, suit):
+    return suit * RANKS_IN_SUIT + rank
+
+
+def round_result(mine, theirs):
+    mine, theirs = mine % RANKS_IN_SUIT, theirs % RANKS_IN_SUIT
+    return WIN if mine > theirs else DRAW if mine == theirs else LOSE
+
+
+# Every 7th card, wrapping around: a fixed shuffle that still has a few draws.
+DECK = [(i * 7) % 52 for i in range(52)]
+PLAYER_ONE_HAND = DECK[:HAND_SIZE]
+PLAYER_TWO_HAND = DECK[HAND_SIZE:]
+
+MESSAGES = {
+    "want_game_legacy": [WANT_GAME, 0],
+    "want_game_v3": [WANT_GAME, 3],
+    "game_start": [GAME_START, *PLAYER_ONE_HAND],
+    "long_game_start": [LONG_GAME_START, 3, card(12, 3), card(0, 0), card(5, 2)],
+    "play_card": [PLAY_CARD, card(10, 2)],
+    "play_result_win": [PLAY_RESULT, WIN],
+    "play_result_draw": [PLAY_RESULT, DRAW],
+    "play_result_lose": [PLAY_RESULT, LOSE],
+    "game_over": [GAME_OVER, LOSE, 9, 17],
+    "error": [ERROR, 2],
+    "forfeit": [FORFEIT],
+    "rematch": [REMATCH],
+    "join_lobby": [JOIN_LOBBY, 2, *b"WAR\n"],
+    "hello": [HELLO, *b"alice".ljust(PLAYER_NAME_LEN, b"\0")],
+    "play_face_down": [PLAY_FACE_DOWN, 3],
+    "ping": [PING, 0x2A],
+    "pong": [PONG, 0x2A],
+    # Version 5, with GameOver (bit 0) and lobbies (bit 3).
+    "server_info": [SERVER_INFO, 5, 0b1001, *b"0.1.0".ljust(8, b"\0")],
+    # Seat 2 of 3, then coming 3rd of 4.
+    "seated": [SEATED, 2, 3],
+    "placed": [PLACED, 3, 4],
+}
+
+
+def transcript(mine, theirs):
+    """One legacy player's whole game: what they send, and what they get."""
+    sent = [WANT_GAME, 0]
+    received = [GAME_START, *mine]
+    for my_card, their_card in zip(mine, theirs):
+        sent += [PLAY_CARD, my_card]
+        received += [PLAY_RESULT, round_result(my_card, their_card)]
+    return bytes(sent), bytes(received)
+
+
+def main():
+    for name, message in MESSAGES.items():
+        (HERE / f"{name}.bin").write_bytes(bytes(message))
+    for player, (mine, theirs) in {
+        "player_one": (PLAYER_ONE_HAND, PLAYER_TWO_HAND),
+        "player_two": (PLAYER_TWO_HAND, PLAYER_ONE_HAND),
+    }.items():
+        sent, received = transcript(mine, theirs)
+        (HERE / f"{player}_sent.bin").write_bytes(sent)
+        (HERE / f"{player}_received.bin").write_bytes(received)
+
+
+if __name__ == "__main__":
+    main()